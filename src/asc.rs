@@ -0,0 +1,388 @@
+use std::fmt::Display;
+use std::io::{BufWriter, Write};
+use std::str::FromStr;
+use std::sync::mpsc::Sender;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use crate::device::Listener;
+use crate::frame::{Direct, DirectionFilter, Frame};
+use crate::identifier::Id;
+
+/// The default interval of flushing [`AscLoggerListener`].
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Parse a frame line of `asc` log, the format that `Display` of [`Frame`] outputs.
+///
+/// Returns the absolute timestamp and the frame, the timestamp of frame is set in milliseconds.
+/// `None` is returned for the lines that are not frames, such as headers and comments.
+pub fn parse_line<F>(line: &str) -> Option<(Duration, F)>
+where
+    F: Frame,
+    F::Channel: FromStr {
+    let mut tokens = line.split_whitespace();
+    let timestamp = parse_timestamp(tokens.next()?)?;
+
+    let mut frame = match tokens.next()? {
+        "CANFD" => {
+            let channel = tokens.next()?.parse().ok()?;
+            let direct = parse_direct(tokens.next()?)?;
+            let id = parse_id(tokens.next()?)?;
+            let bitrate_switch = tokens.next()? == "1";
+            let esi = tokens.next()? == "1";
+            let _dlc = tokens.next()?;
+            let length: usize = tokens.next()?.parse().ok()?;
+            let data = parse_data(&mut tokens, length)?;
+
+            let mut frame = F::new(id, &data)?;
+            frame.set_can_fd(true)
+                .set_bitrate_switch(bitrate_switch)
+                .set_esi(esi)
+                .set_channel(channel)
+                .set_direct(direct);
+            frame
+        },
+        channel => {
+            let channel = channel.parse().ok()?;
+            let id = parse_id(tokens.next()?)?;
+            let direct = parse_direct(tokens.next()?)?;
+            let remote = match tokens.next()? {
+                "r" => true,
+                "d" => false,
+                _ => return None,
+            };
+            let length: usize = tokens.next()?.parse().ok()?;
+
+            let mut frame = if remote {
+                F::new_remote(id, length)?
+            }
+            else {
+                F::new(id, &parse_data(&mut tokens, length)?)?
+            };
+            frame.set_channel(channel)
+                .set_direct(direct);
+            frame
+        },
+    };
+    frame.set_timestamp(Some(timestamp.as_millis() as u64));
+
+    Some((timestamp, frame))
+}
+
+/// Parse the timestamp in seconds without the rounding of float.
+fn parse_timestamp(token: &str) -> Option<Duration> {
+    let (secs, fraction) = token.split_once('.').unwrap_or((token, ""));
+    if fraction.len() > 9 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let nanos = match fraction {
+        "" => 0,
+        _ => fraction.parse::<u32>().ok()? * 10u32.pow(9 - fraction.len() as u32),
+    };
+
+    Some(Duration::new(secs.parse().ok()?, nanos))
+}
+
+/// Parse the identifier in hex, the extended one is suffixed with `x`.
+#[inline]
+fn parse_id(token: &str) -> Option<Id> {
+    match token.strip_suffix('x') {
+        Some(v) => Some(Id::from_bits(u32::from_str_radix(v, 16).ok()?, true)),
+        None => Some(Id::from_bits(u32::from_str_radix(token, 16).ok()?, false)),
+    }
+}
+
+#[inline]
+fn parse_direct(token: &str) -> Option<Direct> {
+    match token {
+        "Tx" => Some(Direct::Transmit),
+        "Rx" => Some(Direct::Receive),
+        _ => None,
+    }
+}
+
+#[inline]
+fn parse_data<'a>(tokens: &mut impl Iterator<Item = &'a str>, length: usize) -> Option<Vec<u8>> {
+    tokens.take(length)
+        .map(|v| u8::from_str_radix(v, 16).ok())
+        .collect::<Option<Vec<_>>>()
+        .filter(|data| data.len() == length)
+}
+
+/// Emit parsed frames by a sender, honoring the original inter-frame gaps.
+pub struct Replayer<F> {
+    sender: Sender<F>,
+    speed: f64,
+}
+
+impl<F> Replayer<F> {
+    pub fn new(sender: Sender<F>) -> Self {
+        Self { sender, speed: 1. }
+    }
+
+    /// Replay faster(`speed` > 1) or slower(`speed` < 1) than the original timing.
+    ///
+    /// The gaps are skipped when `speed` is not positive and finite.
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Send the frames in order, sleeping the gap between the timestamps of adjacent frames.
+    ///
+    /// An out-of-order or identical timestamp does not sleep.
+    /// Returns the count of sent frames, the replay stops when sending failed.
+    pub fn replay(&self, frames: impl IntoIterator<Item = (Duration, F)>) -> usize {
+        let mut count = 0;
+        let mut previous: Option<Duration> = None;
+        for (timestamp, frame) in frames {
+            if let Some(previous) = previous {
+                let gap = timestamp.saturating_sub(previous);
+                if !gap.is_zero() && self.speed.is_finite() && self.speed > 0. {
+                    sleep(gap.div_f64(self.speed));
+                }
+            }
+            previous = Some(timestamp);
+
+            if let Err(e) = self.sender.send(frame) {
+                log::warn!("Replayer - transmit failed: {:?}", e);
+                break;
+            }
+            count += 1;
+        }
+
+        count
+    }
+}
+
+/// A listener that writes all frames transmitting and received as `asc` lines, see [`parse_line`].
+///
+/// The lines are buffered and flushed when the flush interval elapses since the last flush,
+/// and when it is dropped.
+pub struct AscLoggerListener<W: Write> {
+    writer: BufWriter<W>,
+    interval: Duration,
+    flushed: Instant,
+    direction: DirectionFilter,
+}
+
+impl<W: Write> AscLoggerListener<W> {
+    /// Write the lines to `writer`, flushed every second.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: BufWriter::new(writer),
+            interval: FLUSH_INTERVAL,
+            flushed: Instant::now(),
+            direction: Default::default(),
+        }
+    }
+
+    /// Flush every `interval`, every line is flushed if zero.
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Write the frames of the directions of `filter` only, both directions by default.
+    pub fn with_direction_filter(mut self, filter: DirectionFilter) -> Self {
+        self.direction = filter;
+        self
+    }
+
+    /// Flush the buffered lines.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.flushed = Instant::now();
+        self.writer.flush()
+    }
+
+    fn write_frame<C, F>(&mut self, frame: &F, direct: Direct)
+    where
+        C: Display + 'static,
+        F: Frame<Channel = C> + Clone + 'static {
+        let mut frame = frame.clone();
+        frame.set_direct(direct);
+        if let Err(e) = writeln!(self.writer, "{}", &frame as &dyn Frame<Channel = C>) {
+            log::warn!("AscLoggerListener - write failed: {:?}", e);
+        }
+    }
+
+    fn flush_elapsed(&mut self) {
+        if self.flushed.elapsed() >= self.interval {
+            if let Err(e) = self.flush() {
+                log::warn!("AscLoggerListener - flush failed: {:?}", e);
+            }
+        }
+    }
+}
+
+impl<C, I, F, W> Listener<C, I, F> for AscLoggerListener<W>
+where
+    C: Display + 'static,
+    F: Frame<Channel = C> + Clone + 'static,
+    W: Write + Send {
+    fn on_frame_transmitting(&mut self, _: C, frame: &F) {
+        if !self.direction.accepts(Direct::Transmit) {
+            return;
+        }
+
+        self.write_frame(frame, Direct::Transmit);
+        self.flush_elapsed();
+    }
+
+    fn on_frame_transmitted(&mut self, _: C, _: I) {}
+
+    fn on_frame_received(&mut self, _: C, frames: &[F]) {
+        if !self.direction.accepts(Direct::Receive) {
+            return;
+        }
+
+        frames.iter()
+            .for_each(|frame| self.write_frame(frame, Direct::Receive));
+        self.flush_elapsed();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+    use std::time::{Duration, Instant};
+    use crate::frame::{Direct, DirectionFilter, Frame};
+    use crate::identifier::Id;
+    use crate::mock::MockFrame;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use crate::device::Listener;
+    use super::{parse_line, AscLoggerListener, Replayer};
+
+    /// The written bytes are shared with the test.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedBuffer {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(String::from)
+                .collect()
+        }
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let mut frame = MockFrame::new(Id::from_bits(0x18DAF110, true), &[0x02, 0x10, 0x01]).unwrap();
+        frame.set_timestamp(Some(1234))
+            .set_channel(1)
+            .set_direct(Direct::Receive);
+        let line = format!("{}", frame);
+        let (timestamp, parsed) = parse_line::<MockFrame>(&line).unwrap();
+        assert_eq!(timestamp, Duration::from_millis(1234));
+        assert_eq!(parsed, frame);
+
+        let mut frame = MockFrame::new(Id::Standard(0x7E0), &[0x55; 12]).unwrap();
+        frame.set_timestamp(Some(10))
+            .set_bitrate_switch(true)
+            .set_direct(Direct::Transmit);
+        let (_, parsed) = parse_line::<MockFrame>(&format!("{}", frame)).unwrap();
+        assert_eq!(parsed, frame);
+
+        let mut frame = MockFrame::new(Id::from_bits(0x18DAF110, true), &[0x55; 12]).unwrap();
+        frame.set_timestamp(Some(20))
+            .set_channel(2)
+            .set_direct(Direct::Receive);
+        let line = format!("{}", frame);
+        assert!(line.contains("18daf110x"));
+        let (_, parsed) = parse_line::<MockFrame>(&line).unwrap();
+        assert!(parsed.is_extended());
+        assert_eq!(parsed, frame);
+
+        let frame = MockFrame::new_remote(Id::Standard(0x123), 2).unwrap();
+        let (_, parsed) = parse_line::<MockFrame>(&format!("{}", frame)).unwrap();
+        assert_eq!(parsed, frame);
+
+        assert!(parse_line::<MockFrame>("date Mon Oct 14 10:00:00 am 2026").is_none());
+        assert!(parse_line::<MockFrame>("").is_none());
+    }
+
+    #[test]
+    fn test_replay() {
+        let frames = [(20, 0x01), (25, 0x02), (10, 0x03), (10, 0x04), (15, 0x05)]
+            .into_iter()
+            .map(|(ms, id)| (Duration::from_millis(ms), MockFrame::new(Id::Standard(id), &[]).unwrap()))
+            .collect::<Vec<_>>();
+
+        let (sender, receiver) = channel();
+        let start = Instant::now();
+        assert_eq!(Replayer::new(sender).with_speed(0.5).replay(frames), 5);
+        // only positive gaps are slept: (5 + 5) / 0.5
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        let ids = receiver.try_iter()
+            .map(|frame| frame.id(false).as_raw())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![0x01, 0x02, 0x03, 0x04, 0x05]);
+    }
+
+    #[test]
+    fn test_asc_logger_listener() {
+        let buffer = SharedBuffer::default();
+        let mut listener = AscLoggerListener::new(buffer.clone());
+
+        let mut request = MockFrame::new(Id::Standard(0x7E0), &[0x02, 0x10, 0x01]).unwrap();
+        request.set_timestamp(Some(1000))
+            .set_channel(1)
+            .set_direct(Direct::Receive);
+        let mut response = MockFrame::new(Id::from_bits(0x18DAF110, true), &[0x02, 0x50, 0x01]).unwrap();
+        response.set_timestamp(Some(1010))
+            .set_channel(1);
+        Listener::<u8, u32, MockFrame>::on_frame_transmitting(&mut listener, 1, &request);
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut listener, 1, &[response.clone()]);
+        // buffered until the interval elapses
+        assert!(buffer.lines().is_empty());
+
+        listener.flush().unwrap();
+        let frames = buffer.lines()
+            .iter()
+            .map(|line| parse_line::<MockFrame>(line).unwrap().1)
+            .collect::<Vec<_>>();
+        request.set_direct(Direct::Transmit);
+        response.set_direct(Direct::Receive);
+        assert_eq!(frames, vec![request.clone(), response]);
+
+        let mut listener = AscLoggerListener::new(buffer.clone())
+            .with_flush_interval(Duration::ZERO);
+        Listener::<u8, u32, MockFrame>::on_frame_transmitting(&mut listener, 1, &request);
+        assert_eq!(buffer.lines().len(), 3);
+    }
+
+    #[test]
+    fn test_asc_logger_direction_filter() {
+        let buffer = SharedBuffer::default();
+        let mut listener = AscLoggerListener::new(buffer.clone())
+            .with_flush_interval(Duration::ZERO)
+            .with_direction_filter(DirectionFilter::RxOnly);
+
+        let mut frame = MockFrame::new(Id::Standard(0x7E0), &[0x02, 0x10, 0x01]).unwrap();
+        frame.set_timestamp(Some(1000))
+            .set_channel(1);
+        Listener::<u8, u32, MockFrame>::on_frame_transmitting(&mut listener, 1, &frame);
+        assert!(buffer.lines().is_empty());
+
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut listener, 1, &[frame.clone()]);
+        frame.set_direct(Direct::Receive);
+        let frames = buffer.lines()
+            .iter()
+            .map(|line| parse_line::<MockFrame>(line).unwrap().1)
+            .collect::<Vec<_>>();
+        assert_eq!(frames, vec![frame]);
+    }
+}
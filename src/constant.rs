@@ -32,3 +32,44 @@ pub const CAN_FRAME_MAX_SIZE: usize = 8;
 pub const CANFD_FRAME_MAX_SIZE: usize = 64;
 /// Default padding value(0b1010_1010).
 pub const DEFAULT_PADDING: u8 = 0xAA;
+/// The max sizeof frame's data of the active build, [`CANFD_FRAME_MAX_SIZE`] with `can-fd`.
+#[cfg(not(feature = "can-fd"))]
+pub const MAX_FRAME_SIZE: usize = CAN_FRAME_MAX_SIZE;
+/// The max sizeof frame's data of the active build, [`CANFD_FRAME_MAX_SIZE`] with `can-fd`.
+#[cfg(feature = "can-fd")]
+pub const MAX_FRAME_SIZE: usize = CANFD_FRAME_MAX_SIZE;
+/// The max ISO-TP payload length of 12-bit first frame length(ISO 15765-2:2004).
+pub const ISO_TP_MAX_LENGTH: usize = 0xFFF;
+
+/// Returns true if the crate was built with the `can-fd` feature.
+#[inline]
+pub const fn is_can_fd_build() -> bool {
+    cfg!(feature = "can-fd")
+}
+
+/// Returns the max payload length that an ISO-TP session writes, 0xFFF(4095) for both CAN and CAN-FD.
+///
+/// The escaped first frame length of `isotp::util` is not limited by it.
+#[inline]
+pub const fn max_isotp_payload() -> usize {
+    ISO_TP_MAX_LENGTH
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        match is_can_fd_build() {
+            true => assert_eq!(MAX_FRAME_SIZE, CANFD_FRAME_MAX_SIZE),
+            false => assert_eq!(MAX_FRAME_SIZE, CAN_FRAME_MAX_SIZE),
+        }
+        assert_eq!(max_isotp_payload(), 4095);
+        #[cfg(feature = "isotp-rs")]
+        {
+            assert_eq!(MAX_FRAME_SIZE, isotp_rs::can::SINGLE_FRAME_SIZE_2004 + 1);
+            assert_eq!(max_isotp_payload(), isotp_rs::can::ISO_TP_MAX_LENGTH_2004);
+        }
+    }
+}
@@ -1,15 +1,432 @@
+mod bridge;
+pub use bridge::Bridge;
+#[cfg(any(test, feature = "test-util"))]
+mod injector;
+#[cfg(any(test, feature = "test-util"))]
+pub use injector::{ErrorInjector, Fault, Target};
+#[cfg(any(test, feature = "test-util"))]
+mod loopback;
+#[cfg(any(test, feature = "test-util"))]
+pub use loopback::LoopbackDevice;
+
 use std::sync::mpsc::{Receiver, Sender};
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::time::{Duration, Instant};
+use crate::frame::Frame;
+
+/// The reason that a frame is not transmitted.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TransmitFailure {
+    /// The controller is bus-off.
+    BusOff,
+    /// The arbitration is lost and the frame is not retransmitted.
+    ArbitrationLost,
+    /// The transmission is aborted, e.g. by the driver or a timeout.
+    Aborted,
+}
 
 pub trait Listener<Channel, Id, Frame>: Send {
     /// Callback when frame transmitting.
     fn on_frame_transmitting(&mut self, channel: Channel, frame: &Frame);
     /// Callback when frame transmit success.
     fn on_frame_transmitted(&mut self, channel: Channel, id: Id);
+    /// Callback when frame transmit failed, e.g. bus-off, the default implementation ignores it.
+    fn on_frame_transmit_failed(&mut self, channel: Channel, id: Id, reason: TransmitFailure) {
+        let _ = (channel, id, reason);
+    }
     /// Callback when frames received.
     fn on_frame_received(&mut self, channel: Channel, frames: &[Frame]);
 }
 
+/// The named listeners in the registration order.
+type Registry<C, I, F> = Vec<(String, Mutex<Box<dyn Listener<C, I, F>>>)>;
+
+/// The received frames that are accumulated in a coalescing window.
+struct Coalescing<C, F> {
+    window: Option<Duration>,
+    /// When the first pending frame is accumulated.
+    started: Option<Instant>,
+    pending: Vec<(C, Vec<F>)>,
+}
+
+impl<C, F> Default for Coalescing<C, F> {
+    fn default() -> Self {
+        Self { window: None, started: None, pending: Vec::new() }
+    }
+}
+
+/// What is done to the frames received while the dispatch is paused, see [`Listeners::pause`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PausePolicy {
+    /// The frames are kept and dispatched when resumed.
+    #[default]
+    Buffer,
+    /// The frames are discarded.
+    Drop,
+}
+
+/// The received frames while the dispatch is paused.
+struct Pause<C, F> {
+    paused: bool,
+    policy: PausePolicy,
+    buffered: Vec<(C, Vec<F>)>,
+}
+
+impl<C, F> Default for Pause<C, F> {
+    fn default() -> Self {
+        Self { paused: false, policy: Default::default(), buffered: Vec::new() }
+    }
+}
+
+/// The transmit and receive frame listeners of a device.
+///
+/// The registry is guarded by its own lock instead of the device's, so listeners can be
+/// registered or unregistered while [`SyncDevice::sync_receive`] or [`AsyncDevice::async_receive`]
+/// is dispatching frames. Cloning it yields a handle to the same registry.
+///
+/// A listener must not (un)register listeners from inside its own callbacks, the dispatch holds
+/// the registry's read lock while calling them.
+///
+/// The listeners are called in the order they are registered, so a listener observes the state
+/// that the listeners registered before it update for the same frames.
+///
+/// The received frames can be coalesced for a window with [`Self::set_coalescing_window`],
+/// see [`Self::coalesce_received`], and the dispatch can be paused by [`Self::pause`].
+pub struct Listeners<C, I, F> {
+    inner: Arc<RwLock<Registry<C, I, F>>>,
+    coalescing: Arc<Mutex<Coalescing<C, F>>>,
+    pause: Arc<Mutex<Pause<C, F>>>,
+    /// The next sequence of received frames, `None` if the sequencing is disabled.
+    rx_sequence: Arc<Mutex<Option<u64>>>,
+}
+
+impl<C, I, F> Clone for Listeners<C, I, F> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+            coalescing: Arc::clone(&self.coalescing),
+            pause: Arc::clone(&self.pause),
+            rx_sequence: Arc::clone(&self.rx_sequence),
+        }
+    }
+}
+
+impl<C, I, F> Default for Listeners<C, I, F> {
+    fn default() -> Self {
+        Self {
+            inner: Default::default(),
+            coalescing: Default::default(),
+            pause: Default::default(),
+            rx_sequence: Default::default(),
+        }
+    }
+}
+
+impl<C, I, F> Listeners<C, I, F> {
+    /// Register a listener after the registered ones, returns `false` if the name is already registered.
+    pub fn register(&self, name: String, listener: Box<dyn Listener<C, I, F>>) -> bool {
+        match self.inner.write() {
+            Ok(mut listeners) => {
+                if listeners.iter().any(|(v, _)| *v == name) {
+                    return false;
+                }
+                listeners.push((name, Mutex::new(listener)));
+                true
+            },
+            Err(_) => {
+                log::warn!("Device - listeners lock is poisoned");
+                false
+            },
+        }
+    }
+
+    /// Unregister a listener, returns `false` if the name is not registered.
+    pub fn unregister(&self, name: &str) -> bool {
+        match self.inner.write() {
+            Ok(mut listeners) => match listeners.iter().position(|(v, _)| v == name) {
+                Some(index) => {
+                    listeners.remove(index);
+                    true
+                },
+                None => false,
+            },
+            Err(_) => {
+                log::warn!("Device - listeners lock is poisoned");
+                false
+            },
+        }
+    }
+
+    /// Unregister all listeners.
+    pub fn unregister_all(&self) -> bool {
+        match self.inner.write() {
+            Ok(mut listeners) => {
+                listeners.clear();
+                true
+            },
+            Err(_) => {
+                log::warn!("Device - listeners lock is poisoned");
+                false
+            },
+        }
+    }
+
+    /// Set the window that the received frames are accumulated for before they are dispatched
+    /// as one batch per channel, `None`(default) dispatches them immediately.
+    ///
+    /// It trades the latency of up to `window` for fewer dispatches on bursts of frames.
+    pub fn set_coalescing_window(&self, window: Option<Duration>) {
+        match self.coalescing.lock() {
+            Ok(mut coalescing) => coalescing.window = window,
+            Err(_) => log::warn!("Device - coalescing lock is poisoned"),
+        }
+    }
+
+    /// Get the coalescing window of received frames.
+    pub fn coalescing_window(&self) -> Option<Duration> {
+        match self.coalescing.lock() {
+            Ok(coalescing) => coalescing.window,
+            Err(_) => {
+                log::warn!("Device - coalescing lock is poisoned");
+                None
+            },
+        }
+    }
+
+    /// Set what is done to the frames received while paused, [`PausePolicy::Buffer`] by default.
+    pub fn set_pause_policy(&self, policy: PausePolicy) {
+        match self.pause.lock() {
+            Ok(mut pause) => pause.policy = policy,
+            Err(_) => log::warn!("Device - pause lock is poisoned"),
+        }
+    }
+
+    /// Pause the dispatch of received frames of [`Self::coalesce_received`] and [`Self::flush_expired`],
+    /// e.g. while reconfiguring the listeners, the transmit callbacks are still dispatched.
+    pub fn pause(&self) {
+        match self.pause.lock() {
+            Ok(mut pause) => pause.paused = true,
+            Err(_) => log::warn!("Device - pause lock is poisoned"),
+        }
+    }
+
+    /// Returns true if the dispatch of received frames is paused.
+    pub fn is_paused(&self) -> bool {
+        match self.pause.lock() {
+            Ok(pause) => pause.paused,
+            Err(_) => {
+                log::warn!("Device - pause lock is poisoned");
+                false
+            },
+        }
+    }
+
+    /// Stamp the received frames with a monotonically increasing sequence across all channels by
+    /// [`Self::stamp_received`], so the arrival order can be reconstructed from the per-channel batches.
+    ///
+    /// The sequence restarts from 0 when it is enabled again.
+    pub fn set_rx_sequencing(&self, enabled: bool) {
+        match self.rx_sequence.lock() {
+            Ok(mut sequence) => *sequence = if enabled { Some(sequence.unwrap_or_default()) } else { None },
+            Err(_) => log::warn!("Device - rx sequence lock is poisoned"),
+        }
+    }
+
+    /// Returns true if the received frames are stamped with sequences.
+    pub fn is_rx_sequencing(&self) -> bool {
+        match self.rx_sequence.lock() {
+            Ok(sequence) => sequence.is_some(),
+            Err(_) => {
+                log::warn!("Device - rx sequence lock is poisoned");
+                false
+            },
+        }
+    }
+
+    /// Get the names of all registered listeners in the registration order.
+    pub fn names(&self) -> Vec<String> {
+        match self.inner.read() {
+            Ok(listeners) => listeners.iter()
+                .map(|(name, _)| name.clone())
+                .collect(),
+            Err(_) => {
+                log::warn!("Device - listeners lock is poisoned");
+                Vec::new()
+            },
+        }
+    }
+
+    fn dispatch(&self, mut callback: impl FnMut(&mut Box<dyn Listener<C, I, F>>)) {
+        match self.inner.read() {
+            Ok(listeners) => {
+                for (name, listener) in listeners.iter() {
+                    match listener.lock() {
+                        Ok(mut listener) => callback(&mut listener),
+                        Err(_) => log::warn!("Device - listener: {} is poisoned", name),
+                    }
+                }
+            },
+            Err(_) => log::warn!("Device - listeners lock is poisoned"),
+        }
+    }
+}
+
+impl<C: Clone, I: Clone, F> Listeners<C, I, F> {
+    /// Dispatch [`Listener::on_frame_transmitting`] to all listeners.
+    pub fn on_frame_transmitting(&self, channel: C, frame: &F) {
+        self.dispatch(|listener| listener.on_frame_transmitting(channel.clone(), frame));
+    }
+
+    /// Dispatch [`Listener::on_frame_transmitted`] to all listeners.
+    pub fn on_frame_transmitted(&self, channel: C, id: I) {
+        self.dispatch(|listener| listener.on_frame_transmitted(channel.clone(), id.clone()));
+    }
+
+    /// Dispatch [`Listener::on_frame_transmit_failed`] to all listeners.
+    pub fn on_frame_transmit_failed(&self, channel: C, id: I, reason: TransmitFailure) {
+        self.dispatch(|listener| listener.on_frame_transmit_failed(channel.clone(), id.clone(), reason));
+    }
+
+    /// Dispatch [`Listener::on_frame_received`] to all listeners.
+    pub fn on_frame_received(&self, channel: C, frames: &[F]) {
+        self.dispatch(|listener| listener.on_frame_received(channel.clone(), frames));
+    }
+
+    /// Dispatch all pending frames of the coalescing window, whether the window elapsed or not.
+    ///
+    /// Devices should call it when closed, so that no received frames are lost.
+    pub fn flush_received(&self) {
+        let pending = match self.coalescing.lock() {
+            Ok(mut coalescing) => {
+                coalescing.started = None;
+                std::mem::take(&mut coalescing.pending)
+            },
+            Err(_) => {
+                log::warn!("Device - coalescing lock is poisoned");
+                return;
+            },
+        };
+
+        for (channel, frames) in pending {
+            self.on_frame_received(channel, &frames);
+        }
+    }
+
+    /// Dispatch the pending frames if the coalescing window elapsed.
+    ///
+    /// Devices should call it every iteration of the receive loop, even if no frames are received.
+    pub fn flush_expired(&self) {
+        if self.is_paused() {
+            return;
+        }
+
+        let expired = match self.coalescing.lock() {
+            Ok(coalescing) => match (coalescing.window, coalescing.started) {
+                (Some(window), Some(started)) => started.elapsed() >= window,
+                // the window is disabled while frames are pending
+                (None, Some(_)) => true,
+                (_, None) => false,
+            },
+            Err(_) => {
+                log::warn!("Device - coalescing lock is poisoned");
+                return;
+            },
+        };
+
+        if expired {
+            self.flush_received();
+        }
+    }
+}
+
+impl<C: Clone + PartialEq, I: Clone, F> Listeners<C, I, F> {
+    /// Accumulate the received frames in the coalescing window, the frames of a channel are
+    /// dispatched as one batch by [`Self::on_frame_received`] when the window elapsed.
+    ///
+    /// The frames are dispatched immediately if no window is set.
+    pub fn coalesce_received(&self, channel: C, frames: Vec<F>) {
+        if frames.is_empty() {
+            return self.flush_expired();
+        }
+
+        let frames = match self.pause.lock() {
+            Ok(mut pause) if pause.paused => {
+                if pause.policy == PausePolicy::Buffer {
+                    match pause.buffered.iter_mut().find(|(c, _)| *c == channel) {
+                        Some((_, batch)) => batch.extend(frames),
+                        None => pause.buffered.push((channel, frames)),
+                    }
+                }
+                return;
+            },
+            Ok(_) => frames,
+            Err(_) => {
+                log::warn!("Device - pause lock is poisoned");
+                frames
+            },
+        };
+
+        match self.coalescing.lock() {
+            Ok(mut coalescing) => {
+                if coalescing.window.is_none() && coalescing.pending.is_empty() {
+                    drop(coalescing);
+                    return self.on_frame_received(channel, &frames);
+                }
+
+                coalescing.started.get_or_insert_with(Instant::now);
+                match coalescing.pending.iter_mut().find(|(c, _)| *c == channel) {
+                    Some((_, batch)) => batch.extend(frames),
+                    None => coalescing.pending.push((channel, frames)),
+                }
+            },
+            Err(_) => {
+                log::warn!("Device - coalescing lock is poisoned");
+                return;
+            },
+        }
+
+        self.flush_expired();
+    }
+}
+
+impl<C: Clone + PartialEq, I: Clone, F> Listeners<C, I, F> {
+    /// Resume the dispatch paused by [`Self::pause`], the buffered frames are dispatched first.
+    pub fn resume(&self) {
+        let buffered = match self.pause.lock() {
+            Ok(mut pause) => {
+                pause.paused = false;
+                std::mem::take(&mut pause.buffered)
+            },
+            Err(_) => {
+                log::warn!("Device - pause lock is poisoned");
+                return;
+            },
+        };
+
+        for (channel, frames) in buffered {
+            self.coalesce_received(channel, frames);
+        }
+        self.flush_expired();
+    }
+}
+
+impl<C, I, F: Frame> Listeners<C, I, F> {
+    /// Stamp the frames in order of arrival with [`Frame::set_rx_sequence`] if the sequencing is enabled.
+    ///
+    /// Devices should call it before the frames are batched per channel.
+    pub fn stamp_received(&self, frames: &mut [F]) {
+        match self.rx_sequence.lock() {
+            Ok(mut sequence) => if let Some(next) = sequence.as_mut() {
+                for frame in frames {
+                    frame.set_rx_sequence(Some(*next));
+                    *next += 1;
+                }
+            },
+            Err(_) => log::warn!("Device - rx sequence lock is poisoned"),
+        }
+    }
+}
+
 pub trait SyncDevice {
     type Device;
     type Channel;
@@ -19,19 +436,53 @@ pub trait SyncDevice {
     fn new(device: Self::Device) -> Self;
     /// Get the sender for transmit frame.
     fn sender(&self) -> Sender<Self::Frame>;
+    /// Get the transmit and receive frame listeners.
+    fn listeners(&self) -> Listeners<Self::Channel, Self::Id, Self::Frame>;
     /// Register transmit and receive frame listener.
     fn register_listener(
-        &mut self,
+        &self,
         name: String,
         listener: Box<dyn Listener<Self::Channel, Self::Id, Self::Frame>>,
-    ) -> bool;
+    ) -> bool {
+        self.listeners().register(name, listener)
+    }
     /// Unregister transmit and receive frame listener.
-    fn unregister_listener(&mut self, name: String) -> bool;
+    fn unregister_listener(&self, name: String) -> bool {
+        self.listeners().unregister(&name)
+    }
     /// Unregister all transmit and receive frame listeners.
-    fn unregister_all(&mut self) -> bool;
+    fn unregister_all(&self) -> bool {
+        self.listeners().unregister_all()
+    }
     /// Get all transmit and receive frame listener's names.
-    fn listener_names(&self) -> Vec<String>;
-    /// transmit loop.
+    fn listener_names(&self) -> Vec<String> {
+        self.listeners().names()
+    }
+    /// Pause the dispatch of received frames, see [`Listeners::pause`].
+    fn pause_receive(&self) {
+        self.listeners().pause()
+    }
+    /// Resume the dispatch of received frames, see [`Listeners::resume`].
+    fn resume_receive(&self)
+    where
+        Self::Channel: Clone + PartialEq,
+        Self::Id: Clone {
+        self.listeners().resume()
+    }
+
+    /// Get the reference instant of transmitted frame timestamps, usually when the device opened.
+    fn timebase(&self) -> Instant;
+
+    /// Stamp an outgoing frame with the milliseconds elapsed since [`Self::timebase`]
+    /// right before sending, unless it is stamped already, see [`Frame::is_stamped`].
+    fn stamp(&self, frame: &mut Self::Frame)
+    where
+        Self::Frame: Frame {
+        if !frame.is_stamped() {
+            frame.set_timestamp(Some(self.timebase().elapsed().as_millis() as u64));
+        }
+    }
+    /// transmit loop, every frame is [`Self::stamp`]ed before sending.
     fn sync_transmit(device: MutexGuard<Self>,
                      interval_us: u64,
                      stopper: Arc<Mutex<Receiver<()>>>,
@@ -56,19 +507,53 @@ pub trait AsyncDevice {
     fn new(device: Self::Device) -> Self;
     /// Get the sender for transmit frame.
     fn sender(&self) -> Sender<Self::Frame>;
+    /// Get the transmit and receive frame listeners.
+    fn listeners(&self) -> Listeners<Self::Channel, Self::Id, Self::Frame>;
     /// Register transmit and receive frame listener.
     fn register_listener(
-        &mut self,
+        &self,
         name: String,
         listener: Box<dyn Listener<Self::Channel, Self::Id, Self::Frame>>,
-    ) -> bool;
+    ) -> bool {
+        self.listeners().register(name, listener)
+    }
     /// Unregister transmit and receive frame listener.
-    fn unregister_listener(&mut self, name: String) -> bool;
+    fn unregister_listener(&self, name: String) -> bool {
+        self.listeners().unregister(&name)
+    }
     /// Unregister all transmit and receive frame listeners.
-    fn unregister_all(&mut self) -> bool;
+    fn unregister_all(&self) -> bool {
+        self.listeners().unregister_all()
+    }
     /// Get all transmit and receive frame listener's names.
-    fn listener_names(&self) -> Vec<String>;
-    /// transmit loop.
+    fn listener_names(&self) -> Vec<String> {
+        self.listeners().names()
+    }
+    /// Pause the dispatch of received frames, see [`Listeners::pause`].
+    fn pause_receive(&self) {
+        self.listeners().pause()
+    }
+    /// Resume the dispatch of received frames, see [`Listeners::resume`].
+    fn resume_receive(&self)
+    where
+        Self::Channel: Clone + PartialEq,
+        Self::Id: Clone {
+        self.listeners().resume()
+    }
+
+    /// Get the reference instant of transmitted frame timestamps, usually when the device opened.
+    fn timebase(&self) -> Instant;
+
+    /// Stamp an outgoing frame with the milliseconds elapsed since [`Self::timebase`]
+    /// right before sending, unless it is stamped already, see [`Frame::is_stamped`].
+    fn stamp(&self, frame: &mut Self::Frame)
+    where
+        Self::Frame: Frame {
+        if !frame.is_stamped() {
+            frame.set_timestamp(Some(self.timebase().elapsed().as_millis() as u64));
+        }
+    }
+    /// transmit loop, every frame is [`Self::stamp`]ed before sending.
     fn async_transmit(device: Arc<Mutex<Self>>,
                       interval_us: u64,
                       stopper: Arc<Mutex<Receiver<()>>>,
@@ -83,3 +568,121 @@ pub trait AsyncDevice {
     /// Close the device and stop transmit and receive loop.
     fn close(&mut self) -> impl std::future::Future<Output = ()> + Send;
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use super::*;
+
+    struct Counter(Arc<AtomicUsize>);
+
+    impl Listener<u8, u32, u32> for Counter {
+        fn on_frame_transmitting(&mut self, _: u8, _: &u32) {}
+        fn on_frame_transmitted(&mut self, _: u8, _: u32) {}
+        fn on_frame_received(&mut self, _: u8, frames: &[u32]) {
+            self.0.fetch_add(frames.len(), Ordering::SeqCst);
+        }
+    }
+
+    struct Batches(Arc<Mutex<Vec<usize>>>);
+
+    impl Listener<u8, u32, u32> for Batches {
+        fn on_frame_transmitting(&mut self, _: u8, _: &u32) {}
+        fn on_frame_transmitted(&mut self, _: u8, _: u32) {}
+        fn on_frame_received(&mut self, _: u8, frames: &[u32]) {
+            self.0.lock().unwrap().push(frames.len());
+        }
+    }
+
+    #[test]
+    fn test_coalesce_received() {
+        let listeners = Listeners::<u8, u32, u32>::default();
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        assert!(listeners.register("batches".into(), Box::new(Batches(batches.clone()))));
+
+        // dispatched immediately without a window
+        listeners.coalesce_received(0, vec![0, 1]);
+        assert_eq!(*batches.lock().unwrap(), [2]);
+
+        let window = Duration::from_millis(50);
+        listeners.set_coalescing_window(Some(window));
+        assert_eq!(listeners.coalescing_window(), Some(window));
+        for frame in 0..10 {
+            listeners.coalesce_received(0, vec![frame]);
+        }
+        listeners.coalesce_received(1, vec![10, 11]);
+        listeners.flush_expired();
+        assert_eq!(*batches.lock().unwrap(), [2]);
+
+        thread::sleep(window);
+        listeners.coalesce_received(0, Vec::new());
+        assert_eq!(*batches.lock().unwrap(), [2, 10, 2]);
+
+        listeners.coalesce_received(0, vec![12]);
+        listeners.flush_received();
+        assert_eq!(*batches.lock().unwrap(), [2, 10, 2, 1]);
+    }
+
+    struct Ordered(&'static str, Arc<Mutex<Vec<(&'static str, u32)>>>);
+
+    impl Listener<u8, u32, u32> for Ordered {
+        fn on_frame_transmitting(&mut self, _: u8, _: &u32) {}
+        fn on_frame_transmitted(&mut self, _: u8, _: u32) {}
+        fn on_frame_received(&mut self, _: u8, frames: &[u32]) {
+            let mut calls = self.1.lock().unwrap();
+            frames.iter().for_each(|&frame| calls.push((self.0, frame)));
+        }
+    }
+
+    #[test]
+    fn test_dispatch_order() {
+        let listeners = Listeners::<u8, u32, u32>::default();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let names = ["zeta", "alpha", "mu"];
+        for name in names {
+            assert!(listeners.register(name.into(), Box::new(Ordered(name, calls.clone()))));
+        }
+        assert_eq!(listeners.names(), names);
+
+        for frame in 0..100 {
+            listeners.on_frame_received(0, &[frame]);
+        }
+        let expected = (0..100)
+            .flat_map(|frame| names.map(|name| (name, frame)))
+            .collect::<Vec<_>>();
+        assert_eq!(*calls.lock().unwrap(), expected);
+
+        // the order of the rest is kept
+        assert!(listeners.unregister("alpha"));
+        assert!(listeners.register("alpha".into(), Box::new(Ordered("alpha", calls.clone()))));
+        assert_eq!(listeners.names(), ["zeta", "mu", "alpha"]);
+    }
+
+    #[test]
+    fn test_register_while_receiving() {
+        let listeners = Listeners::<u8, u32, u32>::default();
+        let count = Arc::new(AtomicUsize::new(0));
+        assert!(listeners.register("first".into(), Box::new(Counter(count.clone()))));
+        assert!(!listeners.register("first".into(), Box::new(Counter(count.clone()))));
+
+        let receiver = listeners.clone();
+        let handle = thread::spawn(move || {
+            for frame in 0..1000 {
+                receiver.on_frame_received(0, &[frame]);
+            }
+        });
+
+        for index in 0..100 {
+            let name = format!("dynamic-{}", index);
+            assert!(listeners.register(name.clone(), Box::new(Counter(count.clone()))));
+            assert!(listeners.unregister(&name));
+        }
+        handle.join().unwrap();
+
+        assert_eq!(listeners.names(), vec!["first".to_owned()]);
+        assert!(count.load(Ordering::SeqCst) >= 1000);
+        assert!(listeners.unregister_all());
+        assert!(listeners.names().is_empty());
+    }
+}
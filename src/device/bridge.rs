@@ -0,0 +1,197 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use crate::device::Listener;
+use crate::frame::{Direct, Frame};
+use crate::identifier::Id;
+
+/// The max count of forwarded frames that are remembered for loop detection.
+const ORIGINATED_MAX_SIZE: usize = 64;
+
+type Originated<C> = Arc<Mutex<VecDeque<(C, u32, Vec<u8>)>>>;
+type Filter<F> = Box<dyn Fn(&F) -> bool + Send>;
+type Translate = Box<dyn Fn(Id) -> Id + Send>;
+
+/// A listener that forwards the frames received on one channel to the sender of another one.
+///
+/// The frames forwarded by a bridge, or by its [`Self::reverse`] bridge, are remembered and
+/// not forwarded again when they are received back, so a pair of bridges does not loop.
+pub struct Bridge<C, F> {
+    from: C,
+    to: C,
+    sender: Sender<F>,
+    filter: Option<Filter<F>>,
+    translate: Option<Translate>,
+    originated: Originated<C>,
+}
+
+impl<C, F> Bridge<C, F>
+where
+    C: Clone + PartialEq,
+    F: Frame<Channel = C> + Clone {
+    /// Forward the frames received on channel `from` to channel `to` by `sender`.
+    pub fn new(from: C, to: C, sender: Sender<F>) -> Self {
+        Self {
+            from,
+            to,
+            sender,
+            filter: None,
+            translate: None,
+            originated: Default::default(),
+        }
+    }
+
+    /// Only forward the frames accepted by `filter`.
+    pub fn with_filter(mut self, filter: impl Fn(&F) -> bool + Send + 'static) -> Self {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Translate the identifier of forwarded frames.
+    pub fn with_translation(mut self, translate: impl Fn(Id) -> Id + Send + 'static) -> Self {
+        self.translate = Some(Box::new(translate));
+        self
+    }
+
+    /// Create the bridge of the opposite direction that forwards by `sender`.
+    ///
+    /// The filter and translation are not copied.
+    pub fn reverse(&self, sender: Sender<F>) -> Self {
+        Self {
+            from: self.to.clone(),
+            to: self.from.clone(),
+            sender,
+            filter: None,
+            translate: None,
+            originated: Arc::clone(&self.originated),
+        }
+    }
+
+    fn forward(&self, frame: &F) -> Option<F> {
+        let mut result = match &self.translate {
+            Some(translate) => {
+                let id = translate(frame.can_id());
+                let mut result = if frame.is_remote() {
+                    F::new_remote(id, frame.length())?
+                }
+                else {
+                    F::new(id, frame.data())?
+                };
+                result.set_can_fd(frame.is_can_fd())
+                    .set_bitrate_switch(frame.is_bitrate_switch())
+                    .set_esi(frame.is_esi())
+                    .set_channel(self.to.clone());
+                result
+            },
+            None => frame.with_channel(self.to.clone()),
+        };
+        result.set_direct(Direct::Transmit);
+
+        Some(result)
+    }
+
+    /// Returns true if the frame is forwarded by this bridge or its reverse bridge.
+    fn is_originated(&self, channel: &C, frame: &F) -> bool {
+        match self.originated.lock() {
+            Ok(mut originated) => {
+                let raw_id = frame.can_id().as_raw();
+                match originated.iter()
+                    .position(|(c, id, data)| c == channel && *id == raw_id && data == frame.data()) {
+                    Some(index) => {
+                        originated.remove(index);
+                        true
+                    },
+                    None => false,
+                }
+            },
+            Err(_) => false,
+        }
+    }
+
+    fn remember(&self, frame: &F) {
+        if let Ok(mut originated) = self.originated.lock() {
+            if originated.len() >= ORIGINATED_MAX_SIZE {
+                originated.pop_front();
+            }
+            originated.push_back((frame.channel(), frame.can_id().as_raw(), frame.data().to_vec()));
+        }
+    }
+}
+
+impl<C, I, F> Listener<C, I, F> for Bridge<C, F>
+where
+    C: Clone + PartialEq + Send,
+    F: Frame<Channel = C> + Clone + Send {
+    fn on_frame_transmitting(&mut self, _: C, _: &F) {}
+
+    fn on_frame_transmitted(&mut self, _: C, _: I) {}
+
+    fn on_frame_received(&mut self, channel: C, frames: &[F]) {
+        if channel != self.from {
+            return;
+        }
+
+        for frame in frames {
+            if self.is_originated(&channel, frame) {
+                continue;
+            }
+            if let Some(filter) = &self.filter {
+                if !filter(frame) {
+                    continue;
+                }
+            }
+
+            match self.forward(frame) {
+                Some(frame) => {
+                    self.remember(&frame);
+                    if let Err(e) = self.sender.send(frame) {
+                        log::warn!("Bridge - forward failed: {:?}", e);
+                    }
+                },
+                None => log::warn!("Bridge - convert frame failed"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::device::{Bridge, LoopbackDevice, SyncDevice};
+    use crate::frame::Frame;
+    use crate::identifier::Id;
+    use crate::mock::{MockFrame, MockListener};
+
+    fn frame(channel: u8, id: u32) -> MockFrame {
+        let mut frame = MockFrame::new(Id::from_bits(id, false), &[0x01, 0x02]).unwrap();
+        frame.set_channel(channel);
+        frame
+    }
+
+    #[test]
+    fn test_bridge() {
+        let powertrain = LoopbackDevice::<u8, MockFrame>::new(());
+        let body = LoopbackDevice::<u8, MockFrame>::new(());
+        let bridge = Bridge::new(0, 1, body.sender())
+            .with_filter(|f: &MockFrame| f.id(false).as_raw() & 0x700 == 0x700)
+            .with_translation(|id| Id::from_bits(id.as_raw() - 0x100, false));
+        let reverse = bridge.reverse(powertrain.sender());
+        assert!(powertrain.register_listener("bridge".into(), Box::new(bridge)));
+        assert!(body.register_listener("bridge".into(), Box::new(reverse)));
+        let recorder = MockListener::default();
+        assert!(body.register_listener("recorder".into(), Box::new(recorder.clone())));
+
+        powertrain.inject(frame(0, 0x7E0));
+        powertrain.inject(frame(0, 0x123));
+        powertrain.poll();
+        // loops back as received on channel 1 of body
+        assert_eq!(body.poll(), 1);
+        // not forwarded back to the powertrain
+        assert_eq!(powertrain.poll(), 0);
+
+        let received = recorder.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].channel(), 1);
+        assert_eq!(received[0].id(false), Id::Standard(0x6E0));
+        assert_eq!(received[0].data(), &[0x01, 0x02]);
+    }
+}
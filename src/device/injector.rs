@@ -0,0 +1,76 @@
+use std::time::Duration;
+use crate::device::TransmitFailure;
+
+/// The transmitted frames that a rule of [`ErrorInjector`] applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// The n-th transmitted frame, counted from 1.
+    Nth(usize),
+    /// All frames of the raw identifier.
+    Id(u32),
+}
+
+/// The error that is injected into a transmitted frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The frame is lost on the bus, the transmitter is still notified of the transmission.
+    Drop,
+    /// The data byte at `index` is XORed with `mask`, nothing changed if out of range.
+    Corrupt { index: usize, mask: u8 },
+    /// The frame is transmitted after the delay, the frames after it are delayed too.
+    Delay(Duration),
+    /// The frame is not transmitted, the transmitter is notified of the failure instead.
+    TransmitFailed(TransmitFailure),
+}
+
+/// Deterministic fault rules of the transmitted frames of [`LoopbackDevice`](crate::device::LoopbackDevice).
+#[derive(Debug, Clone, Default)]
+pub struct ErrorInjector {
+    rules: Vec<(Target, Fault)>,
+    transmitted: usize,
+}
+
+impl ErrorInjector {
+    /// Add a rule, all the faults of the matched rules are applied in order of addition.
+    pub fn with_rule(mut self, target: Target, fault: Fault) -> Self {
+        self.rules.push((target, fault));
+        self
+    }
+
+    /// The count of frames that are transmitted(including the dropped ones).
+    #[inline]
+    pub fn transmitted(&self) -> usize {
+        self.transmitted
+    }
+
+    /// Count a transmitted frame of `id`, returns the faults of it.
+    pub(crate) fn inject(&mut self, id: u32) -> Vec<Fault> {
+        self.transmitted += 1;
+        let nth = self.transmitted;
+        self.rules.iter()
+            .filter(|(target, _)| match *target {
+                Target::Nth(v) => v == nth,
+                Target::Id(v) => v == id,
+            })
+            .map(|&(_, fault)| fault)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+
+    #[test]
+    fn test_inject() {
+        let mut injector = ErrorInjector::default()
+            .with_rule(Target::Nth(2), Fault::Drop)
+            .with_rule(Target::Id(0x7E0), Fault::Delay(Duration::from_millis(1)));
+
+        assert!(injector.inject(0x7E8).is_empty());
+        assert_eq!(injector.inject(0x7E8), [Fault::Drop]);
+        assert_eq!(injector.inject(0x7E0), [Fault::Delay(Duration::from_millis(1))]);
+        assert_eq!(injector.transmitted(), 3);
+    }
+}
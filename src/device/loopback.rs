@@ -0,0 +1,390 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, sleep, JoinHandle};
+use std::time::{Duration, Instant};
+use crate::device::{ErrorInjector, Fault, Listeners, SyncDevice};
+use crate::frame::{Direct, Frame};
+
+type Loop<D> = fn(MutexGuard<D>, u64, Arc<Mutex<Receiver<()>>>);
+
+/// A device that receives every transmitted frame back, like a bus without other nodes.
+///
+/// It is driven either by the [`SyncDevice`] loops, or step by step with [`Self::transmit`],
+/// [`Self::receive`] and [`Self::poll`] for deterministic tests. Frames from the "bus" can be
+/// simulated with [`Self::inject`], and the transmitted frames can be dropped, corrupted
+/// or delayed by [`Self::with_error_injector`].
+#[derive(Clone)]
+pub struct LoopbackDevice<C, F> {
+    sender: Sender<F>,
+    receiver: Arc<Mutex<Receiver<F>>>,
+    received: Arc<Mutex<VecDeque<F>>>,
+    listeners: Listeners<C, u32, F>,
+    stoppers: Arc<Mutex<Vec<Sender<()>>>>,
+    handles: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    timebase: Instant,
+    injector: Arc<Mutex<ErrorInjector>>,
+}
+
+impl<C, F> LoopbackDevice<C, F>
+where
+    C: Clone + PartialEq + Send + 'static,
+    F: Frame<Channel = C> + Clone + Send + 'static {
+    /// Apply the fault rules of `injector` to the transmitted frames.
+    pub fn with_error_injector(self, injector: ErrorInjector) -> Self {
+        match self.injector.lock() {
+            Ok(mut v) => *v = injector,
+            Err(_) => log::warn!("Loopback - error injector is poisoned"),
+        }
+        self
+    }
+
+    /// Simulate a frame received from the bus.
+    pub fn inject(&self, frame: F) {
+        match self.received.lock() {
+            Ok(mut received) => received.push_back(frame),
+            Err(_) => log::warn!("Loopback - receive queue is poisoned"),
+        }
+    }
+
+    /// Transmit all pending frames and loop them back to the receive queue.
+    ///
+    /// Returns the count of transmitted frames.
+    pub fn transmit(&self) -> usize {
+        let frames = match self.receiver.lock() {
+            Ok(receiver) => receiver.try_iter().collect::<Vec<_>>(),
+            Err(_) => {
+                log::warn!("Loopback - transmit queue is poisoned");
+                return 0;
+            },
+        };
+
+        let count = frames.len();
+        for mut frame in frames {
+            let id = frame.can_id().as_raw();
+            let faults = match self.injector.lock() {
+                Ok(mut injector) => injector.inject(id),
+                Err(_) => Vec::new(),
+            };
+
+            let (mut dropped, mut failure) = (false, None);
+            for fault in faults {
+                match fault {
+                    Fault::Drop => dropped = true,
+                    Fault::TransmitFailed(reason) => failure = Some(reason),
+                    Fault::Corrupt { index, mask } => {
+                        if let Some(corrupted) = Self::corrupt(&frame, index, mask) {
+                            frame = corrupted;
+                        }
+                    },
+                    Fault::Delay(delay) => sleep(delay),
+                }
+            }
+
+            self.stamp(&mut frame);
+            let channel = frame.channel();
+            self.listeners.on_frame_transmitting(channel.clone(), &frame);
+            if let Some(reason) = failure {
+                self.listeners.on_frame_transmit_failed(channel, id, reason);
+                continue;
+            }
+            self.listeners.on_frame_transmitted(channel, id);
+
+            if !dropped {
+                frame.set_direct(Direct::Receive);
+                self.inject(frame);
+            }
+        }
+
+        count
+    }
+
+    /// Dispatch all received frames to the listeners, frames are batched per channel.
+    ///
+    /// With a coalescing window of [`Listeners::set_coalescing_window`], the frames are
+    /// accumulated until the window elapsed.
+    ///
+    /// Returns the count of received frames.
+    pub fn receive(&self) -> usize {
+        let mut frames = match self.received.lock() {
+            Ok(mut received) => received.drain(..).collect::<Vec<_>>(),
+            Err(_) => {
+                log::warn!("Loopback - receive queue is poisoned");
+                return 0;
+            },
+        };
+
+        self.listeners.stamp_received(&mut frames);
+        let count = frames.len();
+        let mut batches: Vec<(C, Vec<F>)> = Vec::new();
+        for frame in frames {
+            let channel = frame.channel();
+            match batches.iter_mut().find(|(c, _)| *c == channel) {
+                Some((_, batch)) => batch.push(frame),
+                None => batches.push((channel, vec![frame])),
+            }
+        }
+        for (channel, frames) in batches {
+            self.listeners.coalesce_received(channel, frames);
+        }
+        self.listeners.flush_expired();
+
+        count
+    }
+
+    /// [`Self::transmit`] then [`Self::receive`], returns the count of received frames.
+    pub fn poll(&self) -> usize {
+        self.transmit();
+        self.receive()
+    }
+
+    /// Rebuild the frame with the data byte at `index` XORed with `mask`.
+    fn corrupt(frame: &F, index: usize, mask: u8) -> Option<F> {
+        let mut data = frame.data().to_vec();
+        *data.get_mut(index)? ^= mask;
+
+        let mut corrupted = F::new(frame.can_id(), &data)?;
+        corrupted.set_timestamp(Some(frame.timestamp()))
+            .set_can_fd(frame.is_can_fd())
+            .set_direct(frame.direct())
+            .set_bitrate_switch(frame.is_bitrate_switch())
+            .set_error_frame(frame.is_error_frame())
+            .set_esi(frame.is_esi())
+            .set_channel(frame.channel());
+        Some(corrupted)
+    }
+
+    fn run(&self, interval_us: u64, stopper: Arc<Mutex<Receiver<()>>>, step: impl Fn(&Self) -> usize) {
+        loop {
+            match stopper.lock() {
+                Ok(stopper) => if stopper.try_recv().is_ok() {
+                    break;
+                },
+                Err(_) => break,
+            }
+
+            step(self);
+            sleep(Duration::from_micros(interval_us));
+        }
+    }
+}
+
+impl<C, F> SyncDevice for LoopbackDevice<C, F>
+where
+    C: Clone + PartialEq + Send + 'static,
+    F: Frame<Channel = C> + Clone + Send + 'static {
+    type Device = ();
+    type Channel = C;
+    type Id = u32;
+    type Frame = F;
+
+    fn new(_: Self::Device) -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            sender,
+            receiver: Arc::new(Mutex::new(receiver)),
+            received: Default::default(),
+            listeners: Default::default(),
+            stoppers: Default::default(),
+            handles: Default::default(),
+            timebase: Instant::now(),
+            injector: Default::default(),
+        }
+    }
+
+    fn sender(&self) -> Sender<Self::Frame> {
+        self.sender.clone()
+    }
+
+    fn listeners(&self) -> Listeners<Self::Channel, Self::Id, Self::Frame> {
+        self.listeners.clone()
+    }
+
+    fn timebase(&self) -> Instant {
+        self.timebase
+    }
+
+    fn sync_transmit(device: MutexGuard<Self>, interval_us: u64, stopper: Arc<Mutex<Receiver<()>>>) {
+        let device = device.clone();
+        device.run(interval_us, stopper, Self::transmit);
+    }
+
+    fn sync_receive(device: MutexGuard<Self>, interval_us: u64, stopper: Arc<Mutex<Receiver<()>>>) {
+        let device = device.clone();
+        device.run(interval_us, stopper, Self::receive);
+    }
+
+    fn sync_start(&mut self, interval_us: u64) {
+        let loops: [Loop<Self>; 2] = [Self::sync_transmit, Self::sync_receive];
+        for run in loops {
+            let (stopper, receiver) = channel();
+            let device = Mutex::new(self.clone());
+            let handle = thread::spawn(move || {
+                if let Ok(device) = device.lock() {
+                    run(device, interval_us, Arc::new(Mutex::new(receiver)));
+                }
+            });
+
+            if let (Ok(mut stoppers), Ok(mut handles)) = (self.stoppers.lock(), self.handles.lock()) {
+                stoppers.push(stopper);
+                handles.push(handle);
+            }
+        }
+    }
+
+    fn close(&mut self) {
+        if let Ok(mut stoppers) = self.stoppers.lock() {
+            for stopper in stoppers.drain(..) {
+                let _ = stopper.send(());
+            }
+        }
+        if let Ok(mut handles) = self.handles.lock() {
+            for handle in handles.drain(..) {
+                let _ = handle.join();
+            }
+        }
+        self.listeners.flush_received();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::sleep;
+    use std::time::Duration;
+    use crate::device::{ErrorInjector, Fault, LoopbackDevice, PausePolicy, SyncDevice, Target};
+    use crate::frame::Frame;
+    use crate::identifier::Id;
+    use crate::mock::{MockFrame, MockListener};
+
+    #[test]
+    fn test_transmit_timestamp() {
+        let device = LoopbackDevice::<u8, MockFrame>::new(());
+        let recorder = MockListener::default();
+        assert!(device.register_listener("recorder".into(), Box::new(recorder.clone())));
+
+        let sender = device.sender();
+        for _ in 0..3 {
+            sleep(Duration::from_millis(2));
+            sender.send(MockFrame::new(Id::Standard(0x7E0), &[0x01]).unwrap()).unwrap();
+            device.transmit();
+        }
+        let mut stamped = MockFrame::new(Id::Standard(0x7E0), &[0x02]).unwrap();
+        stamped.set_timestamp(Some(1));
+        sender.send(stamped).unwrap();
+        device.transmit();
+        let mut zero = MockFrame::new(Id::Standard(0x7E0), &[0x03]).unwrap();
+        zero.set_timestamp(Some(0));
+        assert!(!zero.is_stamped());
+        sender.send(zero).unwrap();
+        device.transmit();
+
+        let timestamps = recorder.transmitting.lock().unwrap()
+            .iter()
+            .map(|frame| frame.timestamp())
+            .collect::<Vec<_>>();
+        assert_eq!(timestamps.len(), 5);
+        assert!(timestamps[0] >= 2);
+        assert!(timestamps[..3].windows(2).all(|v| v[0] < v[1]));
+        assert!(timestamps[2] as u128 <= device.timebase().elapsed().as_millis());
+        // an existing timestamp is kept
+        assert_eq!(timestamps[3], 1);
+        // the timestamp 0 is taken as no timestamp by default
+        assert!(timestamps[4] >= timestamps[2]);
+    }
+
+    #[test]
+    fn test_rx_sequence() {
+        let device = LoopbackDevice::<u8, MockFrame>::new(());
+        let recorder = MockListener::default();
+        assert!(device.register_listener("recorder".into(), Box::new(recorder.clone())));
+        let listeners = device.listeners();
+        assert!(!listeners.is_rx_sequencing());
+
+        let frame = |channel, id| {
+            let mut frame = MockFrame::new(Id::Standard(id), &[0x01]).unwrap();
+            frame.set_channel(channel);
+            frame
+        };
+        device.inject(frame(0, 0x7E0));
+        device.receive();
+        assert_eq!(recorder.received()[0].rx_sequence(), None);
+
+        listeners.set_rx_sequencing(true);
+        for index in 0..3 {
+            device.inject(frame(0, 0x100 + index));
+            device.inject(frame(1, 0x200 + index));
+        }
+        device.receive();
+        device.inject(frame(1, 0x203));
+        device.receive();
+
+        // batched per channel, but the sequences are of the arrival order
+        let mut received = recorder.received().split_off(1);
+        assert_eq!(received.iter().map(|frame| frame.channel()).collect::<Vec<_>>(), [0, 0, 0, 1, 1, 1, 1]);
+        received.sort_by_key(|frame| frame.rx_sequence());
+        assert!(received.windows(2).all(|v| v[0].rx_sequence() < v[1].rx_sequence()));
+        assert_eq!(received[0].rx_sequence(), Some(0));
+        assert_eq!(
+            received.iter().map(|frame| frame.id).collect::<Vec<_>>(),
+            [0x100, 0x200, 0x101, 0x201, 0x102, 0x202, 0x203]
+        );
+    }
+
+    #[test]
+    fn test_pause_receive() {
+        let device = LoopbackDevice::<u8, MockFrame>::new(());
+        let recorder = MockListener::default();
+        assert!(device.register_listener("recorder".into(), Box::new(recorder.clone())));
+        let frame = |id| MockFrame::new(Id::Standard(id), &[0x01]).unwrap();
+
+        device.pause_receive();
+        assert!(device.listeners().is_paused());
+        device.inject(frame(0x7E0));
+        device.inject(frame(0x7E1));
+        assert_eq!(device.receive(), 2);
+        assert!(recorder.received().is_empty());
+
+        device.resume_receive();
+        let ids = |recorder: &MockListener<MockFrame>| recorder.received()
+            .iter()
+            .map(|frame| frame.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids(&recorder), [0x7E0, 0x7E1]);
+
+        device.listeners().set_pause_policy(PausePolicy::Drop);
+        device.pause_receive();
+        device.inject(frame(0x7E2));
+        device.receive();
+        device.resume_receive();
+        device.inject(frame(0x7E3));
+        device.receive();
+        assert_eq!(ids(&recorder), [0x7E0, 0x7E1, 0x7E3]);
+    }
+
+    #[test]
+    fn test_error_injector() {
+        let injector = ErrorInjector::default()
+            .with_rule(Target::Nth(2), Fault::Drop)
+            .with_rule(Target::Id(0x7E8), Fault::Corrupt { index: 1, mask: 0xFF });
+        let device = LoopbackDevice::<u8, MockFrame>::new(())
+            .with_error_injector(injector);
+        let recorder = MockListener::default();
+        assert!(device.register_listener("recorder".into(), Box::new(recorder.clone())));
+
+        let sender = device.sender();
+        for id in [0x7E0, 0x7E0, 0x7E8, 0x7E8] {
+            sender.send(MockFrame::new(Id::Standard(id), &[0x01, 0x02]).unwrap()).unwrap();
+        }
+        assert_eq!(device.poll(), 3);
+
+        // the dropped frame is transmitted but not received
+        assert_eq!(recorder.transmitted.lock().unwrap().len(), 4);
+        let received = recorder.received()
+            .iter()
+            .map(|frame| frame.data().to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(received, [vec![0x01, 0x02], vec![0x01, 0xFD], vec![0x01, 0xFD]]);
+        // out of range
+        assert_eq!(LoopbackDevice::<u8, MockFrame>::corrupt(&recorder.received()[0], 2, 0xFF), None);
+    }
+}
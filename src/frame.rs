@@ -1,6 +1,11 @@
 use std::fmt::{Debug, Display, Formatter, Write};
+use std::io::{self, Read};
+use std::marker::PhantomData;
+use crate::constant::{IdentifierFlags, EFF_MASK};
 use crate::identifier::Id;
 
+pub mod bus_load;
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
 pub enum Direct {
@@ -9,6 +14,40 @@ pub enum Direct {
     Receive,
 }
 
+/// The directions of the frames that a listener handles, e.g. to log the received frames only.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum DirectionFilter {
+    /// The transmitting frames only.
+    TxOnly,
+    /// The received frames only.
+    RxOnly,
+    #[default]
+    Both,
+}
+
+impl DirectionFilter {
+    /// Returns true if the frames of `direct` are handled.
+    #[inline]
+    pub const fn accepts(&self, direct: Direct) -> bool {
+        matches!((self, direct), (Self::Both, _) | (Self::TxOnly, Direct::Transmit) | (Self::RxOnly, Direct::Receive))
+    }
+}
+
+/// The class of a CAN error frame, as reported by the controller.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ErrorClass {
+    /// The monitored bit is different from the transmitted one.
+    Bit,
+    /// More than 5 consecutive bits of the same level.
+    Stuff,
+    /// The received CRC is different from the calculated one.
+    Crc,
+    /// A fixed-form bit field contains illegal bits.
+    Form,
+    /// The transmitted frame is not acknowledged.
+    Ack,
+}
+
 /// CAN 2.0
 pub trait Frame {
     type Channel: Display;
@@ -28,13 +67,55 @@ pub trait Frame {
         Self::new(id, data.as_slice())
     }
 
+    /// The same as [`Self::from_iso_tp`] but padded to the valid lengths of CAN-FD if `can_fd`
+    /// instead of by the `can-fd` feature, the frames of the ISO-TP sessions are built by it.
+    #[cfg(feature = "isotp-rs")]
+    fn from_iso_tp_sized(id: impl Into<Id>, frame: isotp_rs::can::CanIsoTpFrame, padding: Option<u8>, can_fd: bool) -> Option<Self>
+    where
+        Self: Sized {
+        let data = crate::isotp::util::encode_sized(frame, None, padding, can_fd);
+        Self::new(id, data.as_slice())
+    }
+
     fn timestamp(&self) -> u64;
     
     fn set_timestamp(&mut self, value: Option<u64>) -> &mut Self
         where Self: Sized;
 
+    /// Returns true if the frame carries a timestamp, the outgoing frames without it are stamped
+    /// by the devices, see [`SyncDevice::stamp`](crate::device::SyncDevice::stamp).
+    ///
+    /// The default implementation takes the timestamp 0 as no timestamp, i.e. a frame stamped with 0
+    /// is stamped again. Backends that keep a zero timestamp should override it.
+    #[inline]
+    fn is_stamped(&self) -> bool {
+        self.timestamp() != 0
+    }
+
+    /// The sequence of the received frame across all channels of the device,
+    /// see [`Listeners::set_rx_sequencing`](crate::device::Listeners::set_rx_sequencing).
+    ///
+    /// The default implementation does not store it and returns `None`.
+    fn rx_sequence(&self) -> Option<u64> {
+        None
+    }
+
+    /// Set the sequence of the received frame, the default implementation ignores it.
+    fn set_rx_sequence(&mut self, value: Option<u64>) -> &mut Self
+    where
+        Self: Sized {
+        let _ = value;
+        self
+    }
+
     /// Prioritizes returning J1939Id if j1939 is true.
     fn id(&self, j1939: bool) -> Id;
+
+    /// Returns the plain identifier without the J1939 interpretation, i.e. `self.id(false)`.
+    #[inline]
+    fn can_id(&self) -> Id {
+        self.id(Default::default())
+    }
     
     fn is_can_fd(&self) -> bool;
     
@@ -64,6 +145,11 @@ pub trait Frame {
     where
         Self: Sized;
 
+    /// The class of the error frame, `None` if it is not an error frame or the backend does not report it.
+    fn error_class(&self) -> Option<ErrorClass> {
+        None
+    }
+
     /// Error state indicator
     fn is_esi(&self) -> bool;
 
@@ -78,12 +164,161 @@ pub trait Frame {
     where
         Self: Sized;
 
+    /// Returns a copy of the frame that only the channel is changed, e.g. forwarding a frame to another channel.
+    fn with_channel(&self, value: Self::Channel) -> Self
+    where
+        Self: Sized + Clone {
+        let mut frame = self.clone();
+        frame.set_channel(value);
+        frame
+    }
+
     /// ensure return the actual length of data.
     fn data(&self) -> &[u8];
     
     fn dlc(&self) -> Option<usize>;
     
     fn length(&self) -> usize;
+
+    /// Estimate the bit count of the frame on the wire with the worst-case bit stuffing, including
+    /// the SOF, arbitration, control, data, CRC, ACK, EOF fields and the 3-bit interframe space.
+    ///
+    /// The bit rate switch of CAN-FD is not considered, all bits are counted at the nominal bitrate.
+    fn bit_length(&self) -> usize {
+        let data_len = if self.is_remote() { 0 } else { self.length() };
+        bus_load::worst_case_bits(self.is_extended(), self.is_can_fd(), data_len)
+    }
+}
+
+/// The object-safe facade of [`Frame`] of the read-only methods, the channel is formatted to `String`.
+///
+/// It is implemented for all frames, so that the frames of different backends can be collected
+/// as `Box<dyn DynFrame>`. The constructors and setters stay on [`Frame`].
+///
+/// The method calls on a sized frame are ambiguous when both traits are in scope,
+/// import it only where the trait objects are used.
+pub trait DynFrame {
+    fn timestamp(&self) -> u64;
+
+    /// Prioritizes returning J1939Id if j1939 is true.
+    fn id(&self, j1939: bool) -> Id;
+
+    fn is_can_fd(&self) -> bool;
+
+    fn is_remote(&self) -> bool;
+
+    fn is_extended(&self) -> bool;
+
+    fn direct(&self) -> Direct;
+
+    fn is_bitrate_switch(&self) -> bool;
+
+    fn is_error_frame(&self) -> bool;
+
+    /// Error state indicator
+    fn is_esi(&self) -> bool;
+
+    fn channel(&self) -> String;
+
+    /// ensure return the actual length of data.
+    fn data(&self) -> &[u8];
+
+    fn dlc(&self) -> Option<usize>;
+
+    fn length(&self) -> usize;
+}
+
+impl<T: Frame> DynFrame for T {
+    #[inline]
+    fn timestamp(&self) -> u64 {
+        Frame::timestamp(self)
+    }
+    #[inline]
+    fn id(&self, j1939: bool) -> Id {
+        Frame::id(self, j1939)
+    }
+    #[inline]
+    fn is_can_fd(&self) -> bool {
+        Frame::is_can_fd(self)
+    }
+    #[inline]
+    fn is_remote(&self) -> bool {
+        Frame::is_remote(self)
+    }
+    #[inline]
+    fn is_extended(&self) -> bool {
+        Frame::is_extended(self)
+    }
+    #[inline]
+    fn direct(&self) -> Direct {
+        Frame::direct(self)
+    }
+    #[inline]
+    fn is_bitrate_switch(&self) -> bool {
+        Frame::is_bitrate_switch(self)
+    }
+    #[inline]
+    fn is_error_frame(&self) -> bool {
+        Frame::is_error_frame(self)
+    }
+    #[inline]
+    fn is_esi(&self) -> bool {
+        Frame::is_esi(self)
+    }
+    #[inline]
+    fn channel(&self) -> String {
+        Frame::channel(self).to_string()
+    }
+    #[inline]
+    fn data(&self) -> &[u8] {
+        Frame::data(self)
+    }
+    #[inline]
+    fn dlc(&self) -> Option<usize> {
+        Frame::dlc(self)
+    }
+    #[inline]
+    fn length(&self) -> usize {
+        Frame::length(self)
+    }
+}
+
+/// Read the multi-byte fields of the frame's data, `None` if out of the data.
+pub trait FrameDataExt {
+    fn u16_be(&self, offset: usize) -> Option<u16>;
+
+    fn u16_le(&self, offset: usize) -> Option<u16>;
+
+    fn u32_be(&self, offset: usize) -> Option<u32>;
+
+    fn u32_le(&self, offset: usize) -> Option<u32>;
+}
+
+/// Returns the `N` bytes of `data` at `offset`.
+#[inline]
+fn data_bytes<const N: usize>(data: &[u8], offset: usize) -> Option<[u8; N]> {
+    data.get(offset..offset.checked_add(N)?)?
+        .try_into()
+        .ok()
+}
+
+impl<T: Frame> FrameDataExt for T {
+    #[inline]
+    fn u16_be(&self, offset: usize) -> Option<u16> {
+        data_bytes(self.data(), offset).map(u16::from_be_bytes)
+    }
+    #[inline]
+    fn u16_le(&self, offset: usize) -> Option<u16> {
+        data_bytes(self.data(), offset).map(u16::from_le_bytes)
+    }
+    #[inline]
+    fn u32_be(&self, offset: usize) -> Option<u32> {
+        data_bytes(self.data(), offset).map(u32::from_be_bytes)
+    }
+    #[inline]
+    fn u32_le(&self, offset: usize) -> Option<u32> {
+        data_bytes(self.data(), offset).map(u32::from_le_bytes)
+    }
 }
 
 impl<T: Display> Display for dyn Frame<Channel = T> {
@@ -101,54 +336,429 @@ impl<T: Display> Display for dyn Frame<Channel = T> {
         };
 
         if self.is_can_fd() {
+            let bitrate_switch = self.is_bitrate_switch();
+            let esi = self.is_esi();
             let mut flags = 1 << 12;
-            write!(f, "{:.3} CANFD {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {}",
+            if bitrate_switch {
+                flags |= 1 << 13;
+            }
+            if esi {
+                flags |= 1 << 14;
+            }
+            write!(f, "{:.3} CANFD {} {} {: >8x}{} {} {} {: >2} {: >2} {} {: >8} {: <4} {: >8x} {: >8} {: >8} {: >8} {: >8} {: >8}",
                    self.timestamp() as f64 / 1000.,
                    self.channel(),
                    direct(self.direct()),
                    // if self.is_rx() { "Rx" } else { "Tx" },
-                   format!("{: >8x}", self.id(false).as_raw()),
-                   if self.is_bitrate_switch() {
-                       flags |= 1 << 13;
-                       1
-                   } else { 0 },
-                   if self.is_esi() {
-                       flags |= 1 << 14;
-                       1
-                   } else { 0 },
-                   format!("{: >2}", self.dlc().unwrap_or_default()),
-                   format!("{: >2}", self.length()),
+                   self.can_id().as_raw(),
+                   if self.is_extended() { "x" } else { "" },
+                   bitrate_switch as u8,
+                   esi as u8,
+                   self.dlc().unwrap_or_default(),
+                   self.length(),
                    data_str,
-                   format!("{: >8}", 0),       // message_duration
-                   format!("{: <4}", 0),       // message_length
-                   format!("{: >8x}", flags),
-                   format!("{: >8}", 0),       // crc
-                   format!("{: >8}", 0),       // bit_timing_conf_arb
-                   format!("{: >8}", 0),       // bit_timing_conf_data
-                   format!("{: >8}", 0),       // bit_timing_conf_ext_arb
-                   format!("{: >8}", 0),       // bit_timing_conf_ext_data
+                   0,       // message_duration
+                   0,       // message_length
+                   flags,
+                   0,       // crc
+                   0,       // bit_timing_conf_arb
+                   0,       // bit_timing_conf_data
+                   0,       // bit_timing_conf_ext_arb
+                   0,       // bit_timing_conf_ext_data
             )
         }
         else {
-            write!(f, "{:.3} {} {}{: <4} {} {} {} {}",
+            write!(f, "{:.3} {} {: >8x}{: <4} {} {} {: >2} {}",
                    self.timestamp() as f64 / 1000.,
                    self.channel(),
-                   format!("{: >8x}", self.id(false).as_raw()),
+                   self.can_id().as_raw(),
                    if self.is_extended() { "x" } else { "" },
                    direct(self.direct()),
                    // if self.is_rx() { "Rx" } else { "Tx" },
                    if self.is_remote() { "r" } else { "d" },
-                   format!("{: >2}", self.length()),
+                   self.length(),
                    data_str,
             )
         }
     }
 }
 
+/// Output Frame compactly as `timestamp channel id direct data`.
+#[cfg(feature = "defmt")]
+impl<T: Display + defmt::Format> defmt::Format for dyn Frame<Channel = T> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{=u64} {} {} {=str} [{=[u8]:02x}]",
+                      self.timestamp(),
+                      self.channel(),
+                      self.can_id(),
+                      direct(self.direct()),
+                      self.data(),
+        )
+    }
+}
+
+/// The flag of CAN-FD frames in the identifier of raw binary log records.
+pub const RAW_LOG_FD_FLAG: u32 = 0x2000_0000;
+
+/// Write the frames as the records of a raw binary log, they are read by [`RawLogReader`].
+///
+/// Every record is `[id:4][timestamp:8][dlc:1][data:dlc]`, all integers are big-endian:
+/// * `id`: the identifier with the flags of bit 31 for extended([`IdentifierFlags::EXTENDED`]),
+///   bit 30 for remote([`IdentifierFlags::REMOTE`]) and bit 29 for CAN-FD([`RAW_LOG_FD_FLAG`]).
+/// * `timestamp`: the timestamp of frame in milliseconds.
+/// * `dlc`: the length of data in bytes(0..=64), not the DLC code of CAN-FD.
+/// * `data`: the data bytes, the data of a remote frame is ignored.
+///
+/// The channel, direction, bitrate switch, error state indicator and error frame flag are not recorded.
+/// The writer is flushed when dropped.
+pub struct RawLogWriter<W: io::Write> {
+    writer: W,
+}
+
+impl<W: io::Write> RawLogWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    #[inline]
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Write `frame` as a record.
+    pub fn write_frame(&mut self, frame: &impl Frame) -> io::Result<()> {
+        let mut id = frame.can_id().as_raw();
+        if frame.is_extended() {
+            id |= IdentifierFlags::EXTENDED.bits();
+        }
+        if frame.is_remote() {
+            id |= IdentifierFlags::REMOTE.bits();
+        }
+        if frame.is_can_fd() {
+            id |= RAW_LOG_FD_FLAG;
+        }
+
+        let data = frame.data();
+        self.writer.write_all(&id.to_be_bytes())?;
+        self.writer.write_all(&frame.timestamp().to_be_bytes())?;
+        self.writer.write_all(&[data.len() as u8])?;
+        self.writer.write_all(data)
+    }
+
+    #[inline]
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: io::Write> Drop for RawLogWriter<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.writer.flush() {
+            log::warn!("RawLogWriter - flush failed: {}", e);
+        }
+    }
+}
+
+/// Read the frames of a raw binary log sequentially, see [`RawLogWriter`] for the record format.
+///
+/// A truncated trailing record yields [`io::ErrorKind::UnexpectedEof`], and a record that is not
+/// a valid frame yields [`io::ErrorKind::InvalidData`], no more frames are read after an error.
+pub struct RawLogReader<R, F> {
+    reader: R,
+    finished: bool,
+    _frame: PhantomData<F>,
+}
+
+impl<R: Read, F: Frame> RawLogReader<R, F> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, finished: false, _frame: PhantomData }
+    }
+
+    /// Read a record, `None` if the log is ended at a record boundary.
+    fn read_record(&mut self) -> io::Result<Option<F>> {
+        let mut id = [0; 4];
+        let mut count = 0;
+        while count < id.len() {
+            match self.reader.read(&mut id[count..]) {
+                Ok(0) => break,
+                Ok(v) => count += v,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(e),
+            }
+        }
+        match count {
+            0 => return Ok(None),
+            4 => {},
+            _ => return Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+
+        let mut timestamp = [0; 8];
+        self.reader.read_exact(&mut timestamp)?;
+        let mut length = [0; 1];
+        self.reader.read_exact(&mut length)?;
+        let mut data = vec![0; length[0] as usize];
+        self.reader.read_exact(&mut data)?;
+
+        let raw = u32::from_be_bytes(id);
+        let id = Id::from_bits(raw & EFF_MASK, raw & IdentifierFlags::EXTENDED.bits() != 0);
+        let frame = match raw & IdentifierFlags::REMOTE.bits() != 0 {
+            true => F::new_remote(id, data.len()),
+            false => F::new(id, &data),
+        };
+        let mut frame = frame.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid frame record"))?;
+        frame.set_can_fd(raw & RAW_LOG_FD_FLAG != 0)
+            .set_timestamp(Some(u64::from_be_bytes(timestamp)));
+
+        Ok(Some(frame))
+    }
+}
+
+impl<R: Read, F: Frame> Iterator for RawLogReader<R, F> {
+    type Item = io::Result<F>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        match self.read_record() {
+            Ok(Some(frame)) => Some(Ok(frame)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            },
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e))
+            },
+        }
+    }
+}
+
 #[inline]
-fn direct<'a>(direct: Direct) -> &'a str {
+pub(crate) fn direct<'a>(direct: Direct) -> &'a str {
     match direct {
         Direct::Transmit => "Tx",
         Direct::Receive => "Rx",
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::identifier::Id;
+    use crate::mock::MockFrame;
+    use super::{Direct, ErrorClass, Frame, FrameDataExt};
+
+    /// A frame of another backend that is named by string channels.
+    #[derive(Debug, Clone)]
+    struct NamedFrame {
+        inner: MockFrame,
+        channel: String,
+    }
+
+    impl Frame for NamedFrame {
+        type Channel = String;
+
+        fn new(id: impl Into<Id>, data: &[u8]) -> Option<Self> {
+            Some(Self { inner: MockFrame::new(id, data)?, channel: String::new() })
+        }
+        fn new_remote(id: impl Into<Id>, len: usize) -> Option<Self> {
+            Some(Self { inner: MockFrame::new_remote(id, len)?, channel: String::new() })
+        }
+        fn timestamp(&self) -> u64 { self.inner.timestamp() }
+        fn set_timestamp(&mut self, value: Option<u64>) -> &mut Self {
+            self.inner.set_timestamp(value);
+            self
+        }
+        fn id(&self, j1939: bool) -> Id { self.inner.id(j1939) }
+        fn is_can_fd(&self) -> bool { self.inner.is_can_fd() }
+        fn set_can_fd(&mut self, value: bool) -> &mut Self {
+            self.inner.set_can_fd(value);
+            self
+        }
+        fn is_remote(&self) -> bool { self.inner.is_remote() }
+        fn is_extended(&self) -> bool { self.inner.is_extended() }
+        fn direct(&self) -> Direct { self.inner.direct() }
+        fn set_direct(&mut self, direct: Direct) -> &mut Self {
+            self.inner.set_direct(direct);
+            self
+        }
+        fn is_bitrate_switch(&self) -> bool { self.inner.is_bitrate_switch() }
+        fn set_bitrate_switch(&mut self, value: bool) -> &mut Self {
+            self.inner.set_bitrate_switch(value);
+            self
+        }
+        fn is_error_frame(&self) -> bool { self.inner.is_error_frame() }
+        fn set_error_frame(&mut self, value: bool) -> &mut Self {
+            self.inner.set_error_frame(value);
+            self
+        }
+        fn is_esi(&self) -> bool { self.inner.is_esi() }
+        fn set_esi(&mut self, value: bool) -> &mut Self {
+            self.inner.set_esi(value);
+            self
+        }
+        fn channel(&self) -> Self::Channel { self.channel.clone() }
+        fn set_channel(&mut self, value: Self::Channel) -> &mut Self {
+            self.channel = value;
+            self
+        }
+        fn data(&self) -> &[u8] { self.inner.data() }
+        fn dlc(&self) -> Option<usize> { self.inner.dlc() }
+        fn length(&self) -> usize { self.inner.length() }
+    }
+
+    #[test]
+    fn test_with_channel() {
+        let mut frame = MockFrame::new(Id::from_bits(0x18DAF110, true), &[0x02, 0x10, 0x01]).unwrap();
+        frame.set_timestamp(Some(1234))
+            .set_direct(Direct::Receive)
+            .set_bitrate_switch(true)
+            .set_esi(true)
+            .set_channel(0);
+
+        let forward = frame.with_channel(1);
+        assert_eq!(forward.channel(), 1);
+        assert_eq!(frame.channel(), 0);
+        assert_eq!(MockFrame { channel: 0, ..forward }, frame);
+    }
+
+    #[test]
+    fn test_dyn_frame() {
+        use super::DynFrame;
+
+        let mut mock = MockFrame::new(Id::Standard(0x7E8), &[0x02, 0x50, 0x01]).unwrap();
+        mock.set_timestamp(Some(1000))
+            .set_direct(Direct::Receive)
+            .set_channel(1);
+        let mut named = NamedFrame::new(Id::from_bits(0x18DAF110, true), &[0x00; 12]).unwrap();
+        named.set_timestamp(Some(2000))
+            .set_channel("vcan0".into());
+
+        let frames: Vec<Box<dyn DynFrame>> = vec![Box::new(mock), Box::new(named)];
+        assert_eq!(frames[0].channel(), "1");
+        assert_eq!(frames[0].id(false), Id::Standard(0x7E8));
+        assert_eq!(frames[0].direct(), Direct::Receive);
+        assert_eq!(frames[0].data(), [0x02, 0x50, 0x01]);
+        assert!(!frames[0].is_extended());
+
+        assert_eq!(frames[1].channel(), "vcan0");
+        assert_eq!(frames[1].timestamp(), 2000);
+        assert!(frames[1].is_extended());
+        assert!(frames[1].is_can_fd());
+        assert_eq!(frames[1].length(), 12);
+
+        let timestamps = frames.iter()
+            .map(|f| f.timestamp())
+            .collect::<Vec<_>>();
+        assert_eq!(timestamps, [1000, 2000]);
+    }
+
+    #[test]
+    fn test_raw_log() {
+        use super::{RawLogReader, RawLogWriter};
+
+        let mut fd = MockFrame::new(Id::Standard(0x7E0), &[0x02, 0x10, 0x01]).unwrap();
+        fd.set_can_fd(true)
+            .set_timestamp(Some(1234));
+        let mut frames = vec![
+            MockFrame::new(Id::Standard(0x7E0), &[0x02, 0x10, 0x01]).unwrap(),
+            MockFrame::new(Id::from_bits(0x18DAF110, true), &[]).unwrap(),
+            // extended in the 11-bit range
+            MockFrame::new(Id::Extended(0x7E8), &[0x11; 8]).unwrap(),
+            MockFrame::new(Id::Standard(0x123), &[0x55; 64]).unwrap(),
+            MockFrame::new_remote(Id::Standard(0x321), 2).unwrap(),
+            fd,
+        ];
+        frames[0].set_timestamp(Some(0x0102));
+
+        let mut log = Vec::new();
+        {
+            let mut writer = RawLogWriter::new(&mut log);
+            for frame in &frames {
+                writer.write_frame(frame).unwrap();
+            }
+        }
+        assert_eq!(log[..4], [0x00, 0x00, 0x07, 0xE0]);
+        assert_eq!(log[4..12], [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x02]);
+        assert_eq!(log[12..16], [0x03, 0x02, 0x10, 0x01]);
+        assert_eq!(log[16..20], [0x98, 0xDA, 0xF1, 0x10]);
+
+        let parsed = RawLogReader::<_, MockFrame>::new(log.as_slice())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(parsed, frames);
+        assert!(parsed[3].is_can_fd() && parsed[5].is_can_fd());
+        assert!(parsed[4].is_remote());
+
+        // truncated in the data and in the identifier
+        for length in [log.len() - 1, 18] {
+            let mut reader = RawLogReader::<_, MockFrame>::new(&log[..length]);
+            let error = reader.find_map(Result::err).unwrap();
+            assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+            assert!(reader.next().is_none());
+        }
+    }
+
+    #[test]
+    fn test_data_ext() {
+        let frame = MockFrame::new(Id::Standard(0x7E0), &[0x01, 0x02, 0x03, 0x04]).unwrap();
+        assert_eq!(frame.u16_be(1), Some(0x0203));
+        assert_eq!(frame.u16_le(1), Some(0x0302));
+        assert_eq!(frame.u32_be(0), Some(0x01020304));
+        assert_eq!(frame.u32_le(0), Some(0x04030201));
+        // out of the data
+        assert_eq!(frame.u16_be(3), None);
+        assert_eq!(frame.u32_le(1), None);
+        assert_eq!(frame.u16_le(usize::MAX), None);
+    }
+
+    #[test]
+    fn test_error_class() {
+        let mut frame = MockFrame::new(Id::Standard(0x7E0), &[]).unwrap();
+        assert_eq!(frame.error_class(), None);
+
+        frame.set_error_frame(true);
+        frame.error_class = Some(ErrorClass::Stuff);
+        assert!(frame.is_error_frame());
+        assert_eq!(frame.error_class(), Some(ErrorClass::Stuff));
+        // the default of other backends
+        let named = NamedFrame::new(Id::Standard(0x7E0), &[]).unwrap();
+        assert_eq!(named.error_class(), None);
+    }
+
+    #[test]
+    fn test_can_id() {
+        for id in [Id::Standard(0x7E8), Id::Extended(0x18DAF110)] {
+            let frame = MockFrame::new(id, &[0x02, 0x3E, 0x00]).unwrap();
+            assert_eq!(frame.can_id(), frame.id(Default::default()));
+            assert_eq!(frame.can_id(), id);
+        }
+    }
+
+    #[cfg(feature = "isotp-rs")]
+    #[test]
+    fn test_from_iso_tp_sized() {
+        use isotp_rs::can::CanIsoTpFrame;
+
+        let single = |len| CanIsoTpFrame::SingleFrame { data: vec![0x01; len] };
+        let frame = MockFrame::from_iso_tp_sized(Id::Standard(0x7E0), single(3), Some(0xCC), true).unwrap();
+        assert_eq!(frame.data(), [0x03, 0x01, 0x01, 0x01, 0xCC, 0xCC, 0xCC, 0xCC]);
+        // padded to the next valid length of CAN-FD
+        let frame = MockFrame::from_iso_tp_sized(Id::Standard(0x7E0), single(9), Some(0xCC), true).unwrap();
+        assert_eq!(frame.data().len(), 12);
+        assert_eq!(frame.data()[..2], [0x00, 0x09]);
+        assert!(MockFrame::from_iso_tp_sized(Id::Standard(0x7E0), single(7), None, false).is_some());
+    }
+
+    #[test]
+    fn test_bit_length() {
+        let frame = MockFrame::new(Id::Standard(0x7E0), &[0x00; 8]).unwrap();
+        assert_eq!(frame.bit_length(), 135);
+        let frame = MockFrame::new(Id::from_bits(0x18DAF110, true), &[]).unwrap();
+        assert_eq!(frame.bit_length(), 80);
+        let frame = MockFrame::new(Id::Standard(0x7E0), &[0x00; 64]).unwrap();
+        assert!(frame.is_can_fd());
+        assert_eq!(frame.bit_length(), 22 + 512 + 133 + 4 + 21 + 7 + 13);
+    }
+}
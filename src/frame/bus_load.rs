@@ -0,0 +1,237 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::constant::{CAN_FRAME_MAX_SIZE, CANFD_FRAME_MAX_SIZE};
+use crate::device::Listener;
+use crate::frame::{Direct, DirectionFilter, Frame};
+
+/// The data lengths of CAN-FD frames.
+const CANFD_LENGTHS: [usize; 7] = [12, 16, 20, 24, 32, 48, 64];
+/// CRC delimiter, ACK slot, ACK delimiter, end of frame and intermission.
+const TRAILER_BITS: usize = 1 + 1 + 1 + 7 + 3;
+
+/// The bits that are stuffed dynamically and the total bits of a frame without stuff bits.
+fn frame_bits(id_extended: bool, fd: bool, data_len: usize) -> (usize, usize) {
+    let data_bits = 8 * padded_length(fd, data_len);
+    if fd {
+        // SOF, identifier, RRS(SRR and RRS), IDE, FDF, res, BRS, ESI and DLC
+        let header = if id_extended { 1 + 11 + 1 + 1 + 18 + 1 + 1 + 1 + 1 + 1 + 4 } else { 1 + 11 + 1 + 1 + 1 + 1 + 1 + 1 + 4 };
+        // stuff count, CRC-17/21 and the fixed stuff bits of them
+        let crc = if data_bits > 8 * 16 { 4 + 21 + 7 } else { 4 + 17 + 6 };
+        let stuffed = header + data_bits;
+        (stuffed, stuffed + crc + TRAILER_BITS)
+    }
+    else {
+        // SOF, identifier, RTR(SRR and RTR), IDE, r0(r1 and r0) and DLC
+        let header = if id_extended { 1 + 11 + 1 + 1 + 18 + 1 + 1 + 1 + 4 } else { 1 + 11 + 1 + 1 + 1 + 4 };
+        // CRC-15
+        let stuffed = header + data_bits + 15;
+        (stuffed, stuffed + TRAILER_BITS)
+    }
+}
+
+/// The data length that is transmitted on the bus, e.g. 9 bytes of CAN-FD are padded to 12.
+fn padded_length(fd: bool, data_len: usize) -> usize {
+    match fd {
+        true if data_len > CAN_FRAME_MAX_SIZE => CANFD_LENGTHS.into_iter()
+            .find(|&v| v >= data_len)
+            .unwrap_or(CANFD_FRAME_MAX_SIZE),
+        _ => data_len.min(CAN_FRAME_MAX_SIZE),
+    }
+}
+
+/// The bits of a data frame without stuff bits, including the 3 bits of intermission.
+///
+/// `data_len` is padded to a valid length of the frame type, 0 for remote frames.
+/// The bit rate switch of CAN-FD is not accounted, all bits are of the nominal bit rate.
+#[inline]
+pub fn nominal_bits(id_extended: bool, fd: bool, data_len: usize) -> usize {
+    frame_bits(id_extended, fd, data_len).1
+}
+
+/// The bits of a data frame with the max stuff bits, i.e. one of every 4 bits after the first one.
+///
+/// See [`nominal_bits`] for `data_len`, it is the [`Frame::bit_length`] of the frame.
+#[inline]
+pub fn worst_case_bits(id_extended: bool, fd: bool, data_len: usize) -> usize {
+    let (stuffed, total) = frame_bits(id_extended, fd, data_len);
+    total + (stuffed - 1) / 4
+}
+
+struct Samples {
+    window: Duration,
+    samples: VecDeque<(Instant, usize)>,
+}
+
+impl Samples {
+    fn push(&mut self, now: Instant, bits: usize) {
+        self.expire(now);
+        self.samples.push_back((now, bits));
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some(&(at, _)) = self.samples.front() {
+            if now.duration_since(at) < self.window {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+}
+
+/// A listener that estimates the bus load of the frames transmitting and received in a window.
+///
+/// The clones share the estimation, so a clone can be registered and the other one queried.
+#[derive(Clone)]
+pub struct BusLoadMeter {
+    bitrate: u32,
+    worst_case: bool,
+    direction: DirectionFilter,
+    samples: Arc<Mutex<Samples>>,
+}
+
+impl BusLoadMeter {
+    /// Estimate the load of the bus of `bitrate`(bit/s) in the last `window`.
+    pub fn new(bitrate: u32, window: Duration) -> Self {
+        Self {
+            bitrate,
+            worst_case: false,
+            direction: Default::default(),
+            samples: Arc::new(Mutex::new(Samples { window, samples: Default::default() })),
+        }
+    }
+
+    /// Estimate by [`worst_case_bits`] instead of [`nominal_bits`].
+    pub fn with_worst_case(mut self) -> Self {
+        self.worst_case = true;
+        self
+    }
+
+    /// Estimate the frames of the directions of `filter` only, both directions by default.
+    pub fn with_direction_filter(mut self, filter: DirectionFilter) -> Self {
+        self.direction = filter;
+        self
+    }
+
+    /// The estimated bits in the window.
+    pub fn bits(&self) -> usize {
+        self.bits_at(Instant::now())
+    }
+
+    /// The estimated utilization of the bus in percent, may exceed 100 if the estimation is coarse.
+    pub fn utilization(&self) -> f64 {
+        self.utilization_at(Instant::now())
+    }
+
+    fn bits_at(&self, now: Instant) -> usize {
+        match self.samples.lock() {
+            Ok(mut samples) => {
+                samples.expire(now);
+                samples.samples.iter().map(|&(_, bits)| bits).sum()
+            },
+            Err(_) => {
+                log::warn!("BusLoadMeter: samples lock is poisoned");
+                0
+            },
+        }
+    }
+
+    fn utilization_at(&self, now: Instant) -> f64 {
+        let capacity = match self.samples.lock() {
+            Ok(samples) => self.bitrate as f64 * samples.window.as_secs_f64(),
+            Err(_) => 0.,
+        };
+        match capacity > 0. {
+            true => self.bits_at(now) as f64 * 100. / capacity,
+            false => 0.,
+        }
+    }
+
+    fn record<F: Frame>(&self, now: Instant, frame: &F) {
+        let bits = match self.worst_case {
+            true => frame.bit_length(),
+            false => {
+                let data_len = if frame.is_remote() { 0 } else { frame.length() };
+                nominal_bits(frame.is_extended(), frame.is_can_fd(), data_len)
+            },
+        };
+        match self.samples.lock() {
+            Ok(mut samples) => samples.push(now, bits),
+            Err(_) => log::warn!("BusLoadMeter: samples lock is poisoned"),
+        }
+    }
+}
+
+impl<C, I, F: Frame> Listener<C, I, F> for BusLoadMeter {
+    fn on_frame_transmitting(&mut self, _: C, frame: &F) {
+        if self.direction.accepts(Direct::Transmit) {
+            self.record(Instant::now(), frame);
+        }
+    }
+
+    fn on_frame_transmitted(&mut self, _: C, _: I) {}
+
+    fn on_frame_received(&mut self, _: C, frames: &[F]) {
+        if !self.direction.accepts(Direct::Receive) {
+            return;
+        }
+
+        let now = Instant::now();
+        frames.iter()
+            .for_each(|frame| self.record(now, frame));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use crate::frame::Frame;
+    use crate::identifier::Id;
+    use crate::mock::MockFrame;
+    use super::*;
+
+    #[test]
+    fn test_bits() {
+        // 47 bits of frame and 64 bits of data, 98 bits from SOF to CRC are stuffed
+        assert_eq!(nominal_bits(false, false, 8), 111);
+        assert_eq!(worst_case_bits(false, false, 8), 135);
+        assert_eq!(nominal_bits(true, false, 8), 131);
+        assert_eq!(worst_case_bits(true, false, 8), 160);
+        assert_eq!(nominal_bits(false, false, 0), 47);
+        assert_eq!(worst_case_bits(false, false, 0), 55);
+        // out of range
+        assert_eq!(nominal_bits(false, false, 9), 111);
+
+        assert_eq!(nominal_bits(false, true, 8), 126);
+        assert_eq!(nominal_bits(false, true, 9), nominal_bits(false, true, 12));
+        assert_eq!(nominal_bits(false, true, 64), 22 + 512 + 32 + 13);
+    }
+
+    #[test]
+    fn test_bus_load_meter() {
+        let meter = BusLoadMeter::new(500_000, Duration::from_secs(1));
+        let frame = MockFrame::new(Id::Standard(0x7E0), &[0x55; 8]).unwrap();
+
+        let now = Instant::now();
+        (0..1000).for_each(|_| meter.record(now, &frame));
+        assert_eq!(meter.bits_at(now), 111_000);
+        assert!((meter.utilization_at(now) - 22.2).abs() < 1e-9);
+
+        let mut listener = meter.clone().with_worst_case();
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut listener, 0, &[frame]);
+        assert_eq!(meter.bits(), 111_000 + 135);
+        // expired
+        assert_eq!(meter.bits_at(Instant::now() + Duration::from_secs(1)), 0);
+    }
+
+    #[test]
+    fn test_direction_filter() {
+        let frame = MockFrame::new(Id::Standard(0x7E0), &[0x02, 0x10, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00]).unwrap();
+        let meter = BusLoadMeter::new(500_000, Duration::from_secs(1));
+        let mut listener = meter.clone().with_direction_filter(DirectionFilter::RxOnly);
+        Listener::<u8, u32, MockFrame>::on_frame_transmitting(&mut listener, 0, &frame);
+        assert_eq!(meter.bits(), 0);
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut listener, 0, &[frame]);
+        assert_eq!(meter.bits(), 111);
+    }
+}
@@ -2,6 +2,14 @@ use std::fmt::format;
 use crate::constant::{EFF_MASK, SFF_MASK};
 use crate::j1939::J1939Id;
 
+mod range;
+pub use range::IdRange;
+
+/// The lowest priority of the top 3 bits of 29-bit identifiers.
+const PRIORITY_LOWEST: u8 = 0x07;
+/// The offset of the priority bits of 29-bit identifiers.
+const PRIORITY_SHIFT: u32 = 26;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Id {
     Standard(u16),
@@ -9,8 +17,6 @@ pub enum Id {
     J1939(J1939Id),
 }
 
-unsafe impl Send for Id {}
-
 impl Id {
     #[inline]
     pub fn from_bits(bits: u32, extended: bool) -> Self {
@@ -76,7 +82,7 @@ impl Id {
     #[must_use]
     pub fn standard_id(self) -> Self {
         match self {
-            Self::Standard(_) => self.clone(),
+            Self::Standard(_) => self,
             Self::Extended(v) => Self::Standard((v >> 18) as u16),     // ID-28 to ID-18
             Self::J1939(v) => Self::Standard((v.into_bits() >> 18) as u16)
         }
@@ -90,4 +96,162 @@ impl Id {
             Self::J1939(_) => true,
         }
     }
+
+    /// Returns this identifier as a standard or extended identifier.
+    ///
+    /// A J1939 identifier is converted to the extended identifier of the same raw value,
+    /// for the devices which do not understand J1939 identifiers.
+    #[inline]
+    #[must_use]
+    pub fn normalized(self) -> Self {
+        match self {
+            Self::J1939(v) => Self::Extended(v.into_bits()),
+            _ => self,
+        }
+    }
+
+    /// Returns the priority of the top 3 bits(bits 26-28) of an extended or J1939 identifier,
+    /// a standard identifier is of the lowest priority 7.
+    #[inline]
+    #[must_use]
+    pub fn priority(self) -> u8 {
+        match self {
+            Self::Standard(_) => PRIORITY_LOWEST,
+            _ => ((self.into_bits() & EFF_MASK) >> PRIORITY_SHIFT) as u8,
+        }
+    }
+
+    /// Returns this identifier with the top 3 bits rewritten to `priority`(the low 3 bits are used),
+    /// a standard identifier is unchanged.
+    #[inline]
+    #[must_use]
+    pub fn with_priority(self, priority: u8) -> Self {
+        let mask = (PRIORITY_LOWEST as u32) << PRIORITY_SHIFT;
+        let rewrite = |bits: u32| (bits & !mask) | ((priority & PRIORITY_LOWEST) as u32) << PRIORITY_SHIFT;
+        match self {
+            Self::Standard(_) => self,
+            Self::Extended(v) => Self::Extended(rewrite(v)),
+            Self::J1939(v) => Self::J1939(J1939Id::from_bits(rewrite(v.into_bits()))),
+        }
+    }
+
+    /// Returns true if this identifier is a J1939 identifier.
+    #[inline]
+    pub fn is_j1939(&self) -> bool {
+        matches!(self, Self::J1939(_))
+    }
+
+    /// Returns the J1939 bitfield of this identifier.
+    ///
+    /// An extended identifier is reinterpreted as J1939, a standard identifier returns `None`.
+    #[inline]
+    pub fn as_j1939(self) -> Option<J1939Id> {
+        match self {
+            Self::Standard(_) => None,
+            Self::Extended(v) => Some(J1939Id::from_bits(v)),
+            Self::J1939(v) => Some(v),
+        }
+    }
+}
+
+/// Parse a hex identifier of unknown width, a standard identifier is returned when the value fits
+/// [`SFF_MASK`], else an extended identifier.
+///
+/// `None` is returned if `hex_str` is not hex digits or the value exceeds [`EFF_MASK`].
+#[inline]
+pub fn parse_auto(hex_str: &str) -> Option<Id> {
+    if hex_str.is_empty() || !hex_str.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    Id::try_from_hex(hex_str, false)
+}
+
+/// Output `Id` in the hex form of [`Id::into_hex`], the extended identifier is suffixed with `x`.
+///
+/// # Examples
+/// ```ignore
+/// use can_type_rs::identifier::Id;
+///
+/// defmt::info!("{}", Id::Extended(0x18DAF110));   // 18DAF110x
+/// ```
+#[cfg(feature = "defmt")]
+impl defmt::Format for Id {
+    fn format(&self, f: defmt::Formatter) {
+        match self {
+            Self::Standard(v) => defmt::write!(f, "{=u16:03X}", *v),
+            Self::Extended(v) => defmt::write!(f, "{=u32:08X}x", *v),
+            Self::J1939(v) => defmt::write!(f, "{}", v),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::j1939::{J1939, J1939Id};
+    use super::{parse_auto, Id};
+
+    #[test]
+    fn test_j1939() {
+        let standard = Id::Standard(0x7E0);
+        assert!(!standard.is_j1939());
+        assert_eq!(standard.as_j1939(), None);
+
+        let extended = Id::Extended(0x0CF00400);
+        assert!(!extended.is_j1939());
+        let j1939 = extended.as_j1939().unwrap();
+        assert_eq!(j1939.into_bits(), 0x0CF00400);
+        assert_eq!(j1939.priority(), 3);
+        assert_eq!(j1939.pdu_format(), 0xF0);
+
+        let id = Id::J1939(J1939Id::from_bits(0x18FEF100));
+        assert!(id.is_j1939());
+        assert_eq!(id.as_j1939(), Some(J1939Id::from_bits(0x18FEF100)));
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Id>();
+    }
+
+    #[test]
+    fn test_priority() {
+        let id = Id::Extended(0x18DAF110);
+        assert_eq!(id.priority(), 6);
+        let id = id.with_priority(3);
+        assert_eq!(id, Id::Extended(0x0CDAF110));
+        assert_eq!(id.priority(), 3);
+
+        let id = Id::J1939(J1939Id::from_bits(0x18FEF100)).with_priority(3);
+        assert_eq!(id, Id::J1939(J1939Id::from_bits(0x0CFEF100)));
+        assert_eq!(id.as_j1939().unwrap().priority(), 3);
+        // the low 3 bits
+        assert_eq!(Id::Extended(0x18DAF110).with_priority(0x0F).priority(), 7);
+
+        assert_eq!(Id::Standard(0x7E0).priority(), 7);
+        assert_eq!(Id::Standard(0x7E0).with_priority(0), Id::Standard(0x7E0));
+    }
+
+    #[test]
+    fn test_normalized() {
+        let id = Id::J1939(J1939Id::from_bits(0x18FEF100));
+        assert_eq!(id.normalized(), Id::Extended(0x18FEF100));
+        assert_eq!(id.normalized().as_raw(), id.as_raw());
+
+        assert_eq!(Id::Standard(0x7E0).normalized(), Id::Standard(0x7E0));
+        assert_eq!(Id::Extended(0x18DAF110).normalized(), Id::Extended(0x18DAF110));
+    }
+
+    #[test]
+    fn test_parse_auto() {
+        assert_eq!(parse_auto("7FF"), Some(Id::Standard(0x7FF)));
+        assert_eq!(parse_auto("800"), Some(Id::Extended(0x800)));
+        assert_eq!(parse_auto("1FFFFFFF"), Some(Id::Extended(0x1FFFFFFF)));
+
+        assert_eq!(parse_auto("20000000"), None);
+        assert_eq!(parse_auto("+7FF"), None);
+        assert_eq!(parse_auto("7FG"), None);
+        assert_eq!(parse_auto(""), None);
+    }
 }
@@ -0,0 +1,107 @@
+use std::ops::RangeInclusive;
+use crate::constant::{EFF_MASK, SFF_MASK};
+use crate::identifier::Id;
+
+/// A contiguous range of identifiers from `start` to `end` inclusively, e.g. for the ranges
+/// that are not expressible by a mask.
+///
+/// The range is of the width of `start`, a standard identifier is never in an extended range
+/// and vice versa, a J1939 identifier is extended.
+///
+/// # Examples
+/// ```rust
+/// use can_type_rs::identifier::{Id, IdRange};
+///
+/// let range = IdRange::standard(0x700..=0x7FF);
+/// assert!(range.contains(Id::Standard(0x7DF)));
+/// assert!(!range.contains(Id::Extended(0x7DF)));
+/// assert_eq!(range.standard_ids().count(), 0x100);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdRange {
+    pub start: Id,
+    pub end: Id,
+}
+
+impl IdRange {
+    /// The range of standard identifiers, the bounds are masked by [`SFF_MASK`].
+    #[inline]
+    pub fn standard(range: RangeInclusive<u16>) -> Self {
+        let mask = |v: u16| v & SFF_MASK as u16;
+        Self { start: Id::Standard(mask(*range.start())), end: Id::Standard(mask(*range.end())) }
+    }
+
+    /// The range of extended identifiers, the bounds are masked by [`EFF_MASK`].
+    #[inline]
+    pub fn extended(range: RangeInclusive<u32>) -> Self {
+        let mask = |v: u32| v & EFF_MASK;
+        Self { start: Id::Extended(mask(*range.start())), end: Id::Extended(mask(*range.end())) }
+    }
+
+    /// Returns true if `id` is of the width of the range and between the bounds.
+    #[inline]
+    pub fn contains(&self, id: Id) -> bool {
+        id.is_extended() == self.start.is_extended()
+            && (self.start.into_bits()..=self.end.into_bits()).contains(&id.into_bits())
+    }
+
+    /// Returns true if no identifiers are in the range.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.start.is_extended() != self.end.is_extended()
+            || self.start.into_bits() > self.end.into_bits()
+    }
+
+    /// Iterate over the standard identifiers of the range, nothing is yielded by an extended range.
+    pub fn standard_ids(&self) -> impl Iterator<Item = Id> {
+        let bits = match (self.start, self.end) {
+            (Id::Standard(start), Id::Standard(end)) => Some(start..=end),
+            _ => None,
+        };
+
+        bits.into_iter()
+            .flatten()
+            .map(Id::Standard)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::identifier::Id;
+    use super::IdRange;
+
+    #[test]
+    fn test_contains() {
+        let range = IdRange::standard(0x700..=0x7FF);
+        assert!(range.contains(Id::Standard(0x700)));
+        assert!(range.contains(Id::Standard(0x7FF)));
+        assert!(!range.contains(Id::Standard(0x6FF)));
+        assert!(!range.contains(Id::Extended(0x700)));
+        assert!(!range.is_empty());
+
+        let range = IdRange::extended(0x18DA00F1..=0x18DAFFF1);
+        assert!(range.contains(Id::Extended(0x18DA00F1)));
+        assert!(range.contains(Id::Extended(0x18DAFFF1)));
+        assert!(!range.contains(Id::Extended(0x18DAFFF2)));
+        assert!(!range.contains(Id::Standard(0x7FF)));
+        assert!(range.contains(Id::from_bits(0x18DAF1F1, true).as_j1939().map(Id::J1939).unwrap()));
+
+        // masked
+        assert_eq!(IdRange::standard(0x700..=0xFFFF).end, Id::Standard(0x7FF));
+        let empty = IdRange { start: Id::Standard(0x7FF), end: Id::Standard(0x700) };
+        assert!(empty.is_empty());
+        assert!(!empty.contains(Id::Standard(0x700)));
+    }
+
+    #[test]
+    fn test_standard_ids() {
+        let ids = IdRange::standard(0x7E0..=0x7E7).standard_ids().collect::<Vec<_>>();
+        assert_eq!(ids.len(), 8);
+        assert_eq!(ids.first(), Some(&Id::Standard(0x7E0)));
+        assert_eq!(ids.last(), Some(&Id::Standard(0x7E7)));
+
+        let empty = IdRange { start: Id::Standard(0x7FF), end: Id::Standard(0x700) };
+        assert_eq!(empty.standard_ids().count(), 0);
+        assert_eq!(IdRange::extended(0x00..=0x10).standard_ids().count(), 0);
+    }
+}
@@ -0,0 +1,265 @@
+use crate::constant::{EFF_MASK, SFF_MASK};
+
+/// The functional request identifier of OBD-II.
+pub const OBD2_FUNCTIONAL_ID: u32 = 0x7DF;
+/// The physical request identifier of OBD-II ECU 0, ECU `n` requests on `0x7E0 + n`.
+pub const OBD2_REQUEST_ID: u32 = 0x7E0;
+/// The physical response identifier of OBD-II ECU 0, ECU `n` responds on `0x7E8 + n`.
+pub const OBD2_RESPONSE_ID: u32 = 0x7E8;
+/// The count of OBD-II ECUs of 11-bit identifiers.
+pub const OBD2_ECU_COUNT: u8 = 8;
+/// The PDU format of the physical identifiers of the normal fixed addressing, e.g. UDS on J1939.
+pub const NORMAL_FIXED_PHYSICAL_PF: u8 = 0xDA;
+/// The PDU format of the functional identifiers of the normal fixed addressing.
+pub const NORMAL_FIXED_FUNCTIONAL_PF: u8 = 0xDB;
+/// The functional target address of all nodes of the normal fixed addressing(OBD on J1939).
+pub const NORMAL_FIXED_FUNCTIONAL_TA: u8 = 0x33;
+
+/// The transmit identifier of an [`Address`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct TxId(pub u32);
+
+/// The receive identifier of an [`Address`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct RxId(pub u32);
+
+/// The functional address identifier of an [`Address`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct FunctionalId(pub u32);
+
+macro_rules! raw_id {
+    ($($name:ident),+) => {
+        $(
+            impl From<u32> for $name {
+                #[inline]
+                fn from(value: u32) -> Self {
+                    Self(value)
+                }
+            }
+
+            impl From<$name> for u32 {
+                #[inline]
+                fn from(value: $name) -> Self {
+                    value.0
+                }
+            }
+        )+
+    };
+}
+
+raw_id!(TxId, RxId, FunctionalId);
+
+/// ISO-TP address.
+///
+/// * `tx_id`: transmit identifier.
+/// * `rx_id`: receive identifier.
+/// * `fid`: functional address identifier.
+/// * `extended`: the identifiers are 29-bit, the frames are sent with extended identifiers.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Address {
+    pub tx_id: u32,
+    pub rx_id: u32,
+    pub fid: u32,
+    pub extended: bool,
+}
+
+impl Address {
+    /// Constructs from the typed identifiers, so they can not be swapped by mistake.
+    ///
+    /// The address is extended if any of the identifiers is out of the 11-bit range.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use can_type_rs::isotp::{Address, FunctionalId, RxId, TxId};
+    ///
+    /// let address = Address::new(TxId(0x7E0), RxId(0x7E8), FunctionalId(0x7DF));
+    /// assert_eq!((address.tx_id, address.rx_id, address.fid), (0x7E0, 0x7E8, 0x7DF));
+    /// ```
+    ///
+    /// The identifiers are checked by their slots:
+    /// ```compile_fail
+    /// use can_type_rs::isotp::{Address, FunctionalId, RxId, TxId};
+    ///
+    /// let address = Address::new(RxId(0x7E8), TxId(0x7E0), FunctionalId(0x7DF));
+    /// ```
+    #[inline]
+    pub const fn new(tx_id: TxId, rx_id: RxId, fid: FunctionalId) -> Self {
+        let extended = tx_id.0 & EFF_MASK > SFF_MASK
+            || rx_id.0 & EFF_MASK > SFF_MASK
+            || fid.0 & EFF_MASK > SFF_MASK;
+        Self { tx_id: tx_id.0, rx_id: rx_id.0, fid: fid.0, extended }
+    }
+
+    /// Returns the address of the peer, i.e. `tx_id` and `rx_id` are exchanged, `fid` and `extended` are unchanged.
+    #[inline]
+    pub const fn swapped(&self) -> Self {
+        Self { tx_id: self.rx_id, rx_id: self.tx_id, fid: self.fid, extended: self.extended }
+    }
+
+    /// The OBD-II address set of functional requests on 0x7DF.
+    ///
+    /// The responses of ECU 0(0x7E8) are received, use [`Self::obd2_physical`] for the other ECUs.
+    #[inline]
+    pub const fn obd2_functional() -> Self {
+        Self { tx_id: OBD2_FUNCTIONAL_ID, rx_id: OBD2_RESPONSE_ID, fid: OBD2_FUNCTIONAL_ID, extended: false }
+    }
+
+    /// The OBD-II address set of ECU `ecu`(0..=7), `None` if out of range.
+    #[inline]
+    pub const fn obd2_physical(ecu: u8) -> Option<Self> {
+        if ecu >= OBD2_ECU_COUNT {
+            return None;
+        }
+
+        Some(Self {
+            tx_id: OBD2_REQUEST_ID + ecu as u32,
+            rx_id: OBD2_RESPONSE_ID + ecu as u32,
+            fid: OBD2_FUNCTIONAL_ID,
+            extended: false,
+        })
+    }
+
+    /// The normal fixed address set of the requests from `source` to `target` of priority 6,
+    /// the functional requests are sent to all nodes(0x33).
+    ///
+    /// # Examples
+    /// ```rust
+    /// use can_type_rs::isotp::Address;
+    ///
+    /// let address = Address::normal_fixed(0xF1, 0x01);
+    /// assert_eq!((address.tx_id, address.rx_id, address.fid), (0x18DA01F1, 0x18DAF101, 0x18DB33F1));
+    /// ```
+    #[inline]
+    pub const fn normal_fixed(source: u8, target: u8) -> Self {
+        Self {
+            tx_id: u32::from_be_bytes([0x18, NORMAL_FIXED_PHYSICAL_PF, target, source]),
+            rx_id: u32::from_be_bytes([0x18, NORMAL_FIXED_PHYSICAL_PF, source, target]),
+            fid: u32::from_be_bytes([0x18, NORMAL_FIXED_FUNCTIONAL_PF, NORMAL_FIXED_FUNCTIONAL_TA, source]),
+            extended: true,
+        }
+    }
+
+    /// Returns the address of the responder of a normal fixed address, derived from `tx_id`
+    /// by exchanging the source and the target address, the priority and `fid` are kept.
+    ///
+    /// The responses are sent on its `tx_id`, i.e. the identifier to match the responses on.
+    /// `None` if `tx_id` is not a physical identifier of the normal fixed addressing.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use can_type_rs::isotp::Address;
+    ///
+    /// let request = Address::normal_fixed(0xF1, 0x01);
+    /// let response = request.j1939_response().unwrap();
+    /// assert_eq!((response.tx_id, response.rx_id), (0x18DAF101, 0x18DA01F1));
+    /// assert_eq!(response.j1939_response(), Some(request));
+    /// ```
+    #[inline]
+    pub const fn j1939_response(&self) -> Option<Self> {
+        let [priority, pdu_format, target, source] = (self.tx_id & EFF_MASK).to_be_bytes();
+        if !self.extended || pdu_format != NORMAL_FIXED_PHYSICAL_PF {
+            return None;
+        }
+
+        Some(Self {
+            tx_id: u32::from_be_bytes([priority, pdu_format, source, target]),
+            rx_id: self.tx_id & EFF_MASK,
+            fid: self.fid,
+            extended: true,
+        })
+    }
+
+    /// Returns the ECU index of an OBD-II response identifier(0x7E8..=0x7EF).
+    #[inline]
+    pub const fn obd2_ecu(response_id: u32) -> Option<u8> {
+        match response_id.checked_sub(OBD2_RESPONSE_ID) {
+            Some(v) if v < OBD2_ECU_COUNT as u32 => Some(v as u8),
+            _ => None,
+        }
+    }
+}
+
+impl From<isotp_rs::can::Address> for Address {
+    /// The address is extended if any of the identifiers is out of the 11-bit range.
+    #[inline]
+    fn from(value: isotp_rs::can::Address) -> Self {
+        Self::new(TxId(value.tx_id), RxId(value.rx_id), FunctionalId(value.fid))
+    }
+}
+
+impl From<Address> for isotp_rs::can::Address {
+    #[inline]
+    fn from(value: Address) -> Self {
+        Self { tx_id: value.tx_id, rx_id: value.rx_id, fid: value.fid }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_obd2() {
+        let functional = Address::obd2_functional();
+        assert_eq!(functional.fid, 0x7DF);
+        assert_eq!(functional.rx_id, 0x7E8);
+
+        assert_eq!(Address::obd2_ecu(0x7E8), Some(0));
+        assert_eq!(Address::obd2_ecu(0x7EF), Some(7));
+        assert_eq!(Address::obd2_ecu(0x7E7), None);
+        assert_eq!(Address::obd2_ecu(0x7F0), None);
+
+        let ecu7 = Address::obd2_physical(7).unwrap();
+        assert_eq!((ecu7.tx_id, ecu7.rx_id), (0x7E7, 0x7EF));
+        assert_eq!(Address::obd2_ecu(ecu7.rx_id), Some(7));
+        assert!(Address::obd2_physical(8).is_none());
+        assert!(!ecu7.extended);
+    }
+
+    #[test]
+    fn test_swapped() {
+        let address = Address::obd2_physical(1).unwrap();
+        let peer = address.swapped();
+        assert_eq!((peer.tx_id, peer.rx_id, peer.fid), (0x7E9, 0x7E1, 0x7DF));
+        assert_eq!(peer.swapped(), address);
+    }
+
+    #[test]
+    fn test_new() {
+        let address = Address::new(TxId(0x7E0), RxId::from(0x7E8), 0x7DF.into());
+        assert_eq!(address, Address { tx_id: 0x7E0, rx_id: 0x7E8, fid: 0x7DF, extended: false });
+        assert_eq!(u32::from(RxId(address.rx_id)), 0x7E8);
+
+        let address = Address::new(TxId(0x18DA01F1), RxId(0x18DAF101), FunctionalId(0x18DB33F1));
+        assert!(address.extended);
+        assert_eq!(address.swapped(), Address::new(TxId(0x18DAF101), RxId(0x18DA01F1), FunctionalId(0x18DB33F1)));
+    }
+
+    #[test]
+    fn test_j1939_response() {
+        let request = Address::normal_fixed(0xF9, 0x00);
+        assert_eq!(request, Address::new(TxId(0x18DA00F9), RxId(0x18DAF900), FunctionalId(0x18DB33F9)));
+
+        let response = request.j1939_response().unwrap();
+        assert_eq!(response, request.swapped());
+        assert_eq!(response.j1939_response(), Some(request));
+
+        // the priority is kept and the rx_id is derived
+        let request = Address { tx_id: 0x0CDA21F1, rx_id: 0, fid: 0x18DB33F1, extended: true };
+        let response = request.j1939_response().unwrap();
+        assert_eq!((response.tx_id, response.rx_id, response.fid), (0x0CDAF121, 0x0CDA21F1, 0x18DB33F1));
+
+        assert_eq!(Address::obd2_physical(0).unwrap().j1939_response(), None);
+        // functional
+        let functional = Address { tx_id: 0x18DB33F1, ..request };
+        assert_eq!(functional.j1939_response(), None);
+    }
+
+    #[test]
+    fn test_from_isotp_address() {
+        let address = Address::from(isotp_rs::can::Address { tx_id: 0x7E0, rx_id: 0x7E8, fid: 0x7DF });
+        assert!(!address.extended);
+        let address = Address::from(isotp_rs::can::Address { tx_id: 0x18DA01F1, rx_id: 0x18DAF101, fid: 0x18DB33F1 });
+        assert!(address.extended);
+    }
+}
@@ -1,167 +1,668 @@
 mod listener;
 
-use std::sync::{Arc, Mutex};
-use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, RwLock, RwLockWriteGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{SendError, Sender};
 use tokio::time::sleep;
 use std::time::Duration;
-use isotp_rs::{FlowControlContext, FlowControlState, IsoTpEvent, IsoTpEventListener, IsoTpFrame, IsoTpState, can::{Address, CanIsoTpFrame}};
+use std::time::Instant;
+use isotp_rs::{FlowControlContext, FlowControlState, IsoTpFrame, IsoTpState, can::{CanIsoTpFrame, ISO_TP_MAX_LENGTH_2004}};
+use isotp_rs::constant::ST_MIN_ISO15765_2;
 use isotp_rs::error::Error as IsoTpError;
+use crate::device::AsyncDevice;
 use crate::frame::Frame;
 use crate::identifier::Id;
-use crate::isotp::context::IsoTpContext;
+use crate::isotp::{Address, DEFAULT_WFT_MAX, Error, IsoTpEvent, IsoTpEventListener, OnDecodeError, RetryPolicy, util};
+use crate::isotp::context::{ContextSnapshot, IsoTpContext, Retained};
+#[cfg(test)]
+use crate::isotp::context::ACQUISITIONS;
+use crate::isotp::logger::{Logger, RawFrameObserver};
+#[cfg(feature = "metrics")]
+use crate::isotp::TransferMetrics;
 
+/// An ISO-TP session of a channel, the clones share the state of the session.
+///
+/// It is `Send` if the channel and the frame are `Send`:
+/// ```compile_fail
+/// use std::rc::Rc;
+/// use can_type_rs::isotp::AsyncCanIsoTp;
+///
+/// fn assert_send<T: Send>() {}
+/// assert_send::<AsyncCanIsoTp<Rc<u8>, ()>>();
+/// ```
 #[derive(Clone)]
 pub struct AsyncCanIsoTp<C, F> {
     pub(crate) channel: C,
     pub(crate) address: Address,
     pub(crate) sender: Sender<F>,
-    pub(crate) context: IsoTpContext,
+    pub(crate) context: Arc<RwLock<IsoTpContext>>,
     pub(crate) state: Arc<Mutex<IsoTpState>>,
-    pub(crate) listener: Arc<Mutex<Box<dyn IsoTpEventListener>>>,
+    pub(crate) listener: Arc<Mutex<Box<dyn IsoTpEventListener + Send>>>,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) retained: Arc<Mutex<Retained>>,
+    pub(crate) zero_copy: bool,
+    pub(crate) block_size: u8,
+    pub(crate) max_receive_length: usize,
+    pub(crate) min_single_frame_length: usize,
+    pub(crate) wft_max: u8,
+    pub(crate) min_gap: Duration,
+    pub(crate) padding: Option<u8>,
+    pub(crate) can_fd: bool,
+    pub(crate) on_decode_error: OnDecodeError,
+    pub(crate) decode_errors: Arc<AtomicUsize>,
+    pub(crate) logger: Logger,
+    pub(crate) raw_observer: Arc<RwLock<Option<RawFrameObserver<F>>>>,
 }
 
-unsafe impl<C, F> Send for AsyncCanIsoTp<C, F> {}
-
 impl<C: Clone, F: Frame<Channel = C>> AsyncCanIsoTp<C, F> {
 
     pub fn new(channel: C,
-               address: Address,
+               address: impl Into<Address>,
                sender: Sender<F>,
-               listener: Box<dyn IsoTpEventListener>
+               listener: Box<dyn IsoTpEventListener + Send>
     ) -> Self {
         Self {
             channel,
-            address,
+            address: address.into(),
             sender,
             context: Default::default(),
             state: Default::default(),
             listener: Arc::new(Mutex::new(listener)),
+            retry: Default::default(),
+            retained: Default::default(),
+            zero_copy: false,
+            block_size: 0,
+            max_receive_length: ISO_TP_MAX_LENGTH_2004,
+            min_single_frame_length: 0,
+            wft_max: DEFAULT_WFT_MAX,
+            min_gap: Duration::ZERO,
+            padding: None,
+            can_fd: cfg!(feature = "can-fd"),
+            on_decode_error: Default::default(),
+            decode_errors: Default::default(),
+            logger: Default::default(),
+            raw_observer: Default::default(),
+        }
+    }
+
+    /// Retry the frames that are not sent to the device, and retransmit the ones that the device failed
+    /// to transmit by `policy`, see [`Listener::on_frame_transmit_failed`](crate::device::Listener::on_frame_transmit_failed).
+    ///
+    /// The retransmission is spawned to the runtime of the callback to wait the backoff, the backoff
+    /// is waited by blocking if the callback is not called in a runtime. The retries of the flow control
+    /// frames are waited by blocking as they are sent in the callbacks.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Deliver the received messages by [`IsoTpEventListener::on_data_received`] with a borrowed slice
+    /// instead of [`IsoTpEvent::DataReceived`], the reassembly buffer is reused without cloning.
+    pub fn with_zero_copy(mut self, enabled: bool) -> Self {
+        self.zero_copy = enabled;
+        self
+    }
+
+    /// The block size(BS) of the flow control frames sent when receiving, a new flow control frame
+    /// is sent after every `block_size` consecutive frames. 0 means no more flow control frame.
+    pub fn with_block_size(mut self, block_size: u8) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// The max data length of the received messages, 4095 by default.
+    ///
+    /// A first frame that declares a longer length is rejected by an overflow flow control frame
+    /// before the buffer is allocated.
+    pub fn with_max_receive_length(mut self, length: usize) -> Self {
+        self.max_receive_length = length;
+        self
+    }
+
+    /// The min data length of the received single frames, 0 by default, e.g. 1 for UDS responses.
+    ///
+    /// A shorter single frame is not delivered but reported by [`IsoTpEvent::ErrorOccurred`],
+    /// a single frame that declares more bytes than it carries is always rejected.
+    pub fn with_min_single_frame_length(mut self, length: usize) -> Self {
+        self.min_single_frame_length = length;
+        self
+    }
+
+    /// The max count of consecutive `Wait` flow control frames tolerated when writing(N_WFTmax), 10 by default.
+    ///
+    /// The write is aborted with [`Error::WaitOverflow`] when more `Wait` frames are received in a row.
+    pub fn with_wft_max(mut self, max: u8) -> Self {
+        self.wft_max = max;
+        self
+    }
+
+    /// The min gap between the consecutive frames when writing, 0 by default,
+    /// e.g. to throttle the writes on a bus that is shared with time-critical traffic.
+    ///
+    /// The effective gap is the max of it and the separation time(STmin) of the flow control.
+    pub fn with_min_gap(mut self, gap: Duration) -> Self {
+        self.min_gap = gap;
+        self
+    }
+
+    /// The padding value of the written frames and the flow control frames,
+    /// [`isotp_rs::can::DEFAULT_PADDING`] is used when `None`.
+    ///
+    /// [`Self::write_raw`] is padded by its own `padding`.
+    pub fn with_padding(mut self, padding: Option<u8>) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Returns the padding value set by [`Self::with_padding`].
+    #[inline]
+    pub fn padding(&self) -> Option<u8> {
+        self.padding
+    }
+
+    /// Segment and pad the written frames for CAN-FD instead of classic CAN, e.g. to talk to both classic
+    /// and CAN-FD nodes by one build, it is enabled by default with the `can-fd` feature.
+    ///
+    /// The received frames of both are decoded.
+    pub fn with_can_fd(mut self, enabled: bool) -> Self {
+        self.can_fd = enabled;
+        self
+    }
+
+    /// What is done to the received frames that fail to be decoded, [`OnDecodeError::Skip`] by default.
+    pub fn with_decode_error_policy(mut self, policy: OnDecodeError) -> Self {
+        self.on_decode_error = policy;
+        self
+    }
+
+    /// Returns the count of the frames that fail to be decoded by [`OnDecodeError::Count`]
+    /// of this channel and its clones.
+    #[inline]
+    pub fn decode_errors(&self) -> usize {
+        self.decode_errors.load(Ordering::Relaxed)
+    }
+
+    /// Route the log messages of this channel and its clones to `hook` instead of the `log` facade,
+    /// e.g. to downgrade the decoding failures of a busy bus or to count them.
+    pub fn set_log_hook(&mut self, hook: impl Fn(log::Level, &str) + Send + Sync + 'static) {
+        self.logger.set_hook(Box::new(hook));
+    }
+
+    /// Observe every raw frame that this channel and its clones receive on `rx_id` before decoding,
+    /// e.g. to capture the exact bytes of a frame that fails to be decoded.
+    pub fn set_raw_frame_observer(&mut self, observer: impl Fn(&F) + Send + Sync + 'static) {
+        match self.raw_observer.write() {
+            Ok(mut v) => *v = Some(Box::new(observer)),
+            Err(_) => self.logger.warn(format_args!("ISO-TP: raw frame observer lock is poisoned")),
         }
     }
 
+    /// Pre-allocate `capacity` bytes of the reassembly buffer, it only grows for larger messages.
+    pub fn with_receive_capacity(mut self, capacity: usize) -> Self {
+        self.context = Arc::new(RwLock::new(IsoTpContext::with_capacity(capacity)));
+        self
+    }
+
     pub async fn write(&mut self, functional: bool, data: Vec<u8>) -> Result<(), IsoTpError> {
-        log::debug!("ISO-TP(CAN async) - Sending: {:?}", data);
-        let frames = CanIsoTpFrame::from_data(data)?;
+        let can_id = if functional { self.address.fid } else { self.address.tx_id };
+        self.write_to(can_id, data).await
+    }
+
+    /// Write to the explicit identifier `target_id` instead of `tx_id` or `fid` of the address,
+    /// e.g. a functional group identifier that is addressed dynamically.
+    ///
+    /// The flow control is still received on `rx_id`.
+    pub async fn write_to(&mut self, target_id: u32, data: Vec<u8>) -> Result<(), IsoTpError> {
+        self.logger.debug(format_args!("ISO-TP(CAN async) - Sending to {:#X}: {:?}", target_id, data));
+        #[cfg(feature = "metrics")]
+        let (started, bytes) = (Instant::now(), data.len());
+        let frames = util::segment(&data, self.can_fd)?;
+
+        #[cfg(feature = "metrics")]
         let frame_len = frames.len();
+        #[cfg(feature = "metrics")]
+        if let Some(mut context) = self.context_mut() {
+            context.metrics.start_writing();
+        }
+
+        let result = self.write_frames(target_id, frames, self.padding).await;
+        if result.is_err() {
+            self.write_reset();
+        }
+        #[cfg(feature = "metrics")]
+        if let (Ok(_), Some(mut context)) = (&result, self.context_mut()) {
+            context.metrics.complete_writing(started, bytes, frame_len);
+        }
 
+        result
+    }
+
+    /// Transmit the pre-segmented `frames` as-is without re-segmenting, e.g. to replay a captured transfer.
+    ///
+    /// The flow control is still waited after a first frame, the frames are padded with `padding`
+    /// or [`isotp_rs::can::DEFAULT_PADDING`].
+    pub async fn write_raw(&mut self, functional: bool, frames: Vec<CanIsoTpFrame>, padding: Option<u8>) -> Result<(), IsoTpError> {
+        self.logger.debug(format_args!("ISO-TP(CAN async) - Sending raw: {:?}", frames));
         let can_id = if functional { self.address.fid } else { self.address.tx_id };
+
+        let result = self.write_frames(can_id, frames, padding).await;
+        if result.is_err() {
+            self.write_reset();
+        }
+
+        result
+    }
+
+    /// Reset the state after a failed write, the channel is usable for the next write.
+    fn write_reset(&self) {
+        self.state_reset();
+        if let Some(mut context) = self.context_mut() {
+            context.clear_flow_ctrl();
+        }
+    }
+
+    async fn write_frames(&mut self, can_id: u32, frames: Vec<CanIsoTpFrame>, padding: Option<u8>) -> Result<(), IsoTpError> {
+        if let Some(mut context) = self.context_mut() {
+            context.tx.write_id = Some(can_id);
+        }
         for (index, frame) in frames.into_iter().enumerate() {
             self.write_waiting(index).await?;
-            let mut frame = F::from_iso_tp(Id::from_bits(can_id, false), frame, None)
-                .ok_or(IsoTpError::ConvertError {
-                    src: "iso-tp frame",
-                    target: "can-frame",
-                })?;
-            frame.set_channel(self.channel.clone());
+            let (first, consecutive) = (
+                matches!(frame, CanIsoTpFrame::FirstFrame { .. }),
+                matches!(frame, CanIsoTpFrame::ConsecutiveFrame { .. }),
+            );
+            self.retain_frame(can_id, &frame, padding);
+            let frame = self.new_frame(can_id, frame, padding)?;
 
             self.state_append(IsoTpState::Sending);
-            if 0 == index && 1 < frame_len  {
+            if first {
                 self.state_append(IsoTpState::WaitFlowCtrl);
             }
-            self.sender.send(frame)
-                .map_err(|e| {
-                    log::warn!("ISO-TP(CAN async) - transmit failed: {:?}", e);
-                    IsoTpError::DeviceError
-                })?;
+            if consecutive {
+                self.count_block_frame();
+            }
+            let mut pending = Some(frame);
+            self.retry.retry_async(|| match pending.take() {
+                Some(frame) => self.sender.send(frame)
+                    .map_err(|SendError(frame)| {
+                        self.logger.warn(format_args!("ISO-TP(CAN async) - transmit failed"));
+                        pending = Some(frame);
+                        IsoTpError::DeviceError
+                    }),
+                None => Err(IsoTpError::DeviceError),
+            }).await?;
         }
 
         Ok(())
     }
 
-    #[inline]
-    pub(crate) fn on_single_frame(&mut self, data: Vec<u8>) {
-        self.iso_tp_event(IsoTpEvent::DataReceived(data));
+    /// Send `frame` to the device, the failed sends are retried by the retry policy.
+    ///
+    /// It is called by the device callbacks, so the backoff is waited by blocking.
+    fn send(&self, frame: F) -> Result<(), IsoTpError> {
+        let mut pending = Some(frame);
+        self.retry.retry(|| match pending.take() {
+            Some(frame) => self.sender.send(frame)
+                .map_err(|SendError(frame)| {
+                    self.logger.warn(format_args!("ISO-TP(CAN async) - transmit failed"));
+                    pending = Some(frame);
+                    IsoTpError::DeviceError
+                }),
+            None => Err(IsoTpError::DeviceError),
+        })
     }
 
-    #[inline]
-    pub(crate) fn on_first_frame(&mut self, length: u32, data: Vec<u8>) {
-        self.context.update_consecutive(length, data);
+    /// Build the CAN frame of `frame` on the channel.
+    fn new_frame(&self, can_id: u32, frame: CanIsoTpFrame, padding: Option<u8>) -> Result<F, IsoTpError> {
+        // a short single frame of CAN-FD is padded to 8 bytes only
+        let mut frame = F::from_iso_tp_sized(Id::from_bits(can_id, self.address.extended), frame, padding, self.can_fd)
+            .ok_or(IsoTpError::ConvertError {
+                src: "iso-tp frame",
+                target: "can-frame",
+            })?;
+        frame.set_channel(self.channel.clone());
 
-        let iso_tp_frame = CanIsoTpFrame::default_flow_ctrl_frame();
+        Ok(frame)
+    }
 
-        match F::from_iso_tp(
-            Id::from_bits(self.address.tx_id, false),
-            iso_tp_frame,
-            None
-        ) {
-            Some(mut frame) => {
-                frame.set_channel(self.channel.clone());
+    /// Keep the frame being written to be retransmitted, nothing is kept if the retry policy does not retry.
+    fn retain_frame(&self, can_id: u32, frame: &CanIsoTpFrame, padding: Option<u8>) {
+        if self.retry.max_retries == 0 {
+            return;
+        }
 
-                self.state_append(IsoTpState::Sending);
-                match self.sender.send(frame) {
-                    Ok(_) => {
-                        self.iso_tp_event(IsoTpEvent::FirstFrameReceived);
-                    },
-                    Err(e) => {
-                        log::warn!("ISO-TP - transmit failed: {:?}", e);
-                        self.state_append(IsoTpState::Error);
+        match self.retained.lock() {
+            Ok(mut retained) => {
+                retained.frame = Some((can_id, frame.clone(), padding));
+                retained.retries = 0;
+            },
+            Err(_) => self.logger.warn(format_args!("ISO-TP: retained frame lock is poisoned")),
+        }
+    }
 
-                        self.iso_tp_event(IsoTpEvent::ErrorOccurred(IsoTpError::DeviceError));
-                    },
-                }
+    /// Wait until the transfer in flight completes or `timeout` elapses, then unregister the listener
+    /// `name` of this channel from `device` and close the device.
+    ///
+    /// Returns false if timed out, the unfinished transfer is discarded then.
+    pub async fn close_gracefully<D>(&mut self, device: &mut D, name: &str, timeout: Duration) -> bool
+    where
+        D: AsyncDevice<Channel = C, Frame = F> {
+        let start = Instant::now();
+        let idle = loop {
+            if self.is_idle() {
+                break true;
+            }
+            if start.elapsed() >= timeout {
+                self.logger.warn(format_args!("ISO-TP(CAN async) - close timed out, the transfer is discarded"));
+                break false;
+            }
+            sleep(Duration::from_micros(10)).await;
+        };
+
+        device.unregister_listener(name.to_owned());
+        device.close().await;
+
+        self.state_reset();
+        if let Some(mut context) = self.context_mut() {
+            context.reset();
+        }
+
+        idle
+    }
+
+    /// Discard the data of the message being received, the state flags and the flow control are kept.
+    pub fn clear_data(&self) {
+        if let Some(mut context) = self.context_mut() {
+            context.clear_data();
+        }
+    }
+
+    /// Pause the sender of the message being received by a `Wait` flow control frame, e.g. when busy.
+    ///
+    /// Returns false if no message is being received or the frame is not sent, see [`Self::resume`].
+    pub fn request_wait(&self) -> bool {
+        match self.context.read() {
+            Ok(context) if context.rx.consecutive.length.is_some() => {
+                drop(context);
+                self.write_flow_ctrl(FlowControlState::Wait)
+            },
+            _ => false,
+        }
+    }
+
+    /// Resume the sender paused by [`Self::request_wait`] with a `ContinueToSend` flow control frame,
+    /// a new block of consecutive frames is started.
+    ///
+    /// Returns false if no message is being received or the frame is not sent.
+    pub fn resume(&self) -> bool {
+        match self.context_mut() {
+            Some(mut context) if context.rx.consecutive.length.is_some() => {
+                context.rx.block_frames = 0;
+                drop(context);
+                self.write_flow_ctrl(FlowControlState::Continues)
             },
-            None => log::error!("ISO-TP: convert `iso-tp frame` to `can-frame` error"),
+            _ => false,
+        }
+    }
+
+    /// Copy the address, state, flow control and reassembly progress of the channel for diagnostics.
+    pub fn snapshot(&self) -> Option<ContextSnapshot> {
+        let context = self.context.read().ok()?;
+        let state = *self.state.lock().ok()?;
+
+        Some(context.snapshot(self.address, state))
+    }
+
+    /// Returns the count of bytes still expected of the message being received, i.e. the declared length
+    /// minus the bytes received so far, e.g. to request a larger block size when lots remain.
+    ///
+    /// `None` if no segmented message is being received, a single frame is never in progress.
+    pub fn remaining(&self) -> Option<usize> {
+        self.context.read().ok()?.remaining()
+    }
+
+    /// Returns the block size requested by the most recent flow control frame,
+    /// `None` if no flow control frame is received since the last reset.
+    pub fn current_block_size(&self) -> Option<u8> {
+        self.context.read().ok()?
+            .tx.flow_ctrl.as_ref()
+            .map(|ctx| ctx.block_size)
+    }
+
+    /// Returns the metrics of the last completed write.
+    #[cfg(feature = "metrics")]
+    pub fn write_metrics(&self) -> Option<TransferMetrics> {
+        self.context.read().ok()?.metrics.written
+    }
+
+    /// Returns the metrics of the last received message.
+    #[cfg(feature = "metrics")]
+    pub fn receive_metrics(&self) -> Option<TransferMetrics> {
+        self.context.read().ok()?.metrics.received
+    }
+
+    /// Process a batch of received frames while holding the context lock only once.
+    pub(crate) fn on_frames(&mut self, frames: &[CanIsoTpFrame]) {
+        let mut context = match self.context_mut() {
+            Some(v) => v,
+            None => return,
+        };
+
+        let mut idle = false;
+        for frame in frames {
+            match frame {
+                CanIsoTpFrame::SingleFrame { data } if data.len() < self.min_single_frame_length => {
+                    self.logger.warn(format_args!("ISO-TP - single frame length: {} is less than the min: {}", data.len(), self.min_single_frame_length));
+                    // the same as a valid single frame, a segmented message being received is kept
+                    self.iso_tp_event(IsoTpEvent::ErrorOccurred(IsoTpError::InvalidDataLength {
+                        actual: data.len(),
+                        expect: self.min_single_frame_length,
+                    }.into()));
+                },
+                CanIsoTpFrame::SingleFrame { data } => {
+                    #[cfg(feature = "metrics")]
+                    {
+                        context.metrics.start_receiving();
+                        context.metrics.complete_receiving(data.len());
+                    }
+                    self.on_single_frame(data);
+                }
+                CanIsoTpFrame::FirstFrame { length, data } => {
+                    self.on_first_frame(&mut context, *length, data);
+                }
+                CanIsoTpFrame::ConsecutiveFrame { sequence, data } => {
+                    self.on_consecutive_frame(&mut context, *sequence, data);
+                },
+                CanIsoTpFrame::FlowControlFrame(ctx) => {
+                    idle |= self.on_flow_ctrl_frame(&mut context, *ctx);
+                },
+            }
+        }
+        // the listener may start the next write that locks the context
+        drop(context);
+        if idle {
+            self.iso_tp_event(IsoTpEvent::Idle);
         }
     }
 
     #[inline]
-    pub(crate) fn on_consecutive_frame(&mut self, sequence: u8, data: Vec<u8>) {
-        match self.context.append_consecutive(sequence, data) {
-            Ok(event) => {
-                match event {
-                    IsoTpEvent::DataReceived(_) => {
-                        self.context.reset();
-                    },
-                    _ => {},
+    pub(crate) fn on_single_frame(&self, data: &[u8]) {
+        self.iso_tp_event(IsoTpEvent::ReceiveCompleted { frame_count: 1, segmented: false });
+        match self.zero_copy {
+            true => self.data_received(data),
+            false => self.iso_tp_event(IsoTpEvent::DataReceived(data.to_vec())),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn on_first_frame(&self, context: &mut IsoTpContext, length: u32, data: &[u8]) {
+        if length as usize > self.max_receive_length {
+            self.logger.warn(format_args!("ISO-TP - first frame length: {} exceeds the max: {}", length, self.max_receive_length));
+            context.clear_data();
+            self.write_flow_ctrl(FlowControlState::Overload);
+            self.iso_tp_event(IsoTpEvent::ErrorOccurred(IsoTpError::LengthOutOfRange(length as usize).into()));
+            return;
+        }
+
+        context.update_consecutive(length, data);
+        #[cfg(feature = "metrics")]
+        context.metrics.start_receiving();
+
+        if self.write_flow_ctrl(FlowControlState::Continues) {
+            self.iso_tp_event(IsoTpEvent::FirstFrame { length });
+        }
+    }
+
+    #[inline]
+    pub(crate) fn on_consecutive_frame(&self, context: &mut IsoTpContext, sequence: u8, data: &[u8]) {
+        // the count of frames if this one completes the message
+        let frame_count = context.rx.frames + 1;
+        match context.append_consecutive(sequence, data) {
+            Ok(Some(data)) => {
+                #[cfg(feature = "metrics")]
+                let length = data.len();
+                self.iso_tp_event(IsoTpEvent::ReceiveCompleted { frame_count, segmented: true });
+                match self.zero_copy {
+                    true => self.data_received(data),
+                    false => self.iso_tp_event(IsoTpEvent::DataReceived(data.to_vec())),
+                }
+                #[cfg(feature = "metrics")]
+                {
+                    context.metrics.frame_received();
+                    context.metrics.complete_receiving(length);
+                }
+                // the flow control of a message being written is kept
+                context.clear_data();
+            },
+            Ok(None) => {
+                #[cfg(feature = "metrics")]
+                context.metrics.frame_received();
+                self.iso_tp_event(IsoTpEvent::Wait);
+                if context.block_exhausted(self.block_size) {
+                    self.write_flow_ctrl(FlowControlState::Continues);
                 }
-                self.iso_tp_event(event);
             },
             Err(e) => {
-                self.state_append(IsoTpState::Error);
+                // only the message being received is dropped, the next first frame is accepted
+                context.clear_data();
                 self.iso_tp_event(IsoTpEvent::ErrorOccurred(e));
             }
         }
     }
 
+    /// Returns true if the state turns into idle, [`IsoTpEvent::Idle`] is sent by the caller
+    /// after the context lock is released.
     #[inline]
-    pub(crate) fn on_flow_ctrl_frame(&mut self, ctx: FlowControlContext) {
-        match ctx.state() {
+    pub(crate) fn on_flow_ctrl_frame(&self, context: &mut IsoTpContext, ctx: FlowControlContext) -> bool {
+        self.iso_tp_event(IsoTpEvent::FlowControl {
+            state: ctx.state(),
+            block_size: ctx.block_size(),
+            st_min: ctx.st_min(),
+        });
+
+        let idle = match ctx.state() {
             FlowControlState::Continues => {
-                self.state_remove(IsoTpState::WaitBusy | IsoTpState::WaitFlowCtrl);
+                self.state_transit(|v| v.remove(IsoTpState::WaitBusy | IsoTpState::WaitFlowCtrl))
             },
             FlowControlState::Wait => {
+                #[cfg(feature = "metrics")]
+                { context.metrics.flow_control_waits += 1; }
+                context.tx.wait_frames = context.tx.wait_frames.saturating_add(1);
+                if context.tx.wait_frames > self.wft_max {
+                    self.logger.warn(format_args!("ISO-TP - wait flow control frames exceed the max: {}", self.wft_max));
+                    self.state_append(IsoTpState::Error);
+                    self.iso_tp_event(IsoTpEvent::ErrorOccurred(Error::WaitOverflow(self.wft_max)));
+                    return false;
+                }
                 self.state_append(IsoTpState::WaitBusy);
                 self.iso_tp_event(IsoTpEvent::Wait);
-                return;
+                return false;
             }
             FlowControlState::Overload => {
                 self.state_append(IsoTpState::Error);
-                self.iso_tp_event(IsoTpEvent::ErrorOccurred(IsoTpError::OverloadFlow));
-                return;
+                self.iso_tp_event(IsoTpEvent::ErrorOccurred(IsoTpError::OverloadFlow.into()));
+                return false;
             }
-        }
+        };
+
+        context.update_flow_ctrl(ctx);
+        idle
+    }
+
+    /// Send a flow control frame with the block size, returns true if it is sent.
+    fn write_flow_ctrl(&self, state: FlowControlState) -> bool {
+        let iso_tp_frame = CanIsoTpFrame::flow_ctrl_frame(
+            state,
+            self.block_size,
+            ST_MIN_ISO15765_2,
+        );
 
-        self.context.update_flow_ctrl(ctx);
+        self.retain_frame(self.address.tx_id, &iso_tp_frame, self.padding);
+        match self.new_frame(self.address.tx_id, iso_tp_frame, self.padding) {
+            Ok(frame) => {
+                self.state_append(IsoTpState::Sending);
+                match self.send(frame) {
+                    Ok(_) => true,
+                    Err(e) => {
+                        self.logger.warn(format_args!("ISO-TP - transmit failed: {:?}", e));
+                        self.state_append(IsoTpState::Error);
+
+                        self.iso_tp_event(IsoTpEvent::ErrorOccurred(IsoTpError::DeviceError.into()));
+                        false
+                    },
+                }
+            },
+            Err(_) => {
+                self.logger.error(format_args!("ISO-TP: convert `iso-tp frame` to `can-frame` error"));
+                false
+            },
+        }
     }
 
     fn iso_tp_event(&self, event: IsoTpEvent) {
         match self.listener.lock() {
             Ok(mut listener) => {
                 // println!("ISO-TP(CAN asyn): Sending iso-tp event: {:?}", event);
-                log::trace!("ISO-TP(CAN asyn): Sending iso-tp event: {:?}", event);
+                self.logger.trace(format_args!("ISO-TP(CAN asyn): Sending iso-tp event: {:?}", event));
                 listener.on_iso_tp_event(event);
             },
-            Err(_) => log::warn!("ISO-TP(CAN async): Sending event failed"),
+            Err(_) => self.logger.warn(format_args!("ISO-TP(CAN async): Sending event failed")),
+        }
+    }
+
+    fn data_received(&self, data: &[u8]) {
+        match self.listener.lock() {
+            Ok(mut listener) => {
+                self.logger.trace(format_args!("ISO-TP(CAN async): Sending received data: {:?}", data));
+                listener.on_data_received(data);
+            },
+            Err(_) => self.logger.warn(format_args!("ISO-TP(CAN async): Sending data failed")),
         }
     }
 
     async fn write_waiting(&mut self, index: usize) -> Result<(), IsoTpError> {
-        if let Some(ctx) = &self.context.flow_ctrl {
-            if ctx.block_size != 0 &&
-                0 == ctx.block_size as usize % (index + 1) {
-                self.state_append(IsoTpState::WaitFlowCtrl);
-            }
-            sleep(Duration::from_micros(ctx.st_min as u64)).await;
+        let (flow_ctrl, exhausted) = match self.context.read() {
+            Ok(context) => (context.tx.flow_ctrl.clone(), context.write_block_exhausted()),
+            Err(_) => {
+                self.logger.warn(format_args!("ISO-TP: context lock is poisoned"));
+                (None, false)
+            },
+        };
+        // the next block is started by a new flow control frame, the block of the last write is done
+        if index > 0 && exhausted {
+            self.state_append(IsoTpState::WaitFlowCtrl);
+        }
+        let st_min = match flow_ctrl {
+            Some(ctx) => Duration::from_micros(ctx.st_min as u64),
+            None => Duration::ZERO,
+        };
+        // the first frame is not throttled
+        let gap = match index {
+            0 => st_min,
+            _ => st_min.max(self.min_gap),
+        };
+        if !gap.is_zero() {
+            sleep(gap).await;
         }
 
         loop {
@@ -180,12 +681,66 @@ impl<C: Clone, F: Frame<Channel = C>> AsyncCanIsoTp<C, F> {
         Ok(())
     }
 
+    #[inline]
+    pub(crate) fn observe_raw(&self, frame: &F) {
+        if let Ok(observer) = self.raw_observer.read() {
+            if let Some(observer) = observer.as_ref() {
+                observer(frame);
+            }
+        }
+    }
+
+    #[inline]
+    fn context_mut(&self) -> Option<RwLockWriteGuard<'_, IsoTpContext>> {
+        #[cfg(test)]
+        ACQUISITIONS.set(ACQUISITIONS.get() + 1);
+        match self.context.write() {
+            Ok(v) => Some(v),
+            Err(_) => {
+                self.logger.warn(format_args!("ISO-TP: context lock is poisoned"));
+                None
+            },
+        }
+    }
+
+    /// Count a frame written in the block of the flow control, before it is sent so that
+    /// the flow control frame responded to it is not missed.
+    #[inline]
+    fn count_block_frame(&self) {
+        if let Some(mut context) = self.context_mut() {
+            context.tx.block_frames = context.tx.block_frames.saturating_add(1);
+        }
+    }
+
+    /// Returns true if `id` is the identifier that this channel writes to.
+    fn is_write_id<Id: PartialEq<u32>>(&self, id: &Id) -> bool {
+        if *id == self.address.tx_id || *id == self.address.fid {
+            return true;
+        }
+
+        match self.context.read() {
+            Ok(context) => context.tx.write_id.is_some_and(|v| *id == v),
+            Err(_) => false,
+        }
+    }
+
+    /// Returns true if neither writing nor receiving a message.
+    #[inline]
+    fn is_idle(&self) -> bool {
+        let receiving = match self.context.read() {
+            Ok(context) => context.rx.consecutive.length.is_some(),
+            Err(_) => false,
+        };
+
+        !receiving && !self.state_contains(IsoTpState::Sending | IsoTpState::WaitBusy | IsoTpState::WaitFlowCtrl)
+    }
+
     #[inline]
     fn state_contains(&self, flags: IsoTpState) -> bool {
         match self.state.lock() {
             Ok(v) => *v & flags != IsoTpState::Idle,
             Err(_) => {
-                log::warn!("ISO-TP: state mutex is poisoned");
+                self.logger.warn(format_args!("ISO-TP: state mutex is poisoned"));
                 false
             },
         }
@@ -202,15 +757,218 @@ impl<C: Clone, F: Frame<Channel = C>> AsyncCanIsoTp<C, F> {
                     *v |= flags;
                 }
             }
-            Err(_) => log::warn!("ISO-TP: state mutex is poisoned"),
+            Err(_) => self.logger.warn(format_args!("ISO-TP: state mutex is poisoned")),
         }
     }
 
+    #[inline]
+    fn state_reset(&self) {
+        self.state_update(|v| *v = IsoTpState::Idle);
+    }
+
     #[inline]
     fn state_remove(&self, flags: IsoTpState) {
+        self.state_update(|v| v.remove(flags));
+    }
+
+    /// Update the state by `f`, [`IsoTpEvent::Idle`] is sent after the lock is released
+    /// if the state turns into idle.
+    fn state_update(&self, f: impl FnOnce(&mut IsoTpState)) {
+        if self.state_transit(f) {
+            self.iso_tp_event(IsoTpEvent::Idle);
+        }
+    }
+
+    /// Update the state by `f` without any event, returns true if the state turns into idle.
+    fn state_transit(&self, f: impl FnOnce(&mut IsoTpState)) -> bool {
         match self.state.lock() {
-            Ok(mut v) => v.remove(flags),
-            Err(_) => log::warn!("ISO-TP: state mutex is poisoned"),
+            Ok(mut v) => {
+                let busy = *v != IsoTpState::Idle;
+                f(&mut v);
+                busy && *v == IsoTpState::Idle
+            },
+            Err(_) => {
+                self.logger.warn(format_args!("ISO-TP: state mutex is poisoned"));
+                false
+            },
+        }
+    }
+}
+
+impl<C: Clone, F: Frame<Channel = C> + Send + 'static> AsyncCanIsoTp<C, F> {
+    /// Retransmit the retained frame after the backoff of the retry policy,
+    /// returns false if the retries are exhausted.
+    pub(crate) fn retransmit(&self) -> bool {
+        let (can_id, frame, padding) = match self.retained.lock() {
+            Ok(mut retained) if retained.retries < self.retry.max_retries => match retained.frame.clone() {
+                Some(v) => {
+                    retained.retries += 1;
+                    v
+                },
+                None => return false,
+            },
+            Ok(_) => return false,
+            Err(_) => {
+                self.logger.warn(format_args!("ISO-TP: retained frame lock is poisoned"));
+                return false;
+            },
+        };
+
+        self.logger.debug(format_args!("ISO-TP(CAN async) - retransmitting to {:#X}", can_id));
+        let frame = match self.new_frame(can_id, frame, padding) {
+            Ok(v) => v,
+            Err(_) => return false,
+        };
+        match tokio::runtime::Handle::try_current() {
+            _ if self.retry.backoff.is_zero() => self.sender.send(frame).is_ok(),
+            Ok(handle) => {
+                let (sender, backoff) = (self.sender.clone(), self.retry.backoff);
+                handle.spawn(async move {
+                    sleep(backoff).await;
+                    let _ = sender.send(frame);
+                });
+                true
+            },
+            // not called in a runtime, nothing is blocked but the caller
+            Err(_) => {
+                std::thread::sleep(self.retry.backoff);
+                self.sender.send(frame).is_ok()
+            },
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::sync::mpsc::{channel, Receiver, Sender};
+    use std::time::{Duration, Instant};
+    use isotp_rs::IsoTpState;
+    use crate::device::{AsyncDevice, Listener, Listeners, TransmitFailure};
+    use crate::frame::Frame;
+    use crate::identifier::Id;
+    use crate::isotp::{Address, Error, IsoTpEvent, RetryPolicy};
+    use crate::mock::{MockEventListener, MockFrame};
+    use super::AsyncCanIsoTp;
+
+    const ADDRESS: Address = Address { tx_id: 0x7E0, rx_id: 0x7E8, fid: 0x7DF, extended: false };
+
+    /// A device that only keeps the listeners, the frames are not transmitted.
+    struct MockDevice {
+        sender: Sender<MockFrame>,
+        listeners: Listeners<u8, u32, MockFrame>,
+        timebase: Instant,
+        closed: bool,
+    }
+
+    impl AsyncDevice for MockDevice {
+        type Device = Sender<MockFrame>;
+        type Channel = u8;
+        type Id = u32;
+        type Frame = MockFrame;
+
+        fn new(device: Self::Device) -> Self {
+            Self { sender: device, listeners: Default::default(), timebase: Instant::now(), closed: false }
+        }
+        fn sender(&self) -> Sender<Self::Frame> {
+            self.sender.clone()
+        }
+        fn listeners(&self) -> Listeners<Self::Channel, Self::Id, Self::Frame> {
+            self.listeners.clone()
+        }
+        fn timebase(&self) -> Instant {
+            self.timebase
+        }
+        async fn async_transmit(_: Arc<Mutex<Self>>, _: u64, _: Arc<Mutex<Receiver<()>>>) {}
+        async fn async_receive(_: Arc<Mutex<Self>>, _: u64, _: Arc<Mutex<Receiver<()>>>) {}
+        fn async_start(&mut self, _: u64) {}
+        async fn close(&mut self) {
+            self.closed = true;
+        }
+    }
+
+    // the writes are spawned to the only worker, so the constructions of the frames are failed on it only
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_write_reset() {
+        let (sender, receiver) = channel::<MockFrame>();
+        let mut iso_tp = AsyncCanIsoTp::new(0, ADDRESS, sender, Box::new(MockEventListener::default()));
+        let flow_ctrl = MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &[0x30, 0x00, 0x00, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]).unwrap();
+
+        let mut writer = iso_tp.clone();
+        let handle = tokio::spawn(async move {
+            // the 2nd frame fails to be constructed
+            MockFrame::fail_after(1);
+            writer.write(false, vec![0x55; 20]).await
+        });
+
+        assert!(receiver.recv_timeout(Duration::from_secs(1)).is_ok());
+        Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut iso_tp, 0, ADDRESS.tx_id);
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[flow_ctrl]);
+
+        assert!(handle.await.unwrap().is_err());
+        assert!(!iso_tp.state_contains(IsoTpState::Sending | IsoTpState::WaitBusy | IsoTpState::WaitFlowCtrl | IsoTpState::Error));
+        assert!(iso_tp.context.read().unwrap().tx.flow_ctrl.is_none());
+
+        // the channel is usable afterward
+        assert!(iso_tp.write(false, vec![0x01, 0x02, 0x03]).await.is_ok());
+        assert_eq!(receiver.try_recv().unwrap().data()[..4], [0x03, 0x01, 0x02, 0x03]);
+    }
+
+    #[tokio::test]
+    async fn test_retransmit() {
+        let (sender, receiver) = channel::<MockFrame>();
+        let listener = MockEventListener::default();
+        let mut iso_tp = AsyncCanIsoTp::new(0, ADDRESS, sender, Box::new(listener.clone()))
+            .with_retry_policy(RetryPolicy { max_retries: 1, backoff: Duration::from_millis(5) });
+
+        iso_tp.write(false, vec![0x3E, 0x00]).await.unwrap();
+        let frame = receiver.try_recv().unwrap();
+
+        // retransmitted by a task after the backoff
+        Listener::<u8, u32, MockFrame>::on_frame_transmit_failed(&mut iso_tp, 0, ADDRESS.tx_id, TransmitFailure::ArbitrationLost);
+        assert!(receiver.try_recv().is_err());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(receiver.try_recv().unwrap().data(), frame.data());
+        assert!(!iso_tp.state_contains(IsoTpState::Error));
+
+        // the retries are exhausted
+        Listener::<u8, u32, MockFrame>::on_frame_transmit_failed(&mut iso_tp, 0, ADDRESS.tx_id, TransmitFailure::ArbitrationLost);
+        assert!(iso_tp.state_contains(IsoTpState::Error));
+        assert!(matches!(
+            listener.events().last(),
+            Some(IsoTpEvent::ErrorOccurred(Error::TransmitFailed(TransmitFailure::ArbitrationLost)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_close_gracefully() {
+        let (sender, _receiver) = channel::<MockFrame>();
+        let open = || {
+            let device = MockDevice::new(sender.clone());
+            let iso_tp = AsyncCanIsoTp::new(0, ADDRESS, device.sender(), Box::new(MockEventListener::default()));
+            assert!(device.register_listener("iso-tp".into(), Box::new(iso_tp.clone())));
+            (device, iso_tp)
+        };
+
+        // timed out
+        let (mut device, mut iso_tp) = open();
+        iso_tp.state_append(IsoTpState::Sending | IsoTpState::WaitFlowCtrl);
+        assert!(!iso_tp.close_gracefully(&mut device, "iso-tp", Duration::from_millis(20)).await);
+        assert!(device.closed);
+        assert!(device.listener_names().is_empty());
+        assert!(iso_tp.is_idle());
+
+        // completed
+        let (mut device, mut iso_tp) = open();
+        iso_tp.state_append(IsoTpState::Sending);
+        let mut transmitted = iso_tp.clone();
+        let handle = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut transmitted, 0, ADDRESS.tx_id);
+        });
+        assert!(iso_tp.close_gracefully(&mut device, "iso-tp", Duration::from_secs(1)).await);
+        handle.await.unwrap();
+        assert!(device.closed);
+        assert!(device.listener_names().is_empty());
+    }
+}
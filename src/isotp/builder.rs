@@ -0,0 +1,85 @@
+use isotp_rs::{FlowControlContext, FlowControlState};
+use isotp_rs::can::CanIsoTpFrame;
+use crate::isotp::{Error, IsoTpConfig, util};
+
+/// Build a sequence of frames, e.g. the frames of a test or a simulator.
+///
+/// The frames are built as-is without validation, so the invalid sequences can be built too.
+///
+/// # Examples
+/// ```rust
+/// use isotp_rs::IsoTpFrame;
+/// use isotp_rs::can::CanIsoTpFrame;
+/// use can_type_rs::isotp::CanIsoTpFrameBuilder;
+///
+/// let data = (0..20).collect::<Vec<u8>>();
+/// let frames = CanIsoTpFrameBuilder::default()
+///     .first(20, &data[..6])
+///     .consecutive(1, &data[6..13])
+///     .consecutive(2, &data[13..])
+///     .build();
+/// assert_eq!(frames.len(), 3);
+/// assert!(matches!(&frames[2], CanIsoTpFrame::ConsecutiveFrame { sequence: 2, data } if data.len() == 7));
+///
+/// let encoded = frames.into_iter()
+///     .map(|frame| frame.encode(Some(0xAA)))
+///     .collect::<Vec<_>>();
+/// assert_eq!(encoded[0], [0x10, 0x14, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05]);
+/// assert_eq!(encoded[2], [0x22, 0x0D, 0x0E, 0x0F, 0x10, 0x11, 0x12, 0x13]);
+///
+/// // segmented from the payload
+/// let frames = CanIsoTpFrameBuilder::default()
+///     .from_payload([0x3E, 0x00])
+///     .unwrap()
+///     .build();
+/// assert!(matches!(&frames[..], [CanIsoTpFrame::SingleFrame { data }] if data == &[0x3E, 0x00]));
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct CanIsoTpFrameBuilder {
+    config: IsoTpConfig,
+    frames: Vec<CanIsoTpFrame>,
+}
+
+impl CanIsoTpFrameBuilder {
+    /// Segment the payloads of [`Self::from_payload`] by `config`.
+    pub fn with_config(mut self, config: IsoTpConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Append a single frame.
+    pub fn single(mut self, data: impl AsRef<[u8]>) -> Self {
+        self.frames.push(CanIsoTpFrame::SingleFrame { data: data.as_ref().to_vec() });
+        self
+    }
+
+    /// Append a first frame of the message of `length` bytes.
+    pub fn first(mut self, length: u32, data: impl AsRef<[u8]>) -> Self {
+        self.frames.push(CanIsoTpFrame::FirstFrame { length, data: data.as_ref().to_vec() });
+        self
+    }
+
+    /// Append a consecutive frame, the low 4 bits of `sequence` are used when encoding.
+    pub fn consecutive(mut self, sequence: u8, data: impl AsRef<[u8]>) -> Self {
+        self.frames.push(CanIsoTpFrame::ConsecutiveFrame { sequence, data: data.as_ref().to_vec() });
+        self
+    }
+
+    /// Append a flow control frame.
+    pub fn flow_control(mut self, state: FlowControlState, block_size: u8, st_min: u8) -> Self {
+        self.frames.push(CanIsoTpFrame::FlowControlFrame(FlowControlContext::new(state, block_size, st_min)));
+        self
+    }
+
+    /// Append the frames that `data` is segmented into, see [`util::from_data_into`].
+    pub fn from_payload(mut self, data: impl AsRef<[u8]>) -> Result<Self, Error> {
+        util::from_data_into(data, &self.config, &mut self.frames, usize::MAX)?;
+        Ok(self)
+    }
+
+    /// Returns the built frames.
+    #[inline]
+    pub fn build(self) -> Vec<CanIsoTpFrame> {
+        self.frames
+    }
+}
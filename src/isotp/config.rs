@@ -0,0 +1,144 @@
+use std::thread::sleep;
+use std::time::Duration;
+use crate::constant::is_can_fd_build;
+
+/// The default max count of consecutive `Wait` flow control frames(N_WFTmax) tolerated when writing.
+pub const DEFAULT_WFT_MAX: u8 = 10;
+
+/// The encoding of the first frame data length(FF_DL).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FirstFrameLength {
+    /// The short form when the length is not greater than 0xFFF, else the escaped form.
+    #[default]
+    Auto,
+    /// The 12-bit form of ISO 15765-2:2004.
+    Short,
+    /// The escaped 32-bit form of ISO 15765-2:2016.
+    Escaped,
+}
+
+/// The ISO-TP encoding configuration.
+#[derive(Debug, Clone)]
+pub struct IsoTpConfig {
+    /// The address extension(N_AE) prepended to every frame.
+    pub address_extension: Option<u8>,
+    /// The padding value, [`isotp_rs::can::DEFAULT_PADDING`] is used when `None`.
+    pub padding: Option<u8>,
+    /// The encoding of the first frame length.
+    pub first_frame_length: FirstFrameLength,
+    /// Accept the received frames that are not padded, e.g. a 4-byte classic frame of a 3-byte single frame.
+    pub accept_unpadded: bool,
+    /// Encode a first frame even if the data fits a single frame, e.g. to exercise the flow control
+    /// of the peer in conformance or negative testing.
+    ///
+    /// The first frame carries all the data if it fits, no consecutive frame follows then.
+    pub force_multi_frame: bool,
+    /// Allow the single frames of no data(SF_DL=0), e.g. a keep-alive of the protocols other than UDS,
+    /// the empty data is rejected by [`isotp_rs::error::Error::EmptyPdu`] otherwise.
+    pub allow_empty_single: bool,
+    /// Segment to the frames of CAN-FD(64 bytes) and pad to the valid lengths of CAN-FD instead of classic CAN,
+    /// it is true by default with the `can-fd` feature, so a build talks to both classic and CAN-FD nodes.
+    pub can_fd: bool,
+}
+
+impl Default for IsoTpConfig {
+    fn default() -> Self {
+        Self {
+            address_extension: None,
+            padding: None,
+            first_frame_length: Default::default(),
+            accept_unpadded: false,
+            force_multi_frame: false,
+            allow_empty_single: false,
+            can_fd: is_can_fd_build(),
+        }
+    }
+}
+
+/// What a session does to a received frame on `rx_id` that fails to be decoded, e.g. the noise of a bus.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OnDecodeError {
+    /// The frames after it are discarded, the session turns into the error state
+    /// and reports [`crate::isotp::IsoTpEvent::ErrorOccurred`].
+    Abort,
+    /// The frame is discarded, the transfer in progress is continued.
+    #[default]
+    Skip,
+    /// The same as [`Self::Skip`] and the frame is counted.
+    Count,
+}
+
+/// The retry policy of failed frame transmissions.
+///
+/// It is applied both to the frames that are not sent to the device and to the ones that the device
+/// failed to transmit. The backoff of a retransmission of [`AsyncCanIsoTp`](crate::isotp::AsyncCanIsoTp)
+/// is waited by a spawned task, or by blocking the callback if it is not called in a tokio runtime.
+///
+/// The default policy does not retry.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The max retry count after the first attempt failed.
+    pub max_retries: u8,
+    /// The waiting duration before every retry.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Run `op` until it succeeds or the retries are exhausted, the last error is returned then.
+    pub fn retry<T, E>(&self, mut op: impl FnMut() -> Result<T, E>) -> Result<T, E> {
+        let mut retries = 0;
+        loop {
+            match op() {
+                Err(_) if retries < self.max_retries => {
+                    retries += 1;
+                    sleep(self.backoff);
+                },
+                result => return result,
+            }
+        }
+    }
+
+    /// The same as [`Self::retry`] but waiting asynchronously.
+    #[cfg(feature = "tokio")]
+    pub async fn retry_async<T, E>(&self, mut op: impl FnMut() -> Result<T, E>) -> Result<T, E> {
+        let mut retries = 0;
+        loop {
+            match op() {
+                Err(_) if retries < self.max_retries => {
+                    retries += 1;
+                    tokio::time::sleep(self.backoff).await;
+                },
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::RetryPolicy;
+
+    /// Fails twice then succeeds.
+    fn flaky() -> impl FnMut() -> Result<usize, usize> {
+        let mut attempts = 0;
+        move || {
+            attempts += 1;
+            match attempts {
+                ..=2 => Err(attempts),
+                _ => Ok(attempts),
+            }
+        }
+    }
+
+    #[test]
+    fn test_retry() {
+        let policy = RetryPolicy { max_retries: 2, backoff: Duration::from_micros(10) };
+        assert_eq!(policy.retry(flaky()), Ok(3));
+
+        let policy = RetryPolicy { max_retries: 1, backoff: Duration::from_micros(10) };
+        assert_eq!(policy.retry(flaky()), Err(2));
+
+        assert_eq!(RetryPolicy::default().retry(flaky()), Err(1));
+    }
+}
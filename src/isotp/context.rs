@@ -1,6 +1,10 @@
-use isotp_rs::{FlowControlContext, IsoTpEvent};
+use isotp_rs::{FlowControlContext, IsoTpState};
+use isotp_rs::can::CanIsoTpFrame;
 use isotp_rs::constant::CONSECUTIVE_SEQUENCE_START;
 use isotp_rs::error::Error as IsoTpError;
+use crate::isotp::{Address, Error};
+#[cfg(feature = "metrics")]
+use crate::isotp::metrics::Metrics;
 
 #[derive(Debug, Default, Clone)]
 pub(crate) struct FlowCtrl {
@@ -16,68 +20,195 @@ pub(crate) struct Consecutive {
     pub(crate) buffer: Vec<u8>,
 }
 
+/// A read-only copy of the state of an ISO-TP channel for diagnostics.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextSnapshot {
+    pub address: Address,
+    pub state: IsoTpState,
+    /// The separation time of the last flow control frame when writing, in microseconds.
+    pub st_min: Option<u32>,
+    /// The block size of the last flow control frame when writing.
+    pub block_size: Option<u8>,
+    /// The data length of the message being received.
+    pub length: Option<u32>,
+    /// The count of bytes received so far of the message being received.
+    pub received: usize,
+    /// The sequence of the last consecutive frame.
+    pub sequence: Option<u8>,
+}
+
+/// The context of writing, it is kept when a message is received.
 #[derive(Debug, Default, Clone)]
-pub struct IsoTpContext {
+pub(crate) struct TxContext {
     pub(crate) flow_ctrl: Option<FlowCtrl>,
+    /// Count of the `Wait` flow control frames received since the last `ContinueToSend`.
+    pub(crate) wait_frames: u8,
+    /// The identifier of the last write, it may be neither `tx_id` nor `fid` of the address.
+    pub(crate) write_id: Option<u32>,
+    /// Count of the consecutive frames written since the last `ContinueToSend` flow control frame.
+    pub(crate) block_frames: u8,
+}
+
+/// The last frame written, retransmitted by the retry policy when the device failed to transmit it.
+///
+/// It is locked apart from [`IsoTpContext`], the flow control frames are written while the context is locked.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Retained {
+    /// The identifier, frame and padding of the last frame written.
+    pub(crate) frame: Option<(u32, CanIsoTpFrame, Option<u8>)>,
+    /// Count of the retransmissions of the frame.
+    pub(crate) retries: u8,
+}
+
+/// The context of receiving, it is kept when a message is written.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct RxContext {
     pub(crate) consecutive: Consecutive,
+    /// Count of the consecutive frames received since the last flow control frame.
+    pub(crate) block_frames: u8,
+    /// Count of the frames of the message being received, including the first frame.
+    pub(crate) frames: usize,
+}
+
+/// The contexts of both directions, so that a message can be written while another one is received.
+#[derive(Debug, Default, Clone)]
+pub struct IsoTpContext {
+    pub(crate) tx: TxContext,
+    pub(crate) rx: RxContext,
+    #[cfg(feature = "metrics")]
+    pub(crate) metrics: Metrics,
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Count of the context lock acquisitions of the current thread.
+    pub(crate) static ACQUISITIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
 }
 
 impl IsoTpContext {
-    /// reset st_min/consecutive/block_size
+    /// Pre-allocate `capacity` bytes of the reassembly buffer.
+    #[inline]
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        let mut result = Self::default();
+        result.rx.consecutive.buffer.reserve(capacity);
+        result
+    }
+    pub(crate) fn snapshot(&self, address: Address, state: IsoTpState) -> ContextSnapshot {
+        ContextSnapshot {
+            address,
+            state,
+            st_min: self.tx.flow_ctrl.as_ref().map(|ctx| ctx.st_min),
+            block_size: self.tx.flow_ctrl.as_ref().map(|ctx| ctx.block_size),
+            length: self.rx.consecutive.length,
+            received: self.rx.consecutive.buffer.len(),
+            sequence: self.rx.consecutive.sequence,
+        }
+    }
+    /// Returns the count of bytes still expected of the message being received, `None` if not receiving.
+    #[inline]
+    pub(crate) fn remaining(&self) -> Option<usize> {
+        self.rx.consecutive.length
+            .map(|v| (v as usize).saturating_sub(self.rx.consecutive.buffer.len()))
+    }
+    /// Reset the contexts of both directions.
     #[inline]
     pub(crate) fn reset(&mut self) {
         self.clear_flow_ctrl();
-        self.clear_consecutive();
+        self.clear_data();
     }
     #[inline]
     pub(crate) fn clear_flow_ctrl(&mut self) {
-        self.flow_ctrl = Default::default();
+        self.tx.flow_ctrl = Default::default();
+        self.tx.wait_frames = 0;
+        self.tx.block_frames = 0;
     }
     #[inline]
     pub(crate) fn update_flow_ctrl(&mut self, ctx: FlowControlContext) {
-        self.flow_ctrl = Some(FlowCtrl {
+        self.tx.wait_frames = 0;
+        self.tx.block_frames = 0;
+        self.tx.flow_ctrl = Some(FlowCtrl {
             st_min: ctx.st_min_us(),
             block_size: ctx.block_size(),
         });
     }
+    /// Reset the sequence, length and buffer of the reassembly data, the flow control is kept.
+    #[inline]
+    pub(crate) fn clear_data(&mut self) {
+        self.rx.consecutive.sequence = Default::default();
+        self.rx.consecutive.length = Default::default();
+        self.rx.consecutive.buffer.clear();
+        self.rx.block_frames = 0;
+        self.rx.frames = 0;
+    }
     #[inline]
-    pub(crate) fn clear_consecutive(&mut self) {
-        self.consecutive.sequence = Default::default();
-        self.consecutive.length = Default::default();
-        self.consecutive.buffer.clear();
+    pub(crate) fn update_consecutive(&mut self, length: u32, data: &[u8]) {
+        self.rx.consecutive.length = Some(length);
+        self.rx.block_frames = 0;
+        self.rx.frames = 1;
+        self.rx.consecutive.buffer.clear();
+        self.rx.consecutive.buffer.reserve(length as usize);
+        let len = data.len().min(length as usize);
+        self.rx.consecutive.buffer.extend_from_slice(&data[..len]);
     }
+    /// Returns true if `block_size` consecutive frames are written since the last flow control frame,
+    /// i.e. a new flow control frame is awaited before the next one.
     #[inline]
-    pub(crate) fn update_consecutive(&mut self, length: u32, mut data: Vec<u8>) {
-        self.consecutive.length = Some(length);
-        self.consecutive.buffer.append(&mut data);
+    pub(crate) fn write_block_exhausted(&self) -> bool {
+        match &self.tx.flow_ctrl {
+            Some(ctx) => ctx.block_size != 0 && self.tx.block_frames >= ctx.block_size,
+            None => false,
+        }
+    }
+    /// Count a consecutive frame in the current block, returns true when `block_size` frames are received
+    /// and the block is restarted.
+    #[inline]
+    pub(crate) fn block_exhausted(&mut self, block_size: u8) -> bool {
+        if block_size == 0 {
+            return false;
+        }
+
+        self.rx.block_frames += 1;
+        if self.rx.block_frames >= block_size {
+            self.rx.block_frames = 0;
+            return true;
+        }
+
+        false
     }
-    pub(crate) fn append_consecutive(&mut self, sequence: u8, mut data: Vec<u8>) -> Result<IsoTpEvent, IsoTpError> {
-        if self.consecutive.length.is_none() {
-            return Err(IsoTpError::MixFramesError);
+    /// Returns the reassembled data when the last consecutive frame is appended.
+    pub(crate) fn append_consecutive(&mut self, sequence: u8, data: &[u8]) -> Result<Option<&[u8]>, Error> {
+        if self.rx.consecutive.length.is_none() {
+            return Err(IsoTpError::MixFramesError.into());
         }
 
-        let target = match self.consecutive.sequence {
+        let target = match self.rx.consecutive.sequence {
             Some(v) => match v {
                 ..=0x0E => v + 1,
                 _ => 0,
             },
             None => CONSECUTIVE_SEQUENCE_START
         };
-        self.consecutive.sequence = Some(target);
+        self.rx.consecutive.sequence = Some(target);
         if sequence != target {
-            return Err(IsoTpError::InvalidSequence { expect: target, actual: sequence });
+            // jumped forward in a half of the sequence wrapping(0x0..=0xF), the frames are lost
+            return match sequence.wrapping_sub(target) & 0x0F {
+                1..=7 => Err(Error::MissingConsecutive { expected: target, got: sequence }),
+                _ => Err(IsoTpError::InvalidSequence { expect: target, actual: sequence }.into()),
+            };
         }
 
-        self.consecutive.buffer.append(&mut data);
+        // the bytes beyond the declared length are never buffered, e.g. of a hostile peer
+        let target_len = self.rx.consecutive.length.unwrap() as usize;
+        let len = data.len().min(target_len.saturating_sub(self.rx.consecutive.buffer.len()));
+        self.rx.consecutive.buffer.extend_from_slice(&data[..len]);
+        self.rx.frames += 1;
 
-        let buff_len = self.consecutive.buffer.len();
-        let target_len = self.consecutive.length.unwrap() as usize;
-        if buff_len >= target_len {
-            self.consecutive.buffer.resize(target_len, 0);
-            Ok(IsoTpEvent::DataReceived(self.consecutive.buffer.clone()))
+        if self.rx.consecutive.buffer.len() >= target_len {
+            self.rx.consecutive.buffer.truncate(target_len);
+            Ok(Some(&self.rx.consecutive.buffer))
         }
         else {
-            Ok(IsoTpEvent::Wait)
+            Ok(None)
         }
     }
 }
@@ -0,0 +1,39 @@
+use isotp_rs::error::Error as IsoTpError;
+use crate::device::TransmitFailure;
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    IsoTp(#[from] IsoTpError),
+
+    #[error("ISO-TP - frame count: {actual} exceeds the max: {max}")]
+    TooManyFrames { actual: usize, max: usize, },
+
+    #[error("ISO-TP - reserved flow status: {0:#x}")]
+    InvalidFlowStatus(u8),
+
+    #[error("ISO-TP - missing consecutive frame, expected sequence: {expected}, got: {got}")]
+    MissingConsecutive { expected: u8, got: u8, },
+
+    #[error("ISO-TP - wait flow control frames exceed the max: {0}")]
+    WaitOverflow(u8),
+
+    #[error("ISO-TP - transmit failed: {0:?}")]
+    TransmitFailed(TransmitFailure),
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Error {
+    fn format(&self, f: defmt::Formatter) {
+        match self {
+            Self::IsoTp(e) => defmt::write!(f, "{}", defmt::Display2Format(e)),
+            Self::TooManyFrames { actual, max } =>
+                defmt::write!(f, "ISO-TP - frame count: {=usize} exceeds the max: {=usize}", *actual, *max),
+            Self::InvalidFlowStatus(v) => defmt::write!(f, "ISO-TP - reserved flow status: {=u8:#x}", *v),
+            Self::MissingConsecutive { expected, got } =>
+                defmt::write!(f, "ISO-TP - missing consecutive frame, expected sequence: {=u8}, got: {=u8}", *expected, *got),
+            Self::WaitOverflow(v) => defmt::write!(f, "ISO-TP - wait flow control frames exceed the max: {=u8}", *v),
+            Self::TransmitFailed(v) => defmt::write!(f, "ISO-TP - transmit failed: {}", defmt::Debug2Format(v)),
+        }
+    }
+}
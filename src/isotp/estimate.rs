@@ -0,0 +1,100 @@
+use std::time::Duration;
+use isotp_rs::can::{ISO_TP_MAX_LENGTH_2004, ISO_TP_MAX_LENGTH_2016};
+use isotp_rs::error::Error as IsoTpError;
+use crate::constant::{CAN_FRAME_MAX_SIZE, CANFD_FRAME_MAX_SIZE};
+use crate::frame::bus_load::nominal_bits;
+use crate::isotp::Error;
+
+/// Estimate the duration to write a payload of `payload_len` bytes on the bus of `bitrate`(bit/s),
+/// the peer responds by flow control frames of `block_size` and `st_min`.
+///
+/// The frames are of standard identifiers, they are segmented to CAN-FD frames of 64 bytes if `fd`,
+/// regardless of the `can-fd` feature. A frame is padded to the smallest valid length but 8 bytes at least,
+/// and STmin is awaited between the consecutive frames of a block. The bit stuffing and the processing time
+/// of the nodes are not accounted, see [`nominal_bits`].
+///
+/// Returns [`Duration::MAX`] if `bitrate` is 0.
+///
+/// # Examples
+/// ```rust
+/// use std::time::Duration;
+/// use can_type_rs::isotp::estimate_duration;
+///
+/// // a 2KB block of flashing, 293 frames and a flow control frame of 111 bits
+/// let duration = estimate_duration(2048, 500_000, Duration::ZERO, 0, false).unwrap();
+/// assert_eq!(duration, Duration::from_micros(65_268));
+/// ```
+pub fn estimate_duration(
+    payload_len: usize,
+    bitrate: u32,
+    st_min: Duration,
+    block_size: u8,
+    fd: bool,
+) -> Result<Duration, Error> {
+    let frame_size = if fd { CANFD_FRAME_MAX_SIZE } else { CAN_FRAME_MAX_SIZE };
+    let frame_bits = |data_len: usize| nominal_bits(false, fd, data_len.max(CAN_FRAME_MAX_SIZE));
+    // the escaped length of byte1 beyond classic CAN
+    let single_size = if fd { frame_size - 2 } else { frame_size - 1 };
+    // the escaped length of 4 bytes beyond 4095
+    let first_size = match payload_len {
+        ..=ISO_TP_MAX_LENGTH_2004 => frame_size - 2,
+        _ => frame_size - 6,
+    };
+
+    let (bits, gaps) = match payload_len {
+        0 => return Err(IsoTpError::EmptyPdu.into()),
+        v if v < CAN_FRAME_MAX_SIZE => (frame_bits(1 + v), 0),
+        v if v <= single_size => (frame_bits(2 + v), 0),
+        v if v <= ISO_TP_MAX_LENGTH_2016 => {
+            let remaining = v - first_size;
+            let consecutive = remaining.div_ceil(frame_size - 1);
+            let last = remaining - (consecutive - 1) * (frame_size - 1);
+            let blocks = match block_size {
+                0 => 1,
+                v => consecutive.div_ceil(v as usize),
+            };
+
+            let bits = consecutive * frame_bits(frame_size)     // the first frame and the full consecutive frames
+                + frame_bits(1 + last)
+                + blocks * frame_bits(CAN_FRAME_MAX_SIZE);      // the flow control frames
+            (bits, consecutive - blocks)
+        },
+        v => return Err(IsoTpError::LengthOutOfRange(v).into()),
+    };
+
+    match bitrate {
+        0 => Ok(Duration::MAX),
+        v => {
+            let transmitting = Duration::from_nanos(bits as u64 * 1_000_000_000 / v as u64);
+            Ok(transmitting + st_min * gaps as u32)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use isotp_rs::error::Error as IsoTpError;
+    use crate::isotp::Error;
+    use super::estimate_duration;
+
+    #[test]
+    fn test_estimate_duration() -> anyhow::Result<()> {
+        // a frame of 111 bits at 500 kbit/s
+        assert_eq!(estimate_duration(7, 500_000, Duration::ZERO, 0, false)?, Duration::from_micros(222));
+        // a first frame, a flow control frame and 2 consecutive frames with a gap between them
+        let st_min = Duration::from_millis(1);
+        assert_eq!(estimate_duration(20, 500_000, st_min, 0, false)?, Duration::from_micros(888 + 1000));
+        // a flow control frame of every consecutive frame, no gap
+        assert_eq!(estimate_duration(20, 500_000, st_min, 1, false)?, Duration::from_micros(1110));
+        // a single frame of 24 bytes of CAN-FD, 259 bits
+        assert_eq!(estimate_duration(20, 500_000, st_min, 0, true)?, Duration::from_micros(518));
+        // frames of CAN-FD, 3 frames of 579 bits, the last consecutive frame of 16 bytes and a flow control frame
+        assert_eq!(estimate_duration(200, 500_000, Duration::ZERO, 0, true)?, Duration::from_micros(4106));
+
+        assert_eq!(estimate_duration(7, 0, Duration::ZERO, 0, false)?, Duration::MAX);
+        assert!(matches!(estimate_duration(0, 500_000, Duration::ZERO, 0, false), Err(Error::IsoTp(IsoTpError::EmptyPdu))));
+
+        Ok(())
+    }
+}
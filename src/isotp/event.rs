@@ -0,0 +1,69 @@
+use isotp_rs::FlowControlState;
+use crate::isotp::Error;
+
+/// ISO-TP event.
+#[derive(Debug, Clone)]
+pub enum IsoTpEvent {
+    /// The peer asks to wait, or more consecutive frames are expected.
+    Wait,
+    /// A first frame is received, `length` is the declared data length(FF_DL).
+    ///
+    /// It is delivered before any consecutive frame, so a receiver can pre-allocate a buffer
+    /// or reject an oversized transfer early.
+    FirstFrame { length: u32 },
+    /// A flow control frame is received, it is delivered before the state is updated.
+    ///
+    /// A reserved `st_min` is reported as the 127ms that it is treated as.
+    FlowControl { state: FlowControlState, block_size: u8, st_min: u8 },
+    /// A message is reassembled completely, it is delivered right before the data of the message,
+    /// e.g. to analyze the timing or detect an unexpected segmentation.
+    ///
+    /// `frame_count` is 1 of a single frame, else the count of the first frame and the consecutive frames.
+    ReceiveCompleted { frame_count: usize, segmented: bool },
+    /// A complete message is received.
+    DataReceived(Vec<u8>),
+    ErrorOccurred(Error),
+    /// The state turns into idle from a non-idle state, e.g. a write completes or is reset after a failure,
+    /// so the channel is free for the next write.
+    ///
+    /// A message may still be being received, the receiving is not a part of the state.
+    ///
+    /// It is sent after the context lock is released, so the listener may start the next write
+    /// from the callback. The listener is still locked meanwhile, that write must not send
+    /// any event synchronously, e.g. by failing, else it deadlocks.
+    Idle,
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for IsoTpEvent {
+    fn format(&self, f: defmt::Formatter) {
+        match self {
+            Self::Wait => defmt::write!(f, "Wait"),
+            Self::FirstFrame { length } => defmt::write!(f, "FirstFrame({=u32})", *length),
+            Self::FlowControl { state, block_size, st_min } =>
+                defmt::write!(f, "FlowControl({=u8}, {=u8}, {=u8:#x})", *state as u8, *block_size, *st_min),
+            Self::ReceiveCompleted { frame_count, segmented } =>
+                defmt::write!(f, "ReceiveCompleted({=usize}, {=bool})", *frame_count, *segmented),
+            Self::DataReceived(data) => defmt::write!(f, "DataReceived([{=[u8]:02x}])", data.as_slice()),
+            Self::ErrorOccurred(e) => defmt::write!(f, "ErrorOccurred({})", e),
+            Self::Idle => defmt::write!(f, "Idle"),
+        }
+    }
+}
+
+/// ISO-TP event listener.
+pub trait IsoTpEventListener: Send {
+    /// Clear the buffered events.
+    fn clear_buffer(&mut self);
+    /// Callback when an ISO-TP event occurred.
+    fn on_iso_tp_event(&mut self, event: IsoTpEvent);
+    /// Callback when a complete message is received in the zero-copy mode, instead of
+    /// [`IsoTpEvent::DataReceived`].
+    ///
+    /// `data` borrows the reassembly buffer that is reused by the next transfer, so it is valid only
+    /// during the call and the context lock is held meanwhile; copy what must outlive the call.
+    /// The default implementation copies `data` into an [`IsoTpEvent::DataReceived`].
+    fn on_data_received(&mut self, data: &[u8]) {
+        self.on_iso_tp_event(IsoTpEvent::DataReceived(data.to_vec()));
+    }
+}
@@ -0,0 +1,52 @@
+use std::fmt::Arguments;
+use std::sync::{Arc, RwLock};
+
+/// The callback that receives the log messages of an ISO-TP channel instead of the `log` facade.
+pub type LogHook = Box<dyn Fn(log::Level, &str) + Send + Sync>;
+
+/// The callback that observes the raw frames received by an ISO-TP channel before decoding.
+pub type RawFrameObserver<F> = Box<dyn Fn(&F) + Send + Sync>;
+
+/// The log routing of an ISO-TP channel, shared by its clones.
+///
+/// The messages are emitted by the `log` facade until a hook is set.
+#[derive(Clone, Default)]
+pub(crate) struct Logger {
+    hook: Arc<RwLock<Option<LogHook>>>,
+}
+
+impl Logger {
+    pub(crate) fn set_hook(&self, hook: LogHook) {
+        match self.hook.write() {
+            Ok(mut v) => *v = Some(hook),
+            Err(_) => log::warn!("ISO-TP: log hook lock is poisoned"),
+        }
+    }
+
+    pub(crate) fn log(&self, level: log::Level, args: Arguments) {
+        match self.hook.read() {
+            Ok(hook) => match hook.as_ref() {
+                Some(hook) => hook(level, &args.to_string()),
+                None => log::log!(level, "{}", args),
+            },
+            Err(_) => log::log!(level, "{}", args),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn error(&self, args: Arguments) {
+        self.log(log::Level::Error, args)
+    }
+    #[inline]
+    pub(crate) fn warn(&self, args: Arguments) {
+        self.log(log::Level::Warn, args)
+    }
+    #[inline]
+    pub(crate) fn debug(&self, args: Arguments) {
+        self.log(log::Level::Debug, args)
+    }
+    #[inline]
+    pub(crate) fn trace(&self, args: Arguments) {
+        self.log(log::Level::Trace, args)
+    }
+}
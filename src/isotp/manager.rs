@@ -0,0 +1,190 @@
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+use crate::device::{Listener, SyncDevice, TransmitFailure};
+use crate::frame::Frame;
+use crate::isotp::{Address, IsoTpEventListener, SyncCanIsoTp};
+
+/// The name that the router of [`IsoTpManager`] is registered to the device by.
+const LISTENER_NAME: &str = "iso-tp-manager";
+
+type Sessions<C, F> = Arc<Mutex<Vec<SyncCanIsoTp<C, F>>>>;
+
+/// Route the frames of the device to the sessions.
+struct Router<C, F>(Sessions<C, F>);
+
+impl<C, F> Router<C, F> {
+    fn each(&self, f: impl FnMut(&mut SyncCanIsoTp<C, F>)) {
+        match self.0.lock() {
+            Ok(mut sessions) => sessions.iter_mut().for_each(f),
+            Err(_) => log::warn!("IsoTpManager: sessions lock is poisoned"),
+        }
+    }
+}
+
+impl<C, Id, F> Listener<C, Id, F> for Router<C, F>
+where
+    C: Clone + Eq + Display + Send,
+    Id: PartialEq<u32> + Clone,
+    F: Frame<Channel = C> + Clone + Send {
+    fn on_frame_transmitting(&mut self, _: C, _: &F) {}
+
+    fn on_frame_transmitted(&mut self, channel: C, id: Id) {
+        self.each(|session| Listener::<C, Id, F>::on_frame_transmitted(session, channel.clone(), id.clone()));
+    }
+
+    fn on_frame_transmit_failed(&mut self, channel: C, id: Id, reason: TransmitFailure) {
+        self.each(|session| Listener::<C, Id, F>::on_frame_transmit_failed(session, channel.clone(), id.clone(), reason));
+    }
+
+    fn on_frame_received(&mut self, channel: C, frames: &[F]) {
+        self.each(|session| {
+            if session.channel != channel {
+                return;
+            }
+
+            let frames = frames.iter()
+                .filter(|frame| frame.can_id().as_raw() == session.address.rx_id)
+                .cloned()
+                .collect::<Vec<_>>();
+            if !frames.is_empty() {
+                Listener::<C, Id, F>::on_frame_received(session, channel.clone(), &frames);
+            }
+        });
+    }
+}
+
+/// Host the ISO-TP sessions of one device, it is registered to the device once
+/// and routes the received frames to the sessions by channel and `rx_id`.
+///
+/// The sessions should have distinct `rx_id`s of a channel, the event listeners of the sessions
+/// must not open or close a session.
+pub struct IsoTpManager<D: SyncDevice> {
+    device: D,
+    sessions: Sessions<D::Channel, D::Frame>,
+}
+
+impl<D> IsoTpManager<D>
+where
+    D: SyncDevice,
+    D::Channel: Clone + Eq + Display + Send + 'static,
+    D::Id: PartialEq<u32> + Clone + 'static,
+    D::Frame: Frame<Channel = D::Channel> + Clone + Send + 'static {
+    /// Own `device`, a listener named `iso-tp-manager` is registered to it.
+    pub fn new(device: D) -> Self {
+        let sessions: Sessions<D::Channel, D::Frame> = Default::default();
+        if !device.register_listener(LISTENER_NAME.into(), Box::new(Router(Arc::clone(&sessions)))) {
+            log::warn!("IsoTpManager: listener `{}` is registered already", LISTENER_NAME);
+        }
+
+        Self { device, sessions }
+    }
+
+    /// Open a session of `address` on `channel`, the returned handle writes by the sender of the device
+    /// and the received messages are reported to `listener`.
+    pub fn open(&self,
+                channel: D::Channel,
+                address: impl Into<Address>,
+                listener: Box<dyn IsoTpEventListener + Send>,
+    ) -> SyncCanIsoTp<D::Channel, D::Frame> {
+        let session = SyncCanIsoTp::new(channel, address, self.device.sender(), listener);
+        match self.sessions.lock() {
+            Ok(mut sessions) => sessions.push(session.clone()),
+            Err(_) => log::warn!("IsoTpManager: sessions lock is poisoned"),
+        }
+
+        session
+    }
+
+    /// Close the sessions of `address` on `channel`, returns false if no session is opened.
+    pub fn close(&self, channel: &D::Channel, address: &Address) -> bool {
+        match self.sessions.lock() {
+            Ok(mut sessions) => {
+                let count = sessions.len();
+                sessions.retain(|session| session.channel != *channel || session.address != *address);
+                sessions.len() != count
+            },
+            Err(_) => false,
+        }
+    }
+
+    /// The count of the opened sessions.
+    pub fn sessions(&self) -> usize {
+        self.sessions.lock()
+            .map(|sessions| sessions.len())
+            .unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    /// Returns the device to start or close it.
+    #[inline]
+    pub fn device_mut(&mut self) -> &mut D {
+        &mut self.device
+    }
+
+    /// Unregister the listener from the device and return the device, the sessions are closed.
+    pub fn into_device(self) -> D {
+        self.device.unregister_listener(LISTENER_NAME.into());
+        self.device
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use crate::device::{LoopbackDevice, SyncDevice};
+    use crate::isotp::{Address, IsoTpEvent};
+    use crate::mock::{MockEventListener, MockFrame};
+    use super::IsoTpManager;
+
+    const ADDRESS_A: Address = Address { tx_id: 0x7E0, rx_id: 0x7E8, fid: 0x7DF, extended: false };
+    const ADDRESS_B: Address = Address { tx_id: 0x7E1, rx_id: 0x7E9, fid: 0x7DF, extended: false };
+
+    fn wait_received(listener: &MockEventListener) -> Option<Vec<u8>> {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(500) {
+            let received = listener.events()
+                .into_iter()
+                .find_map(|event| match event {
+                    IsoTpEvent::DataReceived(data) => Some(data),
+                    _ => None,
+                });
+            if received.is_some() {
+                return received;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        None
+    }
+
+    #[test]
+    fn test_sessions() {
+        let mut manager = IsoTpManager::new(LoopbackDevice::<u8, MockFrame>::new(()));
+        let (server_a, server_b) = (MockEventListener::default(), MockEventListener::default());
+        let mut client_a = manager.open(0, ADDRESS_A, Box::new(MockEventListener::default()));
+        let mut client_b = manager.open(0, ADDRESS_B, Box::new(MockEventListener::default()));
+        manager.open(0, ADDRESS_A.swapped(), Box::new(server_a.clone()));
+        manager.open(0, ADDRESS_B.swapped(), Box::new(server_b.clone()));
+        assert_eq!(manager.sessions(), 4);
+        assert_eq!(manager.device().listener_names(), vec!["iso-tp-manager".to_owned()]);
+        manager.device_mut().sync_start(100);
+
+        // the flow control is routed to the client too
+        let data = (0..20).collect::<Vec<u8>>();
+        client_a.write(false, data.clone()).unwrap();
+        assert_eq!(wait_received(&server_a), Some(data));
+        assert_eq!(wait_received(&server_b), None);
+
+        client_b.write(false, vec![0x3E, 0x00]).unwrap();
+        assert_eq!(wait_received(&server_b), Some(vec![0x3E, 0x00]));
+
+        assert!(manager.close(&0, &ADDRESS_A));
+        assert!(!manager.close(&0, &ADDRESS_A));
+        manager.device_mut().close();
+        assert!(manager.into_device().listener_names().is_empty());
+    }
+}
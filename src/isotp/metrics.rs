@@ -0,0 +1,82 @@
+use std::time::{Duration, Instant};
+
+/// The timing metrics of a completed ISO-TP transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferMetrics {
+    /// When the write is called or the first frame is received.
+    pub started: Instant,
+    /// When the last frame is written or received.
+    pub completed: Instant,
+    /// The data length of the transfer.
+    pub bytes: usize,
+    /// The count of data frames(single, first and consecutive frames).
+    pub frames: usize,
+    /// The count of the `Wait` flow control frames received while writing.
+    pub flow_control_waits: usize,
+}
+
+impl TransferMetrics {
+    #[inline]
+    pub fn duration(&self) -> Duration {
+        self.completed.duration_since(self.started)
+    }
+
+    /// Returns the effective throughput in bytes per second, `None` if the duration is zero.
+    #[inline]
+    pub fn throughput(&self) -> Option<f64> {
+        let secs = self.duration().as_secs_f64();
+        match secs > 0. {
+            true => Some(self.bytes as f64 / secs),
+            false => None,
+        }
+    }
+}
+
+/// The metrics recording of an ISO-TP channel.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Metrics {
+    /// The start instant and the count of frames of the transfer being received.
+    pub(crate) receiving: Option<(Instant, usize)>,
+    pub(crate) flow_control_waits: usize,
+    pub(crate) written: Option<TransferMetrics>,
+    pub(crate) received: Option<TransferMetrics>,
+}
+
+impl Metrics {
+    #[inline]
+    pub(crate) fn start_writing(&mut self) {
+        self.flow_control_waits = 0;
+    }
+    #[inline]
+    pub(crate) fn complete_writing(&mut self, started: Instant, bytes: usize, frames: usize) {
+        self.written = Some(TransferMetrics {
+            started,
+            completed: Instant::now(),
+            bytes,
+            frames,
+            flow_control_waits: self.flow_control_waits,
+        });
+    }
+    #[inline]
+    pub(crate) fn start_receiving(&mut self) {
+        self.receiving = Some((Instant::now(), 1));
+    }
+    #[inline]
+    pub(crate) fn frame_received(&mut self) {
+        if let Some((_, frames)) = &mut self.receiving {
+            *frames += 1;
+        }
+    }
+    #[inline]
+    pub(crate) fn complete_receiving(&mut self, bytes: usize) {
+        let (started, frames) = self.receiving.take()
+            .unwrap_or((Instant::now(), 1));
+        self.received = Some(TransferMetrics {
+            started,
+            completed: Instant::now(),
+            bytes,
+            frames,
+            flow_control_waits: 0,
+        });
+    }
+}
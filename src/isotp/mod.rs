@@ -3,6 +3,34 @@ mod asynchronous;
 #[cfg(feature = "tokio")]
 pub use asynchronous::AsyncCanIsoTp;
 mod synchronous;
-pub use synchronous::SyncCanIsoTp;
+pub use synchronous::{SyncCanIsoTp, WriteHandle, WriteProgress};
 
+mod address;
+mod builder;
+pub use builder::CanIsoTpFrameBuilder;
+pub use address::*;
+mod config;
+pub use config::*;
 mod context;
+pub use context::ContextSnapshot;
+mod error;
+pub use error::Error;
+mod estimate;
+pub use estimate::estimate_duration;
+mod event;
+pub use event::*;
+mod logger;
+pub use logger::{LogHook, RawFrameObserver};
+mod manager;
+pub use manager::IsoTpManager;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "metrics")]
+pub use metrics::TransferMetrics;
+mod padding;
+pub use padding::PaddingDetector;
+mod session;
+pub use session::{IsoTpRequester, IsoTpResponder, PendingRequest};
+mod state;
+pub use state::describe_state;
+pub mod util;
@@ -0,0 +1,95 @@
+use crate::isotp::util;
+
+/// Infer the padding value that a peer uses from the trailing bytes of its frames, e.g. for ECU fingerprinting.
+#[derive(Debug, Clone)]
+pub struct PaddingDetector {
+    address_extension: Option<u8>,
+    counts: [usize; 256],
+}
+
+impl Default for PaddingDetector {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl PaddingDetector {
+    /// Detect the frames of the address extension(N_AE) `address_extension`, see [`util::decode`].
+    pub fn new(address_extension: Option<u8>) -> Self {
+        Self { address_extension, counts: [0; 256] }
+    }
+
+    /// Count the trailing bytes of a received frame with all bytes of the can-frame,
+    /// returns false if the data is not an ISO-TP frame.
+    ///
+    /// `remaining` is the count of data bytes that a consecutive frame carries, the padding
+    /// is only in the last consecutive frame of a message, see [`util::decode_tail`].
+    pub fn feed(&mut self, data: &[u8], remaining: Option<usize>) -> bool {
+        match util::decode_tail(data, self.address_extension, remaining) {
+            Ok((_, tail)) => {
+                tail.iter()
+                    .for_each(|&v| self.counts[v as usize] += 1);
+                true
+            },
+            Err(_) => false,
+        }
+    }
+
+    /// The count of trailing bytes that are fed.
+    pub fn samples(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// The most frequent trailing byte, the smallest one if tied, `None` if no trailing bytes are fed.
+    pub fn padding(&self) -> Option<u8> {
+        let (value, &count) = self.counts.iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|&(_, count)| count)?;
+
+        match count {
+            0 => None,
+            _ => Some(value as u8),
+        }
+    }
+
+    /// Forget the fed frames.
+    pub fn reset(&mut self) {
+        self.counts = [0; 256];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PaddingDetector;
+
+    #[test]
+    fn test_padding_detector() {
+        let mut detector = PaddingDetector::default();
+        assert_eq!(detector.padding(), None);
+
+        // single frames and a flow control frame padded with 0xAA
+        assert!(detector.feed(&[0x02, 0x3E, 0x80, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA], None));
+        assert!(detector.feed(&[0x30, 0x00, 0x00, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA], None));
+        // the last consecutive frame padded with 0x00
+        assert!(detector.feed(&[0x21, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00], Some(2)));
+        // no padding of first frames
+        assert!(detector.feed(&[0x10, 0x14, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06], None));
+        assert!(!detector.feed(&[], None));
+
+        assert_eq!(detector.samples(), 15);
+        assert_eq!(detector.padding(), Some(0xAA));
+
+        (0..2).for_each(|_| {
+            detector.feed(&[0x01, 0x3E, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], None);
+        });
+        assert_eq!(detector.padding(), Some(0x00));
+
+        detector.reset();
+        assert_eq!(detector.samples(), 0);
+        // with address extension
+        let mut detector = PaddingDetector::new(Some(0xF1));
+        assert!(detector.feed(&[0xF1, 0x02, 0x3E, 0x80, 0xCC, 0xCC, 0xCC, 0xCC], None));
+        assert_eq!((detector.samples(), detector.padding()), (4, Some(0xCC)));
+    }
+}
@@ -0,0 +1,210 @@
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use isotp_rs::error::Error as IsoTpError;
+use crate::frame::Frame;
+use crate::isotp::{Error, IsoTpEvent, IsoTpEventListener, SyncCanIsoTp};
+
+/// Forward the events of a session to the wrapper.
+struct Forwarder(Sender<IsoTpEvent>);
+
+impl IsoTpEventListener for Forwarder {
+    fn clear_buffer(&mut self) {}
+
+    fn on_iso_tp_event(&mut self, event: IsoTpEvent) {
+        let _ = self.0.send(event);
+    }
+}
+
+/// Replace the event listener of `iso_tp` by a [`Forwarder`].
+fn forward<C, F>(iso_tp: &mut SyncCanIsoTp<C, F>) -> Receiver<IsoTpEvent> {
+    let (sender, receiver) = channel();
+    iso_tp.listener = Arc::new(Mutex::new(Box::new(Forwarder(sender))));
+    receiver
+}
+
+/// Wait for a complete message of `events` until `timeout` elapses.
+fn receive(events: &Receiver<IsoTpEvent>, timeout: Duration) -> Result<Vec<u8>, Error> {
+    let start = Instant::now();
+    loop {
+        let remaining = timeout.saturating_sub(start.elapsed());
+        match events.recv_timeout(remaining) {
+            Ok(IsoTpEvent::DataReceived(data)) => return Ok(data),
+            Ok(IsoTpEvent::ErrorOccurred(e)) => return Err(e),
+            Ok(_) => continue,
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) =>
+                return Err(IsoTpError::Timeout { value: timeout.as_millis() as u64, unit: "ms" }.into()),
+        }
+    }
+}
+
+/// The client side of a session, a request is always written before its response is received.
+///
+/// # Examples
+/// ```
+/// use can_type_rs::frame::Frame;
+/// use can_type_rs::isotp::{Error, IsoTpRequester};
+///
+/// fn read_vin<F: Frame<Channel = u8> + Clone>(requester: &mut IsoTpRequester<u8, F>) -> Result<Vec<u8>, Error> {
+///     requester.request(vec![0x22, 0xF1, 0x90])
+/// }
+/// ```
+///
+/// There is no way to receive without a request:
+/// ```compile_fail
+/// use can_type_rs::frame::Frame;
+/// use can_type_rs::isotp::IsoTpRequester;
+///
+/// fn receive_only<F: Frame<Channel = u8> + Clone>(requester: &mut IsoTpRequester<u8, F>) {
+///     let _ = requester.receive();
+/// }
+/// ```
+pub struct IsoTpRequester<C, F> {
+    iso_tp: SyncCanIsoTp<C, F>,
+    events: Receiver<IsoTpEvent>,
+    timeout: Duration,
+}
+
+impl<C: Clone, F: Frame<Channel = C> + Clone> IsoTpRequester<C, F> {
+    /// Wrap `iso_tp`, the responses are waited for up to `timeout`.
+    ///
+    /// The event listener of `iso_tp` is replaced, so wrap it before its clones are registered,
+    /// register [`Self::listener`] to the device instead.
+    pub fn new(mut iso_tp: SyncCanIsoTp<C, F>, timeout: Duration) -> Self {
+        let events = forward(&mut iso_tp);
+        Self { iso_tp, events, timeout }
+    }
+
+    /// Returns the frame listener of the session to register to the device.
+    #[inline]
+    pub fn listener(&self) -> SyncCanIsoTp<C, F> {
+        self.iso_tp.clone()
+    }
+
+    /// Write `data` physically and wait for the response, the unsolicited messages before are discarded.
+    pub fn request(&mut self, data: Vec<u8>) -> Result<Vec<u8>, Error> {
+        self.events.try_iter().for_each(drop);
+        self.iso_tp.write(false, data)?;
+        receive(&self.events, self.timeout)
+    }
+
+    /// Returns the wrapped session for advanced use.
+    #[inline]
+    pub fn into_inner(self) -> SyncCanIsoTp<C, F> {
+        self.iso_tp
+    }
+}
+
+/// The server side of a session, a response can only be written to a received request.
+///
+/// # Examples
+/// ```
+/// use can_type_rs::frame::Frame;
+/// use can_type_rs::isotp::{Error, IsoTpResponder};
+///
+/// fn serve<F: Frame<Channel = u8> + Clone>(responder: &mut IsoTpResponder<u8, F>) -> Result<(), Error> {
+///     let request = responder.receive()?;
+///     let response = vec![request.data()[0] + 0x40];
+///     request.respond(response)
+/// }
+/// ```
+///
+/// There is no way to respond without a request:
+/// ```compile_fail
+/// use can_type_rs::frame::Frame;
+/// use can_type_rs::isotp::IsoTpResponder;
+///
+/// fn respond_first<F: Frame<Channel = u8> + Clone>(responder: &mut IsoTpResponder<u8, F>) {
+///     let _ = responder.respond(vec![0x7E, 0x00]);
+/// }
+/// ```
+pub struct IsoTpResponder<C, F> {
+    iso_tp: SyncCanIsoTp<C, F>,
+    events: Receiver<IsoTpEvent>,
+    timeout: Duration,
+}
+
+impl<C: Clone, F: Frame<Channel = C> + Clone> IsoTpResponder<C, F> {
+    /// Wrap `iso_tp`, the requests are waited for up to `timeout`, see [`IsoTpRequester::new`].
+    pub fn new(mut iso_tp: SyncCanIsoTp<C, F>, timeout: Duration) -> Self {
+        let events = forward(&mut iso_tp);
+        Self { iso_tp, events, timeout }
+    }
+
+    /// Returns the frame listener of the session to register to the device.
+    #[inline]
+    pub fn listener(&self) -> SyncCanIsoTp<C, F> {
+        self.iso_tp.clone()
+    }
+
+    /// Wait for the next request.
+    pub fn receive(&mut self) -> Result<PendingRequest<'_, C, F>, Error> {
+        let data = receive(&self.events, self.timeout)?;
+        Ok(PendingRequest { responder: self, data })
+    }
+
+    /// Returns the wrapped session for advanced use.
+    #[inline]
+    pub fn into_inner(self) -> SyncCanIsoTp<C, F> {
+        self.iso_tp
+    }
+}
+
+/// A received request of [`IsoTpResponder`] that is not responded yet.
+pub struct PendingRequest<'a, C, F> {
+    responder: &'a mut IsoTpResponder<C, F>,
+    data: Vec<u8>,
+}
+
+impl<C: Clone, F: Frame<Channel = C> + Clone> PendingRequest<'_, C, F> {
+    /// The data of the request.
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Write the response physically.
+    pub fn respond(self, data: Vec<u8>) -> Result<(), Error> {
+        self.responder.iso_tp.write(false, data)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::device::{LoopbackDevice, SyncDevice};
+    use crate::isotp::{Address, Error, SyncCanIsoTp};
+    use crate::mock::{MockEventListener, MockFrame};
+    use super::{IsoTpRequester, IsoTpResponder};
+
+    const ADDRESS: Address = Address { tx_id: 0x7E0, rx_id: 0x7E8, fid: 0x7DF, extended: false };
+    const TIMEOUT: Duration = Duration::from_millis(500);
+
+    #[test]
+    fn test_request_response() {
+        let mut device = LoopbackDevice::<u8, MockFrame>::new(());
+        let iso_tp = |address| SyncCanIsoTp::new(0, address, device.sender(), Box::new(MockEventListener::default()));
+        let mut requester = IsoTpRequester::new(iso_tp(ADDRESS), TIMEOUT);
+        let mut responder = IsoTpResponder::new(iso_tp(ADDRESS.swapped()), TIMEOUT);
+        assert!(device.register_listener("requester".into(), Box::new(requester.listener())));
+        assert!(device.register_listener("responder".into(), Box::new(responder.listener())));
+        device.sync_start(100);
+
+        let server = std::thread::spawn(move || {
+            let request = responder.receive().unwrap();
+            assert_eq!(request.data(), [0x22, 0xF1, 0x90]);
+            let mut response = vec![0x62, 0xF1, 0x90];
+            response.extend_from_slice(b"WDB1234567A890123");
+            request.respond(response).unwrap();
+            // no more requests
+            assert!(matches!(responder.receive(), Err(Error::IsoTp(_))));
+        });
+
+        let response = requester.request(vec![0x22, 0xF1, 0x90]).unwrap();
+        assert_eq!(&response[..3], [0x62, 0xF1, 0x90]);
+        assert_eq!(&response[3..], b"WDB1234567A890123");
+        server.join().unwrap();
+        device.close();
+    }
+}
@@ -0,0 +1,66 @@
+use isotp_rs::IsoTpState;
+
+/// Describe the active flags of `state` and what the combination means, e.g. for logging stuck transfers.
+///
+/// The flags are joined by `|`, e.g. `WaitFlowCtrl|Sending: sending the first frame, then waiting for flow control`.
+pub fn describe_state(state: IsoTpState) -> String {
+    let flags = state.iter_names()
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>();
+    let flags = match flags.is_empty() {
+        true => "Idle".to_owned(),
+        false => flags.join("|"),
+    };
+
+    let meaning = if state.contains(IsoTpState::Error) {
+        "aborted by an error"
+    }
+    else if state.contains(IsoTpState::WaitBusy) {
+        "paused by a wait flow control frame"
+    }
+    else if state.contains(IsoTpState::Sending | IsoTpState::WaitFlowCtrl) {
+        "sending the first frame, then waiting for flow control"
+    }
+    else if state.contains(IsoTpState::WaitFlowCtrl) {
+        "waiting for flow control after first frame"
+    }
+    else if state.contains(IsoTpState::Sending) {
+        "sending a frame"
+    }
+    else if state == IsoTpState::Idle {
+        "idle"
+    }
+    else {
+        "unknown"
+    };
+
+    format!("{}: {}", flags, meaning)
+}
+
+#[cfg(test)]
+mod tests {
+    use isotp_rs::IsoTpState;
+    use super::describe_state;
+
+    #[test]
+    fn test_describe_state() {
+        assert_eq!(describe_state(IsoTpState::Idle), "Idle: idle");
+        assert_eq!(describe_state(IsoTpState::Sending), "Sending: sending a frame");
+        assert_eq!(
+            describe_state(IsoTpState::Sending | IsoTpState::WaitFlowCtrl),
+            "WaitFlowCtrl|Sending: sending the first frame, then waiting for flow control"
+        );
+        assert_eq!(
+            describe_state(IsoTpState::WaitFlowCtrl),
+            "WaitFlowCtrl: waiting for flow control after first frame"
+        );
+        assert_eq!(
+            describe_state(IsoTpState::WaitFlowCtrl | IsoTpState::WaitBusy),
+            "WaitFlowCtrl|WaitBusy: paused by a wait flow control frame"
+        );
+        assert_eq!(
+            describe_state(IsoTpState::Sending | IsoTpState::Error),
+            "Sending|Error: aborted by an error"
+        );
+    }
+}
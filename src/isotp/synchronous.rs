@@ -1,183 +1,766 @@
 mod listener;
+mod nonblocking;
+pub use nonblocking::{WriteHandle, WriteProgress};
 
-use std::sync::{Arc, Mutex};
-use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, RwLock, RwLockWriteGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{SendError, Sender};
 use std::thread::sleep;
 use std::time::Duration;
-use isotp_rs::{FlowControlContext, FlowControlState, IsoTpEvent, IsoTpEventListener, IsoTpFrame, IsoTpState, can::{Address, CanIsoTpFrame}};
+use std::time::Instant;
+use isotp_rs::{FlowControlContext, FlowControlState, IsoTpFrame, IsoTpState, can::{CanIsoTpFrame, ISO_TP_MAX_LENGTH_2004}};
+use isotp_rs::constant::ST_MIN_ISO15765_2;
 use isotp_rs::error::Error as IsoTpError;
+use crate::device::SyncDevice;
 use crate::frame::Frame;
 use crate::identifier::Id;
-use crate::isotp::context::IsoTpContext;
+use crate::isotp::{Address, DEFAULT_WFT_MAX, Error, IsoTpEvent, IsoTpEventListener, OnDecodeError, RetryPolicy, util};
+use crate::isotp::context::{ContextSnapshot, IsoTpContext, Retained};
+#[cfg(test)]
+use crate::isotp::context::ACQUISITIONS;
+use crate::isotp::logger::{Logger, RawFrameObserver};
+#[cfg(feature = "metrics")]
+use crate::isotp::TransferMetrics;
 
+/// An ISO-TP session of a channel, the clones share the state of the session.
+///
+/// It is `Send` if the channel and the frame are `Send`:
+/// ```compile_fail
+/// use std::rc::Rc;
+/// use can_type_rs::isotp::SyncCanIsoTp;
+///
+/// fn assert_send<T: Send>() {}
+/// assert_send::<SyncCanIsoTp<Rc<u8>, ()>>();
+/// ```
 #[derive(Clone)]
 pub struct SyncCanIsoTp<C, F> {
     pub(crate) channel: C,
     pub(crate) address: Address,
     pub(crate) sender: Sender<F>,
-    pub(crate) context: IsoTpContext,
+    pub(crate) context: Arc<RwLock<IsoTpContext>>,
     pub(crate) state: Arc<Mutex<IsoTpState>>,
-    pub(crate) listener: Arc<Mutex<Box<dyn IsoTpEventListener>>>,
+    pub(crate) listener: Arc<Mutex<Box<dyn IsoTpEventListener + Send>>>,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) retained: Arc<Mutex<Retained>>,
+    pub(crate) zero_copy: bool,
+    pub(crate) block_size: u8,
+    pub(crate) max_receive_length: usize,
+    pub(crate) min_single_frame_length: usize,
+    pub(crate) wft_max: u8,
+    pub(crate) min_gap: Duration,
+    pub(crate) padding: Option<u8>,
+    pub(crate) can_fd: bool,
+    pub(crate) on_decode_error: OnDecodeError,
+    pub(crate) decode_errors: Arc<AtomicUsize>,
+    pub(crate) logger: Logger,
+    pub(crate) raw_observer: Arc<RwLock<Option<RawFrameObserver<F>>>>,
 }
 
-unsafe impl<C, F> Send for SyncCanIsoTp<C, F> {}
-
 impl<C: Clone, F: Frame<Channel = C>> SyncCanIsoTp<C, F> {
 
     pub fn new(channel: C,
-               address: Address,
+               address: impl Into<Address>,
                sender: Sender<F>,
-               listener: Box<dyn IsoTpEventListener>
+               listener: Box<dyn IsoTpEventListener + Send>
     ) -> Self {
         Self {
             channel,
-            address,
+            address: address.into(),
             sender,
             context: Default::default(),
             state: Default::default(),
             listener: Arc::new(Mutex::new(listener)),
+            retry: Default::default(),
+            retained: Default::default(),
+            zero_copy: false,
+            block_size: 0,
+            max_receive_length: ISO_TP_MAX_LENGTH_2004,
+            min_single_frame_length: 0,
+            wft_max: DEFAULT_WFT_MAX,
+            min_gap: Duration::ZERO,
+            padding: None,
+            can_fd: cfg!(feature = "can-fd"),
+            on_decode_error: Default::default(),
+            decode_errors: Default::default(),
+            logger: Default::default(),
+            raw_observer: Default::default(),
+        }
+    }
+
+    /// Retry the frames that are not sent to the device, and retransmit the ones that the device failed
+    /// to transmit by `policy`, see [`Listener::on_frame_transmit_failed`](crate::device::Listener::on_frame_transmit_failed).
+    ///
+    /// The backoff of a retransmission is waited in the callback, i.e. the device loop is blocked meanwhile.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Deliver the received messages by [`IsoTpEventListener::on_data_received`] with a borrowed slice
+    /// instead of [`IsoTpEvent::DataReceived`], the reassembly buffer is reused without cloning.
+    pub fn with_zero_copy(mut self, enabled: bool) -> Self {
+        self.zero_copy = enabled;
+        self
+    }
+
+    /// The block size(BS) of the flow control frames sent when receiving, a new flow control frame
+    /// is sent after every `block_size` consecutive frames. 0 means no more flow control frame.
+    pub fn with_block_size(mut self, block_size: u8) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// The max data length of the received messages, 4095 by default.
+    ///
+    /// A first frame that declares a longer length is rejected by an overflow flow control frame
+    /// before the buffer is allocated.
+    pub fn with_max_receive_length(mut self, length: usize) -> Self {
+        self.max_receive_length = length;
+        self
+    }
+
+    /// The min data length of the received single frames, 0 by default, e.g. 1 for UDS responses.
+    ///
+    /// A shorter single frame is not delivered but reported by [`IsoTpEvent::ErrorOccurred`],
+    /// a single frame that declares more bytes than it carries is always rejected.
+    pub fn with_min_single_frame_length(mut self, length: usize) -> Self {
+        self.min_single_frame_length = length;
+        self
+    }
+
+    /// The max count of consecutive `Wait` flow control frames tolerated when writing(N_WFTmax), 10 by default.
+    ///
+    /// The write is aborted with [`Error::WaitOverflow`] when more `Wait` frames are received in a row.
+    pub fn with_wft_max(mut self, max: u8) -> Self {
+        self.wft_max = max;
+        self
+    }
+
+    /// The min gap between the consecutive frames when writing, 0 by default,
+    /// e.g. to throttle the writes on a bus that is shared with time-critical traffic.
+    ///
+    /// The effective gap is the max of it and the separation time(STmin) of the flow control.
+    pub fn with_min_gap(mut self, gap: Duration) -> Self {
+        self.min_gap = gap;
+        self
+    }
+
+    /// The padding value of the written frames and the flow control frames,
+    /// [`isotp_rs::can::DEFAULT_PADDING`] is used when `None`.
+    ///
+    /// [`Self::write_raw`] is padded by its own `padding`.
+    pub fn with_padding(mut self, padding: Option<u8>) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Returns the padding value set by [`Self::with_padding`].
+    #[inline]
+    pub fn padding(&self) -> Option<u8> {
+        self.padding
+    }
+
+    /// Segment and pad the written frames for CAN-FD instead of classic CAN, e.g. to talk to both classic
+    /// and CAN-FD nodes by one build, it is enabled by default with the `can-fd` feature.
+    ///
+    /// The received frames of both are decoded.
+    pub fn with_can_fd(mut self, enabled: bool) -> Self {
+        self.can_fd = enabled;
+        self
+    }
+
+    /// What is done to the received frames that fail to be decoded, [`OnDecodeError::Skip`] by default.
+    pub fn with_decode_error_policy(mut self, policy: OnDecodeError) -> Self {
+        self.on_decode_error = policy;
+        self
+    }
+
+    /// Returns the count of the frames that fail to be decoded by [`OnDecodeError::Count`]
+    /// of this channel and its clones.
+    #[inline]
+    pub fn decode_errors(&self) -> usize {
+        self.decode_errors.load(Ordering::Relaxed)
+    }
+
+    /// Route the log messages of this channel and its clones to `hook` instead of the `log` facade,
+    /// e.g. to downgrade the decoding failures of a busy bus or to count them.
+    pub fn set_log_hook(&mut self, hook: impl Fn(log::Level, &str) + Send + Sync + 'static) {
+        self.logger.set_hook(Box::new(hook));
+    }
+
+    /// Observe every raw frame that this channel and its clones receive on `rx_id` before decoding,
+    /// e.g. to capture the exact bytes of a frame that fails to be decoded.
+    pub fn set_raw_frame_observer(&mut self, observer: impl Fn(&F) + Send + Sync + 'static) {
+        match self.raw_observer.write() {
+            Ok(mut v) => *v = Some(Box::new(observer)),
+            Err(_) => self.logger.warn(format_args!("ISO-TP: raw frame observer lock is poisoned")),
         }
     }
 
+    /// Pre-allocate `capacity` bytes of the reassembly buffer, it only grows for larger messages.
+    pub fn with_receive_capacity(mut self, capacity: usize) -> Self {
+        self.context = Arc::new(RwLock::new(IsoTpContext::with_capacity(capacity)));
+        self
+    }
+
     pub fn write(&mut self, functional: bool, data: Vec<u8>) -> Result<(), IsoTpError> {
-        log::debug!("ISO-TP(CAN sync) - Sending: {:?}", data);
-        let frames = CanIsoTpFrame::from_data(data)?;
+        let can_id = if functional { self.address.fid } else { self.address.tx_id };
+        self.write_to(can_id, data)
+    }
+
+    /// Write to the explicit identifier `target_id` instead of `tx_id` or `fid` of the address,
+    /// e.g. a functional group identifier that is addressed dynamically.
+    ///
+    /// The flow control is still received on `rx_id`.
+    pub fn write_to(&mut self, target_id: u32, data: Vec<u8>) -> Result<(), IsoTpError> {
+        self.logger.debug(format_args!("ISO-TP(CAN sync) - Sending to {:#X}: {:?}", target_id, data));
+        #[cfg(feature = "metrics")]
+        let (started, bytes) = (Instant::now(), data.len());
+        let frames = util::segment(&data, self.can_fd)?;
+
+        #[cfg(feature = "metrics")]
         let frame_len = frames.len();
+        #[cfg(feature = "metrics")]
+        if let Some(mut context) = self.context_mut() {
+            context.metrics.start_writing();
+        }
+
+        let result = self.write_frames(target_id, frames, self.padding);
+        if result.is_err() {
+            self.write_reset();
+        }
+        #[cfg(feature = "metrics")]
+        if let (Ok(_), Some(mut context)) = (&result, self.context_mut()) {
+            context.metrics.complete_writing(started, bytes, frame_len);
+        }
+
+        result
+    }
 
+    /// Transmit the pre-segmented `frames` as-is without re-segmenting, e.g. to replay a captured transfer.
+    ///
+    /// The flow control is still waited after a first frame, the frames are padded with `padding`
+    /// or [`isotp_rs::can::DEFAULT_PADDING`].
+    pub fn write_raw(&mut self, functional: bool, frames: Vec<CanIsoTpFrame>, padding: Option<u8>) -> Result<(), IsoTpError> {
+        self.logger.debug(format_args!("ISO-TP(CAN sync) - Sending raw: {:?}", frames));
         let can_id = if functional { self.address.fid } else { self.address.tx_id };
+
+        let result = self.write_frames(can_id, frames, padding);
+        if result.is_err() {
+            self.write_reset();
+        }
+
+        result
+    }
+
+    /// Reset the state after a failed write, the channel is usable for the next write.
+    fn write_reset(&self) {
+        self.state_reset();
+        if let Some(mut context) = self.context_mut() {
+            context.clear_flow_ctrl();
+        }
+    }
+
+    fn write_frames(&mut self, can_id: u32, frames: Vec<CanIsoTpFrame>, padding: Option<u8>) -> Result<(), IsoTpError> {
+        if let Some(mut context) = self.context_mut() {
+            context.tx.write_id = Some(can_id);
+        }
         for (index, frame) in frames.into_iter().enumerate() {
             self.write_waiting(index)?;
-            let mut frame = F::from_iso_tp(Id::from_bits(can_id, false), frame, None)
-                .ok_or(IsoTpError::ConvertError {
-                    src: "iso-tp frame",
-                    target: "can-frame",
-                })?;
-            frame.set_channel(self.channel.clone());
-
-            self.state_append(IsoTpState::Sending);
-            if 0 == index && 1 < frame_len {
-                self.state_append(IsoTpState::WaitFlowCtrl);
-            }
-            self.sender.send(frame)
-                .map_err(|e| {
-                    log::warn!("ISO-TP(CAN sync) - transmit failed: {:?}", e);
-                    IsoTpError::DeviceError
-                })?;
+            self.send_frame(can_id, frame, padding)?;
         }
 
         Ok(())
     }
 
-    #[inline]
-    pub(crate) fn on_single_frame(&mut self, data: Vec<u8>) {
-        self.iso_tp_event(IsoTpEvent::DataReceived(data));
+    fn send_frame(&self, can_id: u32, frame: CanIsoTpFrame, padding: Option<u8>) -> Result<(), IsoTpError> {
+        let (first, consecutive) = (
+            matches!(frame, CanIsoTpFrame::FirstFrame { .. }),
+            matches!(frame, CanIsoTpFrame::ConsecutiveFrame { .. }),
+        );
+        self.retain_frame(can_id, &frame, padding);
+        let frame = self.new_frame(can_id, frame, padding)?;
+
+        self.state_append(IsoTpState::Sending);
+        if first {
+            self.state_append(IsoTpState::WaitFlowCtrl);
+        }
+        if consecutive {
+            self.count_block_frame();
+        }
+        self.send(frame)
     }
 
-    #[inline]
-    pub(crate) fn on_first_frame(&mut self, length: u32, data: Vec<u8>) {
-        self.context.update_consecutive(length, data);
+    /// Send `frame` to the device, the failed sends are retried by the retry policy.
+    fn send(&self, frame: F) -> Result<(), IsoTpError> {
+        let mut pending = Some(frame);
+        self.retry.retry(|| match pending.take() {
+            Some(frame) => self.sender.send(frame)
+                .map_err(|SendError(frame)| {
+                    self.logger.warn(format_args!("ISO-TP(CAN sync) - transmit failed"));
+                    pending = Some(frame);
+                    IsoTpError::DeviceError
+                }),
+            None => Err(IsoTpError::DeviceError),
+        })
+    }
 
-        let iso_tp_frame = CanIsoTpFrame::default_flow_ctrl_frame();
+    /// Build the CAN frame of `frame` on the channel.
+    fn new_frame(&self, can_id: u32, frame: CanIsoTpFrame, padding: Option<u8>) -> Result<F, IsoTpError> {
+        // a short single frame of CAN-FD is padded to 8 bytes only
+        let mut frame = F::from_iso_tp_sized(Id::from_bits(can_id, self.address.extended), frame, padding, self.can_fd)
+            .ok_or(IsoTpError::ConvertError {
+                src: "iso-tp frame",
+                target: "can-frame",
+            })?;
+        frame.set_channel(self.channel.clone());
 
-        match F::from_iso_tp(
-            Id::from_bits(self.address.tx_id, false),
-            iso_tp_frame,
-            None
-        ) {
-            Some(mut frame) => {
-                frame.set_channel(self.channel.clone());
+        Ok(frame)
+    }
 
-                self.state_append(IsoTpState::Sending);
-                match self.sender.send(frame) {
-                    Ok(_) => {
-                        self.iso_tp_event(IsoTpEvent::FirstFrameReceived);
-                    },
-                    Err(e) => {
-                        log::warn!("ISO-TP - transmit failed: {:?}", e);
-                        self.state_append(IsoTpState::Error);
+    /// Keep the frame being written to be retransmitted, nothing is kept if the retry policy does not retry.
+    fn retain_frame(&self, can_id: u32, frame: &CanIsoTpFrame, padding: Option<u8>) {
+        if self.retry.max_retries == 0 {
+            return;
+        }
 
-                        self.iso_tp_event(IsoTpEvent::ErrorOccurred(IsoTpError::DeviceError));
-                    },
-                }
+        match self.retained.lock() {
+            Ok(mut retained) => {
+                retained.frame = Some((can_id, frame.clone(), padding));
+                retained.retries = 0;
+            },
+            Err(_) => self.logger.warn(format_args!("ISO-TP: retained frame lock is poisoned")),
+        }
+    }
+
+    /// Retransmit the retained frame after the backoff of the retry policy,
+    /// returns false if the retries are exhausted.
+    pub(crate) fn retransmit(&self) -> bool {
+        let (can_id, frame, padding) = match self.retained.lock() {
+            Ok(mut retained) if retained.retries < self.retry.max_retries => match retained.frame.clone() {
+                Some(v) => {
+                    retained.retries += 1;
+                    v
+                },
+                None => return false,
             },
-            None => log::error!("ISO-TP: convert `iso-tp frame` to `can-frame` error"),
+            Ok(_) => return false,
+            Err(_) => {
+                self.logger.warn(format_args!("ISO-TP: retained frame lock is poisoned"));
+                return false;
+            },
+        };
+
+        self.logger.debug(format_args!("ISO-TP(CAN sync) - retransmitting to {:#X}", can_id));
+        if !self.retry.backoff.is_zero() {
+            sleep(self.retry.backoff);
+        }
+        match self.new_frame(can_id, frame, padding) {
+            Ok(frame) => self.sender.send(frame).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    /// Wait until the transfer in flight completes or `timeout` elapses, then unregister the listener
+    /// `name` of this channel from `device` and close the device.
+    ///
+    /// Returns false if timed out, the unfinished transfer is discarded then.
+    pub fn close_gracefully<D>(&mut self, device: &mut D, name: &str, timeout: Duration) -> bool
+    where
+        D: SyncDevice<Channel = C, Frame = F> {
+        let start = Instant::now();
+        let idle = loop {
+            if self.is_idle() {
+                break true;
+            }
+            if start.elapsed() >= timeout {
+                self.logger.warn(format_args!("ISO-TP(CAN sync) - close timed out, the transfer is discarded"));
+                break false;
+            }
+            sleep(Duration::from_micros(10));
+        };
+
+        device.unregister_listener(name.to_owned());
+        device.close();
+
+        self.state_reset();
+        if let Some(mut context) = self.context_mut() {
+            context.reset();
+        }
+
+        idle
+    }
+
+    /// Discard the data of the message being received, the state flags and the flow control are kept.
+    pub fn clear_data(&self) {
+        if let Some(mut context) = self.context_mut() {
+            context.clear_data();
+        }
+    }
+
+    /// Pause the sender of the message being received by a `Wait` flow control frame, e.g. when busy.
+    ///
+    /// Returns false if no message is being received or the frame is not sent, see [`Self::resume`].
+    pub fn request_wait(&self) -> bool {
+        match self.context.read() {
+            Ok(context) if context.rx.consecutive.length.is_some() => {
+                drop(context);
+                self.write_flow_ctrl(FlowControlState::Wait)
+            },
+            _ => false,
+        }
+    }
+
+    /// Resume the sender paused by [`Self::request_wait`] with a `ContinueToSend` flow control frame,
+    /// a new block of consecutive frames is started.
+    ///
+    /// Returns false if no message is being received or the frame is not sent.
+    pub fn resume(&self) -> bool {
+        match self.context_mut() {
+            Some(mut context) if context.rx.consecutive.length.is_some() => {
+                context.rx.block_frames = 0;
+                drop(context);
+                self.write_flow_ctrl(FlowControlState::Continues)
+            },
+            _ => false,
+        }
+    }
+
+    /// Copy the address, state, flow control and reassembly progress of the channel for diagnostics.
+    pub fn snapshot(&self) -> Option<ContextSnapshot> {
+        let context = self.context.read().ok()?;
+        let state = *self.state.lock().ok()?;
+
+        Some(context.snapshot(self.address, state))
+    }
+
+    /// Returns the count of bytes still expected of the message being received, i.e. the declared length
+    /// minus the bytes received so far, e.g. to request a larger block size when lots remain.
+    ///
+    /// `None` if no segmented message is being received, a single frame is never in progress.
+    pub fn remaining(&self) -> Option<usize> {
+        self.context.read().ok()?.remaining()
+    }
+
+    /// Returns the block size requested by the most recent flow control frame,
+    /// `None` if no flow control frame is received since the last reset.
+    pub fn current_block_size(&self) -> Option<u8> {
+        self.context.read().ok()?
+            .tx.flow_ctrl.as_ref()
+            .map(|ctx| ctx.block_size)
+    }
+
+    /// Returns the metrics of the last completed write.
+    #[cfg(feature = "metrics")]
+    pub fn write_metrics(&self) -> Option<TransferMetrics> {
+        self.context.read().ok()?.metrics.written
+    }
+
+    /// Returns the metrics of the last received message.
+    #[cfg(feature = "metrics")]
+    pub fn receive_metrics(&self) -> Option<TransferMetrics> {
+        self.context.read().ok()?.metrics.received
+    }
+
+    /// Process a batch of received frames while holding the context lock only once.
+    pub(crate) fn on_frames(&mut self, frames: &[CanIsoTpFrame]) {
+        let mut context = match self.context_mut() {
+            Some(v) => v,
+            None => return,
+        };
+
+        let mut idle = false;
+        for frame in frames {
+            match frame {
+                CanIsoTpFrame::SingleFrame { data } if data.len() < self.min_single_frame_length => {
+                    self.logger.warn(format_args!("ISO-TP - single frame length: {} is less than the min: {}", data.len(), self.min_single_frame_length));
+                    // the same as a valid single frame, a segmented message being received is kept
+                    self.iso_tp_event(IsoTpEvent::ErrorOccurred(IsoTpError::InvalidDataLength {
+                        actual: data.len(),
+                        expect: self.min_single_frame_length,
+                    }.into()));
+                },
+                CanIsoTpFrame::SingleFrame { data } => {
+                    #[cfg(feature = "metrics")]
+                    {
+                        context.metrics.start_receiving();
+                        context.metrics.complete_receiving(data.len());
+                    }
+                    self.on_single_frame(data);
+                }
+                CanIsoTpFrame::FirstFrame { length, data } => {
+                    self.on_first_frame(&mut context, *length, data);
+                }
+                CanIsoTpFrame::ConsecutiveFrame { sequence, data } => {
+                    self.on_consecutive_frame(&mut context, *sequence, data);
+                },
+                CanIsoTpFrame::FlowControlFrame(ctx) => {
+                    idle |= self.on_flow_ctrl_frame(&mut context, *ctx);
+                },
+            }
+        }
+        // the listener may start the next write that locks the context
+        drop(context);
+        if idle {
+            self.iso_tp_event(IsoTpEvent::Idle);
         }
     }
 
     #[inline]
-    pub(crate) fn on_consecutive_frame(&mut self, sequence: u8, data: Vec<u8>) {
-        match self.context.append_consecutive(sequence, data) {
-            Ok(event) => {
-                match event {
-                    IsoTpEvent::DataReceived(_) => {
-                        self.context.reset();
-                    },
-                    _ => {},
+    pub(crate) fn on_single_frame(&self, data: &[u8]) {
+        self.iso_tp_event(IsoTpEvent::ReceiveCompleted { frame_count: 1, segmented: false });
+        match self.zero_copy {
+            true => self.data_received(data),
+            false => self.iso_tp_event(IsoTpEvent::DataReceived(data.to_vec())),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn on_first_frame(&self, context: &mut IsoTpContext, length: u32, data: &[u8]) {
+        if length as usize > self.max_receive_length {
+            self.logger.warn(format_args!("ISO-TP - first frame length: {} exceeds the max: {}", length, self.max_receive_length));
+            context.clear_data();
+            self.write_flow_ctrl(FlowControlState::Overload);
+            self.iso_tp_event(IsoTpEvent::ErrorOccurred(IsoTpError::LengthOutOfRange(length as usize).into()));
+            return;
+        }
+
+        context.update_consecutive(length, data);
+        #[cfg(feature = "metrics")]
+        context.metrics.start_receiving();
+
+        if self.write_flow_ctrl(FlowControlState::Continues) {
+            self.iso_tp_event(IsoTpEvent::FirstFrame { length });
+        }
+    }
+
+    #[inline]
+    pub(crate) fn on_consecutive_frame(&self, context: &mut IsoTpContext, sequence: u8, data: &[u8]) {
+        // the count of frames if this one completes the message
+        let frame_count = context.rx.frames + 1;
+        match context.append_consecutive(sequence, data) {
+            Ok(Some(data)) => {
+                #[cfg(feature = "metrics")]
+                let length = data.len();
+                self.iso_tp_event(IsoTpEvent::ReceiveCompleted { frame_count, segmented: true });
+                match self.zero_copy {
+                    true => self.data_received(data),
+                    false => self.iso_tp_event(IsoTpEvent::DataReceived(data.to_vec())),
+                }
+                #[cfg(feature = "metrics")]
+                {
+                    context.metrics.frame_received();
+                    context.metrics.complete_receiving(length);
+                }
+                // the flow control of a message being written is kept
+                context.clear_data();
+            },
+            Ok(None) => {
+                #[cfg(feature = "metrics")]
+                context.metrics.frame_received();
+                self.iso_tp_event(IsoTpEvent::Wait);
+                if context.block_exhausted(self.block_size) {
+                    self.write_flow_ctrl(FlowControlState::Continues);
                 }
-                self.iso_tp_event(event);
             },
             Err(e) => {
-                self.state_append(IsoTpState::Error);
+                // only the message being received is dropped, the next first frame is accepted
+                context.clear_data();
                 self.iso_tp_event(IsoTpEvent::ErrorOccurred(e));
             }
         }
     }
 
+    /// Returns true if the state turns into idle, [`IsoTpEvent::Idle`] is sent by the caller
+    /// after the context lock is released.
     #[inline]
-    pub(crate) fn on_flow_ctrl_frame(&mut self, ctx: FlowControlContext) {
-        match ctx.state() {
+    pub(crate) fn on_flow_ctrl_frame(&self, context: &mut IsoTpContext, ctx: FlowControlContext) -> bool {
+        self.iso_tp_event(IsoTpEvent::FlowControl {
+            state: ctx.state(),
+            block_size: ctx.block_size(),
+            st_min: ctx.st_min(),
+        });
+
+        let idle = match ctx.state() {
             FlowControlState::Continues => {
-                self.state_remove(IsoTpState::WaitBusy | IsoTpState::WaitFlowCtrl);
+                self.state_transit(|v| v.remove(IsoTpState::WaitBusy | IsoTpState::WaitFlowCtrl))
             },
             FlowControlState::Wait => {
+                #[cfg(feature = "metrics")]
+                { context.metrics.flow_control_waits += 1; }
+                context.tx.wait_frames = context.tx.wait_frames.saturating_add(1);
+                if context.tx.wait_frames > self.wft_max {
+                    self.logger.warn(format_args!("ISO-TP - wait flow control frames exceed the max: {}", self.wft_max));
+                    self.state_append(IsoTpState::Error);
+                    self.iso_tp_event(IsoTpEvent::ErrorOccurred(Error::WaitOverflow(self.wft_max)));
+                    return false;
+                }
                 self.state_append(IsoTpState::WaitBusy);
                 self.iso_tp_event(IsoTpEvent::Wait);
-                return;
+                return false;
             }
             FlowControlState::Overload => {
                 self.state_append(IsoTpState::Error);
-                self.iso_tp_event(IsoTpEvent::ErrorOccurred(IsoTpError::OverloadFlow));
-                return;
+                self.iso_tp_event(IsoTpEvent::ErrorOccurred(IsoTpError::OverloadFlow.into()));
+                return false;
             }
-        }
+        };
 
-        self.context.update_flow_ctrl(ctx);
+        context.update_flow_ctrl(ctx);
+        idle
+    }
+
+    /// Send a flow control frame with the block size, returns true if it is sent.
+    fn write_flow_ctrl(&self, state: FlowControlState) -> bool {
+        let iso_tp_frame = CanIsoTpFrame::flow_ctrl_frame(
+            state,
+            self.block_size,
+            ST_MIN_ISO15765_2,
+        );
+
+        self.retain_frame(self.address.tx_id, &iso_tp_frame, self.padding);
+        match self.new_frame(self.address.tx_id, iso_tp_frame, self.padding) {
+            Ok(frame) => {
+                self.state_append(IsoTpState::Sending);
+                match self.send(frame) {
+                    Ok(_) => true,
+                    Err(e) => {
+                        self.logger.warn(format_args!("ISO-TP - transmit failed: {:?}", e));
+                        self.state_append(IsoTpState::Error);
+
+                        self.iso_tp_event(IsoTpEvent::ErrorOccurred(IsoTpError::DeviceError.into()));
+                        false
+                    },
+                }
+            },
+            Err(_) => {
+                self.logger.error(format_args!("ISO-TP: convert `iso-tp frame` to `can-frame` error"));
+                false
+            },
+        }
     }
 
     fn iso_tp_event(&self, event: IsoTpEvent) {
         match self.listener.lock() {
             Ok(mut listener) => {
                 // println!("ISO-TP(CAN asyn): Sending iso-tp event: {:?}", event);
-                log::trace!("ISO-TP(CAN asyn): Sending iso-tp event: {:?}", event);
+                self.logger.trace(format_args!("ISO-TP(CAN asyn): Sending iso-tp event: {:?}", event));
                 listener.on_iso_tp_event(event);
             },
-            Err(_) => log::warn!("ISO-TP(CAN async): Sending event failed"),
+            Err(_) => self.logger.warn(format_args!("ISO-TP(CAN async): Sending event failed")),
+        }
+    }
+
+    fn data_received(&self, data: &[u8]) {
+        match self.listener.lock() {
+            Ok(mut listener) => {
+                self.logger.trace(format_args!("ISO-TP(CAN sync): Sending received data: {:?}", data));
+                listener.on_data_received(data);
+            },
+            Err(_) => self.logger.warn(format_args!("ISO-TP(CAN sync): Sending data failed")),
         }
     }
 
     fn write_waiting(&mut self, index: usize) -> Result<(), IsoTpError> {
-        if let Some(ctx) = &self.context.flow_ctrl {
-            if ctx.block_size != 0 &&
-                0 == ctx.block_size as usize % (index + 1) {
-                self.state_append(IsoTpState::WaitFlowCtrl);
-            }
-            sleep(Duration::from_micros(ctx.st_min as u64));
+        let gap = self.write_gap(index);
+        if !gap.is_zero() {
+            sleep(gap);
         }
 
-        loop {
-            if self.state_contains(IsoTpState::Error) {
-                return Err(IsoTpError::DeviceError);
-            }
+        while self.write_blocked()? {
+            sleep(Duration::from_micros(10));
+        }
 
-            if self.state_contains(IsoTpState::Sending | IsoTpState::WaitBusy | IsoTpState::WaitFlowCtrl) {
-                sleep(Duration::from_micros(10));
-            }
-            else {
-                break;
+        Ok(())
+    }
+
+    /// The gap before writing the frame of `index`, the flow control is awaited again
+    /// when a block is exhausted.
+    fn write_gap(&self, index: usize) -> Duration {
+        let (flow_ctrl, exhausted) = match self.context.read() {
+            Ok(context) => (context.tx.flow_ctrl.clone(), context.write_block_exhausted()),
+            Err(_) => {
+                self.logger.warn(format_args!("ISO-TP: context lock is poisoned"));
+                (None, false)
+            },
+        };
+        // the next block is started by a new flow control frame, the block of the last write is done
+        if index > 0 && exhausted {
+            self.state_append(IsoTpState::WaitFlowCtrl);
+        }
+        let st_min = match flow_ctrl {
+            Some(ctx) => Duration::from_micros(ctx.st_min as u64),
+            None => Duration::ZERO,
+        };
+        // the first frame is not throttled
+        match index {
+            0 => st_min,
+            _ => st_min.max(self.min_gap),
+        }
+    }
+
+    /// Returns true if the next frame is not writable until the last one is transmitted
+    /// or the flow control is received.
+    fn write_blocked(&self) -> Result<bool, IsoTpError> {
+        if self.state_contains(IsoTpState::Error) {
+            return Err(IsoTpError::DeviceError);
+        }
+
+        Ok(self.state_contains(IsoTpState::Sending | IsoTpState::WaitBusy | IsoTpState::WaitFlowCtrl))
+    }
+
+    #[inline]
+    pub(crate) fn observe_raw(&self, frame: &F) {
+        if let Ok(observer) = self.raw_observer.read() {
+            if let Some(observer) = observer.as_ref() {
+                observer(frame);
             }
         }
+    }
 
-        Ok(())
+    #[inline]
+    fn context_mut(&self) -> Option<RwLockWriteGuard<'_, IsoTpContext>> {
+        #[cfg(test)]
+        ACQUISITIONS.set(ACQUISITIONS.get() + 1);
+        match self.context.write() {
+            Ok(v) => Some(v),
+            Err(_) => {
+                self.logger.warn(format_args!("ISO-TP: context lock is poisoned"));
+                None
+            },
+        }
+    }
+
+    /// Count a frame written in the block of the flow control, before it is sent so that
+    /// the flow control frame responded to it is not missed.
+    #[inline]
+    fn count_block_frame(&self) {
+        if let Some(mut context) = self.context_mut() {
+            context.tx.block_frames = context.tx.block_frames.saturating_add(1);
+        }
+    }
+
+    /// Returns true if `id` is the identifier that this channel writes to.
+    fn is_write_id<Id: PartialEq<u32>>(&self, id: &Id) -> bool {
+        if *id == self.address.tx_id || *id == self.address.fid {
+            return true;
+        }
+
+        match self.context.read() {
+            Ok(context) => context.tx.write_id.is_some_and(|v| *id == v),
+            Err(_) => false,
+        }
+    }
+
+    /// Returns true if neither writing nor receiving a message.
+    #[inline]
+    fn is_idle(&self) -> bool {
+        let receiving = match self.context.read() {
+            Ok(context) => context.rx.consecutive.length.is_some(),
+            Err(_) => false,
+        };
+
+        !receiving && !self.state_contains(IsoTpState::Sending | IsoTpState::WaitBusy | IsoTpState::WaitFlowCtrl)
     }
 
     #[inline]
@@ -185,7 +768,7 @@ impl<C: Clone, F: Frame<Channel = C>> SyncCanIsoTp<C, F> {
         match self.state.lock() {
             Ok(v) => *v & flags != IsoTpState::Idle,
             Err(_) => {
-                log::warn!("ISO-TP: state mutex is poisoned");
+                self.logger.warn(format_args!("ISO-TP: state mutex is poisoned"));
                 false
             },
         }
@@ -202,15 +785,1101 @@ impl<C: Clone, F: Frame<Channel = C>> SyncCanIsoTp<C, F> {
                     *v |= flags;
                 }
             }
-            Err(_) => log::warn!("ISO-TP: state mutex is poisoned"),
+            Err(_) => self.logger.warn(format_args!("ISO-TP: state mutex is poisoned")),
         }
     }
 
+    #[inline]
+    fn state_reset(&self) {
+        self.state_update(|v| *v = IsoTpState::Idle);
+    }
+
     #[inline]
     fn state_remove(&self, flags: IsoTpState) {
+        self.state_update(|v| v.remove(flags));
+    }
+
+    /// Update the state by `f`, [`IsoTpEvent::Idle`] is sent after the lock is released
+    /// if the state turns into idle.
+    fn state_update(&self, f: impl FnOnce(&mut IsoTpState)) {
+        if self.state_transit(f) {
+            self.iso_tp_event(IsoTpEvent::Idle);
+        }
+    }
+
+    /// Update the state by `f` without any event, returns true if the state turns into idle.
+    fn state_transit(&self, f: impl FnOnce(&mut IsoTpState)) -> bool {
         match self.state.lock() {
-            Ok(mut v) => v.remove(flags),
-            Err(_) => log::warn!("ISO-TP: state mutex is poisoned"),
+            Ok(mut v) => {
+                let busy = *v != IsoTpState::Idle;
+                f(&mut v);
+                busy && *v == IsoTpState::Idle
+            },
+            Err(_) => {
+                self.logger.warn(format_args!("ISO-TP: state mutex is poisoned"));
+                false
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::sync::mpsc::channel;
+    use std::time::{Duration, Instant};
+    use isotp_rs::{FlowControlState, IsoTpFrame, IsoTpState, can::CanIsoTpFrame};
+    use isotp_rs::constant::ST_MIN_ISO15765_2;
+    use isotp_rs::error::Error as IsoTpError;
+    use crate::device::{ErrorInjector, Fault, Listener, LoopbackDevice, SyncDevice, Target, TransmitFailure};
+    use crate::frame::Frame;
+    use crate::identifier::Id;
+    use crate::isotp::{Address, Error, IsoTpEvent, IsoTpEventListener, OnDecodeError, RetryPolicy};
+    use crate::isotp::context::ACQUISITIONS;
+    use crate::mock::{MockEventListener, MockFrame};
+    use super::{SyncCanIsoTp, WriteProgress};
+
+    const ADDRESS: Address = Address { tx_id: 0x7E0, rx_id: 0x7E8, fid: 0x7DF, extended: false };
+
+    fn received_frames(data: &[u8]) -> Vec<MockFrame> {
+        CanIsoTpFrame::from_data(data).unwrap()
+            .into_iter()
+            .map(|frame| MockFrame::from_iso_tp(Id::from_bits(ADDRESS.rx_id, false), frame, None).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<SyncCanIsoTp<u8, MockFrame>>();
+    }
+
+    #[test]
+    fn test_batch_lock_acquisitions() {
+        // 1 first frame and 9 consecutive frames
+        let data = (0..69).map(|v| v as u8).collect::<Vec<u8>>();
+        let frames = received_frames(&data);
+        assert_eq!(frames.len(), 10);
+
+        let (sender, _receiver) = channel();
+        let listener = MockEventListener::default();
+        let mut batched = SyncCanIsoTp::new(0, ADDRESS, sender.clone(), Box::new(listener.clone()));
+        let acquisitions = ACQUISITIONS.get();
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut batched, 0, &frames);
+        assert_eq!(ACQUISITIONS.get() - acquisitions, 1);
+        assert!(matches!(listener.events().last(), Some(IsoTpEvent::DataReceived(v)) if *v == data));
+
+        let mut per_frame = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(MockEventListener::default()));
+        let acquisitions = ACQUISITIONS.get();
+        for frame in &frames {
+            Listener::<u8, u32, MockFrame>::on_frame_received(&mut per_frame, 0, std::slice::from_ref(frame));
+        }
+        assert_eq!(ACQUISITIONS.get() - acquisitions, frames.len());
+    }
+
+    #[test]
+    fn test_first_frame_event() {
+        let data = (0..300).map(|v| v as u8).collect::<Vec<u8>>();
+        let frames = received_frames(&data);
+
+        let (sender, receiver) = channel();
+        let listener = MockEventListener::default();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(listener.clone()));
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &frames[..1]);
+        assert!(matches!(listener.events().as_slice(), [IsoTpEvent::FirstFrame { length: 300 }]));
+        // the flow control frame is responded
+        assert!(receiver.try_recv().is_ok());
+    }
+
+    #[test]
+    fn test_flow_control_event() {
+        let (sender, _receiver) = channel();
+        let flow_ctrl = |data: &[u8]| {
+            let listener = MockEventListener::default();
+            let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender.clone(), Box::new(listener.clone()))
+                .with_decode_error_policy(OnDecodeError::Abort);
+            let frame = MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), data).unwrap();
+            Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[frame]);
+            let error = iso_tp.state_contains(IsoTpState::Error);
+            (listener.events(), error)
+        };
+
+        let (events, error) = flow_ctrl(&[0x30, 0x08, 0x0A, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]);
+        assert!(matches!(events.as_slice(), [
+            IsoTpEvent::FlowControl { state: FlowControlState::Continues, block_size: 0x08, st_min: 0x0A }
+        ]));
+        assert!(!error);
+
+        let (events, error) = flow_ctrl(&[0x31, 0x00, 0x00, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]);
+        assert!(matches!(events.as_slice(), [
+            IsoTpEvent::FlowControl { state: FlowControlState::Wait, .. },
+            IsoTpEvent::Wait,
+        ]));
+        assert!(!error);
+
+        let (events, error) = flow_ctrl(&[0x32, 0x00, 0x00, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]);
+        assert!(matches!(events.as_slice(), [
+            IsoTpEvent::FlowControl { state: FlowControlState::Overload, .. },
+            IsoTpEvent::ErrorOccurred(_),
+        ]));
+        assert!(error);
+
+        let (events, error) = flow_ctrl(&[0x3F, 0x00, 0x00, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]);
+        assert!(matches!(events.as_slice(), [IsoTpEvent::ErrorOccurred(Error::InvalidFlowStatus(0x0F))]));
+        assert!(error);
+    }
+
+    #[test]
+    fn test_zero_copy() {
+        let data = (0..300).map(|v| v as u8).collect::<Vec<u8>>();
+        let frames = received_frames(&data);
+
+        let (sender, _receiver) = channel();
+        let listener = MockEventListener::default();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(listener.clone()))
+            .with_zero_copy(true)
+            .with_receive_capacity(data.len());
+        let buffer = |iso_tp: &SyncCanIsoTp<u8, MockFrame>| {
+            let context = iso_tp.context.read().unwrap();
+            (context.rx.consecutive.buffer.as_ptr(), context.rx.consecutive.buffer.capacity())
+        };
+        let allocated = buffer(&iso_tp);
+
+        for _ in 0..3 {
+            Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &frames);
+            assert_eq!(buffer(&iso_tp), allocated);
+        }
+        let received = listener.events()
+            .into_iter()
+            .filter(|event| matches!(event, IsoTpEvent::DataReceived(v) if *v == data))
+            .count();
+        assert_eq!(received, 3);
+    }
+
+    #[test]
+    fn test_block_size_flow_control() {
+        let data = (0..200).map(|v| v as u8).collect::<Vec<u8>>();
+        let frames = received_frames(&data);
+        // 1 first frame and 28 consecutive frames
+        assert_eq!(frames.len(), 29);
+
+        let (sender, receiver) = channel();
+        let listener = MockEventListener::default();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(listener.clone()))
+            .with_block_size(4);
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &frames);
+        assert!(matches!(listener.events().last(), Some(IsoTpEvent::DataReceived(v)) if *v == data));
+
+        let flow_ctrl = receiver.try_iter()
+            .map(|frame| frame.data().to_vec())
+            .collect::<Vec<_>>();
+        // after the first frame and every 4 consecutive frames except the last block
+        assert_eq!(flow_ctrl.len(), 7);
+        assert!(flow_ctrl.iter().all(|v| v[..2] == [0x30, 0x04]));
+    }
+
+    #[test]
+    fn test_write_failure() {
+        let (sender, receiver) = channel();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(MockEventListener::default()));
+
+        let mut writer = iso_tp.clone();
+        let handle = std::thread::spawn(move || {
+            // the 2nd frame fails to be constructed
+            MockFrame::fail_after(1);
+            writer.write(false, vec![0x55; 20])
+        });
+
+        assert!(receiver.recv().is_ok());
+        Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut iso_tp, 0, ADDRESS.tx_id);
+        let flow_ctrl = MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &[0x30, 0x00, 0x00, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]).unwrap();
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[flow_ctrl]);
+
+        assert!(handle.join().unwrap().is_err());
+        assert!(!iso_tp.state_contains(IsoTpState::Sending | IsoTpState::WaitBusy | IsoTpState::WaitFlowCtrl | IsoTpState::Error));
+        assert!(iso_tp.context.read().unwrap().tx.flow_ctrl.is_none());
+
+        // the channel is usable afterward
+        assert!(iso_tp.write(false, vec![0x01, 0x02, 0x03]).is_ok());
+        assert_eq!(receiver.try_recv().unwrap().data()[..4], [0x03, 0x01, 0x02, 0x03]);
+    }
+
+    #[test]
+    fn test_full_duplex() {
+        let (sender, receiver) = channel();
+        let listener = MockEventListener::default();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(listener.clone()));
+
+        let mut writer = iso_tp.clone();
+        let handle = std::thread::spawn(move || writer.write(false, vec![0x55; 20]));
+
+        // the first frame of the request
+        assert!(receiver.recv().is_ok());
+        Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut iso_tp, 0, ADDRESS.tx_id);
+        // 20ms between the consecutive frames
+        let flow_ctrl = MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &[0x30, 0x00, 0x14, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]).unwrap();
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[flow_ctrl]);
+        // the 1st consecutive frame of the request
+        assert_eq!(receiver.recv().unwrap().data()[0], 0x21);
+
+        // a response is received while the request is being written
+        let data = (0..20).map(|v| v as u8).collect::<Vec<u8>>();
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &received_frames(&data));
+        assert!(matches!(listener.events().last(), Some(IsoTpEvent::DataReceived(v)) if *v == data));
+        assert_eq!(iso_tp.current_block_size(), Some(0));
+
+        Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut iso_tp, 0, ADDRESS.tx_id);
+        assert!(handle.join().unwrap().is_ok());
+        let frames = receiver.try_iter()
+            .map(|frame| frame.data()[0])
+            .collect::<Vec<_>>();
+        // the flow control of the response and the 2nd consecutive frame of the request
+        assert_eq!(frames, vec![0x30, 0x22]);
+    }
+
+    #[test]
+    fn test_write_nonblocking() {
+        let (sender, receiver) = channel::<MockFrame>();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(MockEventListener::default()))
+            .with_min_gap(Duration::from_millis(10));
+        let mut handle = iso_tp.write_nonblocking(false, vec![0x55; 20]).unwrap();
+        assert_eq!(handle.remaining(), 3);
+
+        // the first frame is sent, then the flow control is awaited
+        assert!(matches!(handle.poll(), WriteProgress::Pending));
+        assert_eq!(receiver.try_recv().unwrap().data()[0], 0x10);
+        Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut iso_tp, 0, ADDRESS.tx_id);
+        assert!(matches!(handle.poll(), WriteProgress::Pending));
+        assert!(receiver.try_recv().is_err());
+
+        // 10ms between the consecutive frames by the min gap
+        let flow_ctrl = MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &[0x30, 0x00, 0x00, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]).unwrap();
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[flow_ctrl]);
+        let start = Instant::now();
+        let mut sent = Vec::new();
+        let progress = loop {
+            match handle.poll() {
+                WriteProgress::Pending => {
+                    if let Ok(frame) = receiver.try_recv() {
+                        sent.push(frame.data()[0]);
+                        Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut iso_tp, 0, ADDRESS.tx_id);
+                    }
+                    assert!(start.elapsed() < Duration::from_millis(500));
+                },
+                progress => break progress,
+            }
+        };
+        assert!(matches!(progress, WriteProgress::Done));
+        sent.extend(receiver.try_iter().map(|frame| frame.data()[0]));
+        assert_eq!(sent, vec![0x21, 0x22]);
+        assert!(start.elapsed() >= Duration::from_millis(10));
+        assert_eq!(handle.remaining(), 0);
+        assert!(matches!(handle.poll(), WriteProgress::Done));
+
+        // aborted by an overload flow control
+        let mut handle = iso_tp.write_nonblocking(false, vec![0x55; 20]).unwrap();
+        assert!(matches!(handle.poll(), WriteProgress::Pending));
+        let overload = MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &[0x32, 0x00, 0x00, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]).unwrap();
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[overload]);
+        assert!(matches!(handle.poll(), WriteProgress::Error(IsoTpError::DeviceError)));
+        assert!(matches!(handle.poll(), WriteProgress::Error(_)));
+        assert_eq!(handle.remaining(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "metrics")]
+    fn test_metrics() {
+        let data = (0..200).map(|v| v as u8).collect::<Vec<u8>>();
+        let frames = received_frames(&data);
+
+        let (sender, _receiver) = channel();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(MockEventListener::default()));
+        assert!(iso_tp.receive_metrics().is_none());
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &frames);
+        let metrics = iso_tp.receive_metrics().unwrap();
+        assert_eq!(metrics.bytes, 200);
+        assert_eq!(metrics.frames, frames.len());
+        assert!(metrics.completed >= metrics.started);
+
+        // the flow control frames are transmitted
+        Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut iso_tp, 0, ADDRESS.tx_id);
+        iso_tp.write(false, vec![0x01, 0x02, 0x03]).unwrap();
+        let metrics = iso_tp.write_metrics().unwrap();
+        assert_eq!((metrics.bytes, metrics.frames, metrics.flow_control_waits), (3, 1, 0));
+    }
+
+    #[test]
+    fn test_current_block_size() {
+        let (sender, _receiver) = channel::<MockFrame>();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(MockEventListener::default()));
+        assert_eq!(iso_tp.current_block_size(), None);
+
+        for (block_size, expected) in [(0x08, 8), (0x00, 0)] {
+            let flow_ctrl = MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &[0x30, block_size, 0x0A, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]).unwrap();
+            Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[flow_ctrl]);
+            assert_eq!(iso_tp.current_block_size(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_min_single_frame_length() {
+        let (sender, _receiver) = channel::<MockFrame>();
+        let listener = MockEventListener::default();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(listener.clone()))
+            .with_min_single_frame_length(1);
+
+        iso_tp.on_frames(&[CanIsoTpFrame::SingleFrame { data: vec![] }]);
+        assert!(matches!(
+            listener.events().as_slice(),
+            [IsoTpEvent::ErrorOccurred(Error::IsoTp(IsoTpError::InvalidDataLength { actual: 0, expect: 1 }))]
+        ));
+
+        let frame = MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &[0x01, 0x7E, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]).unwrap();
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[frame]);
+        assert!(matches!(listener.events().last(), Some(IsoTpEvent::DataReceived(v)) if *v == [0x7E]));
+
+        // a segmented message being received is not aborted by the rejected single frame
+        let data = (0..20).map(|v| v as u8).collect::<Vec<u8>>();
+        let frames = CanIsoTpFrame::from_data(data.clone()).unwrap();
+        iso_tp.on_frames(&[frames[0].clone(), CanIsoTpFrame::SingleFrame { data: vec![] }]);
+        assert!(matches!(
+            listener.events().last(),
+            Some(IsoTpEvent::ErrorOccurred(Error::IsoTp(IsoTpError::InvalidDataLength { actual: 0, expect: 1 })))
+        ));
+        iso_tp.on_frames(&frames[1..]);
+        assert!(matches!(listener.events().last(), Some(IsoTpEvent::DataReceived(v)) if *v == data));
+    }
+
+    #[test]
+    fn test_clear_data_on_error() {
+        let (sender, _receiver) = channel::<MockFrame>();
+        let listener = MockEventListener::default();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(listener.clone()));
+        let stale = received_frames(&[0xEE; 20]);
+
+        // the 1st consecutive frame is lost
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[stale[0].clone(), stale[2].clone()]);
+        assert!(matches!(
+            listener.events().last(),
+            Some(IsoTpEvent::ErrorOccurred(Error::MissingConsecutive { expected: 1, got: 2 }))
+        ));
+        let snapshot = iso_tp.snapshot().unwrap();
+        assert_eq!((snapshot.length, snapshot.received, snapshot.sequence), (None, 0, None));
+        assert!(!iso_tp.state_contains(IsoTpState::Error));
+        // the rest of the aborted message is not appended
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &stale[1..2]);
+        assert!(matches!(listener.events().last(), Some(IsoTpEvent::ErrorOccurred(Error::IsoTp(IsoTpError::MixFramesError)))));
+
+        let data = (0..20).map(|v| v as u8).collect::<Vec<u8>>();
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &received_frames(&data));
+        assert!(matches!(listener.events().last(), Some(IsoTpEvent::DataReceived(v)) if *v == data));
+    }
+
+    #[test]
+    fn test_request_wait() {
+        let (sender, receiver) = channel::<MockFrame>();
+        let listener = MockEventListener::default();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(listener.clone()));
+        assert!(!iso_tp.request_wait());
+        assert!(!iso_tp.resume());
+
+        let peer = ADDRESS.swapped();
+        let (peer_sender, peer_receiver) = channel::<MockFrame>();
+        let mut peer_tp = SyncCanIsoTp::new(0, peer, peer_sender, Box::new(MockEventListener::default()));
+        let mut writer = peer_tp.clone();
+        let data = (0..20).map(|v| v as u8).collect::<Vec<u8>>();
+        let handle = std::thread::spawn({
+            let data = data.clone();
+            move || writer.write(false, data)
+        });
+
+        // first frame and ContinueToSend
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[peer_receiver.recv().unwrap()]);
+        Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut peer_tp, 0, peer.tx_id);
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut peer_tp, 0, &[receiver.recv().unwrap()]);
+
+        // the receiver is busy after the first consecutive frame
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[peer_receiver.recv().unwrap()]);
+        assert!(iso_tp.request_wait());
+        let wait = receiver.recv().unwrap();
+        assert_eq!(wait.data()[0], 0x31);
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut peer_tp, 0, &[wait]);
+        Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut peer_tp, 0, peer.tx_id);
+        assert!(peer_tp.state_contains(IsoTpState::WaitBusy));
+        assert!(peer_receiver.recv_timeout(Duration::from_millis(20)).is_err());
+
+        assert!(iso_tp.resume());
+        let resume = receiver.recv().unwrap();
+        assert_eq!(resume.data()[0], 0x30);
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut peer_tp, 0, &[resume]);
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[peer_receiver.recv().unwrap()]);
+        Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut peer_tp, 0, peer.tx_id);
+
+        assert!(handle.join().unwrap().is_ok());
+        assert!(matches!(listener.events().last(), Some(IsoTpEvent::DataReceived(v)) if *v == data));
+    }
+
+    #[test]
+    fn test_can_fd_coexistence() {
+        let (sender, receiver) = channel::<MockFrame>();
+        let classic = SyncCanIsoTp::new(0, ADDRESS, sender.clone(), Box::new(MockEventListener::default()))
+            .with_can_fd(false);
+        let mut fd = SyncCanIsoTp::new(1, ADDRESS, sender, Box::new(MockEventListener::default()))
+            .with_can_fd(true);
+        let data = (0..20).map(|v| v as u8).collect::<Vec<u8>>();
+
+        // a single frame of 24 bytes
+        fd.write(false, data.clone()).unwrap();
+        let frame = receiver.try_recv().unwrap();
+        assert_eq!((frame.data().len(), &frame.data()[..2]), (24, &[0x00, 20][..]));
+        // a first frame of classic CAN, then the flow control is awaited
+        let mut handle = classic.write_nonblocking(false, data.clone()).unwrap();
+        assert_eq!(handle.remaining(), 3);
+        assert!(matches!(handle.poll(), WriteProgress::Pending));
+        assert_eq!(receiver.try_recv().unwrap().data(), &[0x10, 0x14, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05]);
+
+        // the frames of CAN-FD are received by a session of classic CAN too
+        let listener = MockEventListener::default();
+        let mut peer = SyncCanIsoTp::new(1, ADDRESS.swapped(), channel().0, Box::new(listener.clone()))
+            .with_can_fd(false);
+        let mut frame = MockFrame::new(Id::from_bits(ADDRESS.tx_id, false), frame.data()).unwrap();
+        frame.set_channel(1);
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut peer, 1, &[frame]);
+        assert!(matches!(listener.events().last(), Some(IsoTpEvent::DataReceived(v)) if *v == data));
+    }
+
+    #[test]
+    fn test_remaining() {
+        let data = (0..20).map(|v| v as u8).collect::<Vec<u8>>();
+        let frames = received_frames(&data);
+        let (sender, _receiver) = channel();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(MockEventListener::default()));
+        assert_eq!(iso_tp.remaining(), None);
+
+        // 6 bytes of the first frame, 7 bytes of every consecutive frame
+        let remaining = frames.iter()
+            .map(|frame| {
+                Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, std::slice::from_ref(frame));
+                iso_tp.remaining()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(remaining, vec![Some(14), Some(7), None]);
+
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &received_frames(&[0x3E, 0x00]));
+        assert_eq!(iso_tp.remaining(), None);
+    }
+
+    #[test]
+    fn test_idle_event() {
+        let (sender, receiver) = channel::<MockFrame>();
+        let listener = MockEventListener::default();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(listener.clone()));
+
+        iso_tp.write(false, vec![0x3E, 0x00]).unwrap();
+        assert!(receiver.try_recv().is_ok());
+        assert!(listener.events().is_empty());
+
+        Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut iso_tp, 0, ADDRESS.tx_id);
+        assert!(matches!(listener.events().as_slice(), [IsoTpEvent::Idle]));
+        // idle already
+        Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut iso_tp, 0, ADDRESS.tx_id);
+        assert_eq!(listener.events().len(), 1);
+    }
+
+    #[test]
+    fn test_write_on_idle_event() {
+        /// Write the next message when the channel turns into idle.
+        struct IdleWriter {
+            iso_tp: Arc<Mutex<Option<SyncCanIsoTp<u8, MockFrame>>>>,
+            results: Arc<Mutex<Vec<Result<(), IsoTpError>>>>,
+        }
+
+        impl IsoTpEventListener for IdleWriter {
+            fn clear_buffer(&mut self) {}
+            fn on_iso_tp_event(&mut self, event: IsoTpEvent) {
+                if let (IsoTpEvent::Idle, Some(iso_tp)) = (event, self.iso_tp.lock().unwrap().as_mut()) {
+                    self.results.lock().unwrap().push(iso_tp.write(false, vec![0x3E, 0x00]));
+                }
+            }
         }
+
+        let (sender, receiver) = channel::<MockFrame>();
+        let (writer, results) = (Arc::new(Mutex::new(None)), Arc::new(Mutex::new(Vec::new())));
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(IdleWriter {
+            iso_tp: writer.clone(),
+            results: results.clone(),
+        }));
+        *writer.lock().unwrap() = Some(iso_tp.clone());
+
+        // the flow control frame turns the state into idle while the frames are processed
+        iso_tp.state_append(IsoTpState::WaitFlowCtrl);
+        let (done, finished) = channel();
+        std::thread::spawn(move || {
+            let frame = MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &[0x30, 0x00, 0x00, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]).unwrap();
+            Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[frame]);
+            done.send(()).unwrap();
+        });
+
+        assert!(finished.recv_timeout(Duration::from_secs(1)).is_ok());
+        assert!(matches!(results.lock().unwrap().as_slice(), [Ok(())]));
+        assert_eq!(receiver.try_recv().unwrap().data(), &[0x02, 0x3E, 0x00, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]);
+        // release the session held by the listener
+        writer.lock().unwrap().take();
+    }
+
+    #[test]
+    fn test_wft_max() {
+        let (sender, receiver) = channel::<MockFrame>();
+        let listener = MockEventListener::default();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(listener.clone()));
+        let mut writer = iso_tp.clone();
+        let handle = std::thread::spawn(move || writer.write(false, vec![0x01; 20]));
+
+        receiver.recv().unwrap();
+        Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut iso_tp, 0, ADDRESS.tx_id);
+        let wait = MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &[0x31, 0x00, 0x00, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]).unwrap();
+        for _ in 0..10 {
+            Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, std::slice::from_ref(&wait));
+        }
+        assert!(!iso_tp.state_contains(IsoTpState::Error));
+
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[wait]);
+        assert!(handle.join().unwrap().is_err());
+        // the write is reset after the error
+        assert!(matches!(
+            listener.events().as_slice(),
+            [.., IsoTpEvent::ErrorOccurred(Error::WaitOverflow(10)), IsoTpEvent::Idle]
+        ));
+        // no consecutive frame is sent
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_min_gap() {
+        let (sender, receiver) = channel::<MockFrame>();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(MockEventListener::default()))
+            .with_min_gap(Duration::from_millis(1));
+        let mut writer = iso_tp.clone();
+        let handle = std::thread::spawn(move || {
+            let start = Instant::now();
+            writer.write(false, vec![0x01; 40]).map(|_| start.elapsed())
+        });
+
+        receiver.recv().unwrap();
+        Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut iso_tp, 0, ADDRESS.tx_id);
+        // STmin = 0
+        let flow_ctrl = MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &[0x30, 0x00, 0x00, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]).unwrap();
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[flow_ctrl]);
+        // 5 consecutive frames
+        for _ in 0..5 {
+            receiver.recv().unwrap();
+            Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut iso_tp, 0, ADDRESS.tx_id);
+        }
+
+        let elapsed = handle.join().unwrap().unwrap();
+        assert!(elapsed >= Duration::from_millis(5));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_consecutive_overflow() {
+        let (sender, _receiver) = channel::<MockFrame>();
+        let listener = MockEventListener::default();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(listener.clone()));
+
+        // 10 bytes are declared but 6 + 2 * 63 bytes are sent
+        let mut first = vec![0x10, 0x0A];
+        first.extend(0..6);
+        let mut frames = vec![MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &first).unwrap()];
+        frames.extend((1..=2).map(|sequence| {
+            let mut data = vec![0x20 | sequence];
+            data.extend([0x55; 63]);
+            MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &data).unwrap()
+        }));
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &frames[..2]);
+
+        let expected = [0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x55, 0x55, 0x55, 0x55];
+        assert!(matches!(listener.events().last(), Some(IsoTpEvent::DataReceived(v)) if *v == expected));
+        // the buffer is not grown beyond the declared length
+        assert!(iso_tp.context.read().unwrap().rx.consecutive.buffer.capacity() < 63);
+
+        // no message is being received
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &frames[2..]);
+        assert!(matches!(listener.events().last(), Some(IsoTpEvent::ErrorOccurred(Error::IsoTp(IsoTpError::MixFramesError)))));
+    }
+
+    #[test]
+    fn test_decode_error_policy() {
+        let data = (0..30).collect::<Vec<u8>>();
+        let mut frames = received_frames(&data);
+        // the noise of a reserved frame type amid the consecutive frames
+        let noise = MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &[0x40, 0x01, 0x02, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]).unwrap();
+        frames.insert(2, noise);
+
+        let (sender, _receiver) = channel::<MockFrame>();
+        let receive = |policy| {
+            let listener = MockEventListener::default();
+            let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender.clone(), Box::new(listener.clone()))
+                .with_decode_error_policy(policy);
+            frames.chunks(1)
+                .for_each(|frame| Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, frame));
+            let received = listener.events()
+                .into_iter()
+                .find_map(|event| match event {
+                    IsoTpEvent::DataReceived(data) => Some(data),
+                    _ => None,
+                });
+            (received, iso_tp.decode_errors(), iso_tp.state_contains(IsoTpState::Error))
+        };
+
+        assert_eq!(receive(OnDecodeError::Skip), (Some(data.clone()), 0, false));
+        assert_eq!(receive(OnDecodeError::Count), (Some(data.clone()), 1, false));
+        assert_eq!(receive(OnDecodeError::Abort), (None, 0, true));
+    }
+
+    #[test]
+    fn test_receive_completed() {
+        let (sender, _receiver) = channel::<MockFrame>();
+        let receive = |data: &[u8]| {
+            let listener = MockEventListener::default();
+            let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender.clone(), Box::new(listener.clone()));
+            Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &received_frames(data));
+            let events = listener.events();
+            let index = events.iter()
+                .position(|event| matches!(event, IsoTpEvent::ReceiveCompleted { .. }))
+                .unwrap();
+            // right before the data
+            assert!(matches!(&events[index + 1], IsoTpEvent::DataReceived(v) if v == data));
+            match events[index] {
+                IsoTpEvent::ReceiveCompleted { frame_count, segmented } => (frame_count, segmented),
+                _ => unreachable!(),
+            }
+        };
+
+        assert_eq!(receive(&[0x3E, 0x00]), (1, false));
+        // a first frame of 6 bytes and 2 consecutive frames
+        assert_eq!(receive(&(0..20).collect::<Vec<u8>>()), (3, true));
+    }
+
+    #[test]
+    fn test_raw_frame_observer() {
+        let (sender, _receiver) = channel::<MockFrame>();
+        let listener = MockEventListener::default();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(listener.clone()))
+            .with_decode_error_policy(OnDecodeError::Abort);
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        let cloned = Arc::clone(&observed);
+        iso_tp.set_raw_frame_observer(move |frame: &MockFrame| cloned.lock().unwrap().push(frame.data().to_vec()));
+
+        // not a frame type of ISO-TP
+        let invalid = MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &[0x40, 0x01, 0x02, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]).unwrap();
+        let other = MockFrame::new(Id::from_bits(0x100, false), &[0x02, 0x3E, 0x00]).unwrap();
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp.clone(), 0, &[other, invalid.clone()]);
+
+        assert!(matches!(listener.events().last(), Some(IsoTpEvent::ErrorOccurred(_))));
+        assert_eq!(*observed.lock().unwrap(), vec![invalid.data().to_vec()]);
+    }
+
+    #[test]
+    fn test_write_to() {
+        let (sender, receiver) = channel::<MockFrame>();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(MockEventListener::default()));
+
+        iso_tp.write_to(0x7DE, vec![0x3E, 0x80]).unwrap();
+        let frame = receiver.try_recv().unwrap();
+        assert_eq!(frame.id(false), Id::Standard(0x7DE));
+        assert_eq!(&frame.data()[..3], [0x02, 0x3E, 0x80]);
+        assert!(iso_tp.state_contains(IsoTpState::Sending));
+
+        // the transmission of the explicit identifier completes the write
+        Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut iso_tp, 0, 0x7DE);
+        assert!(!iso_tp.state_contains(IsoTpState::Sending));
+    }
+
+    #[test]
+    fn test_transmit_failed() {
+        let (sender, receiver) = channel::<MockFrame>();
+        let listener = MockEventListener::default();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(listener.clone()));
+        let mut writer = iso_tp.clone();
+        let handle = std::thread::spawn(move || writer.write(false, vec![0x01; 20]));
+
+        receiver.recv().unwrap();
+        // other identifiers are ignored
+        Listener::<u8, u32, MockFrame>::on_frame_transmit_failed(&mut iso_tp, 0, ADDRESS.rx_id, TransmitFailure::ArbitrationLost);
+        assert!(!iso_tp.state_contains(IsoTpState::Error));
+
+        Listener::<u8, u32, MockFrame>::on_frame_transmit_failed(&mut iso_tp, 0, ADDRESS.tx_id, TransmitFailure::ArbitrationLost);
+        // the write errors out instead of waiting for the flow control
+        assert!(handle.join().unwrap().is_err());
+        assert!(matches!(
+            listener.events().as_slice(),
+            [.., IsoTpEvent::ErrorOccurred(Error::TransmitFailed(TransmitFailure::ArbitrationLost)), IsoTpEvent::Idle]
+        ));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_log_hook() {
+        let (sender, _receiver) = channel::<MockFrame>();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(MockEventListener::default()));
+        // the registered clone shares the hook
+        let mut registered = iso_tp.clone();
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        iso_tp.set_log_hook({
+            let messages = messages.clone();
+            move |level, message| messages.lock().unwrap().push((level, message.to_owned()))
+        });
+
+        let frame = MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &[0x3F, 0x00, 0x00, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]).unwrap();
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut registered, 0, &[frame]);
+
+        let messages = messages.lock().unwrap();
+        assert!(messages.iter().any(|(level, message)|
+            *level == log::Level::Warn && message.contains("data convert to frame failed")
+        ));
+        assert!(messages.iter().any(|(level, _)| *level == log::Level::Debug));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let data = (0..40).map(|v| v as u8).collect::<Vec<u8>>();
+        let frames = received_frames(&data);
+
+        let (sender, _receiver) = channel();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(MockEventListener::default()));
+        let snapshot = iso_tp.snapshot().unwrap();
+        assert_eq!(snapshot.state, IsoTpState::Idle);
+        assert_eq!((snapshot.length, snapshot.received, snapshot.sequence), (None, 0, None));
+
+        let flow_ctrl = MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &[0x30, 0x08, 0x0A, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]).unwrap();
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[flow_ctrl, frames[0].clone(), frames[1].clone()]);
+        iso_tp.state_remove(IsoTpState::Sending);
+        let snapshot = iso_tp.snapshot().unwrap();
+        assert_eq!(snapshot.address, ADDRESS);
+        assert_eq!(snapshot.state, IsoTpState::Idle);
+        assert_eq!(snapshot.st_min, Some(10_000));
+        assert_eq!(snapshot.block_size, Some(8));
+        assert_eq!(snapshot.length, Some(40));
+        assert_eq!(snapshot.received, 13);
+        assert_eq!(snapshot.sequence, Some(1));
+    }
+
+    #[test]
+    fn test_clear_data() {
+        let data = (0..200).map(|v| v as u8).collect::<Vec<u8>>();
+        let frames = received_frames(&data);
+
+        let (sender, _receiver) = channel();
+        let listener = MockEventListener::default();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(listener.clone()));
+        let flow_ctrl = MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &[0x30, 0x08, 0x0A, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]).unwrap();
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[flow_ctrl, frames[0].clone(), frames[1].clone()]);
+        iso_tp.state_append(IsoTpState::WaitBusy);
+
+        iso_tp.clear_data();
+        let context = iso_tp.context.read().unwrap();
+        assert!(context.rx.consecutive.buffer.is_empty());
+        assert!(context.rx.consecutive.sequence.is_none());
+        assert!(context.rx.consecutive.length.is_none());
+        assert!(matches!(&context.tx.flow_ctrl, Some(ctx) if ctx.block_size == 0x08));
+        drop(context);
+        assert!(iso_tp.state_contains(IsoTpState::WaitBusy | IsoTpState::Sending));
+    }
+
+    #[test]
+    fn test_max_receive_length() {
+        let (sender, receiver) = channel();
+        let listener = MockEventListener::default();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(listener.clone()))
+            .with_max_receive_length(0x10000);
+        // the escaped first frame of 0xFFFFFFFF bytes
+        let first = MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &[0x10, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x01]).unwrap();
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[first]);
+
+        assert!(matches!(
+            listener.events().as_slice(),
+            [IsoTpEvent::ErrorOccurred(Error::IsoTp(isotp_rs::error::Error::LengthOutOfRange(0xFFFFFFFF)))]
+        ));
+        assert_eq!(iso_tp.context.read().unwrap().rx.consecutive.buffer.capacity(), 0);
+        // rejected by an overflow flow control frame
+        assert_eq!(receiver.try_recv().unwrap().data()[0], 0x32);
+    }
+
+    #[test]
+    #[cfg(feature = "can-fd")]
+    fn test_can_fd_short_single() {
+        let (sender, receiver) = channel::<MockFrame>();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(MockEventListener::default()));
+
+        iso_tp.write(false, vec![0x22, 0xF1, 0x90]).unwrap();
+        let frame = receiver.try_recv().unwrap();
+        assert_eq!(frame.data().len(), 8);
+        assert_eq!(&frame.data()[..4], [0x03, 0x22, 0xF1, 0x90]);
+    }
+
+    #[test]
+    fn test_padding() {
+        let (sender, receiver) = channel::<MockFrame>();
+        let iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(MockEventListener::default()));
+        assert_eq!(iso_tp.padding(), None);
+        let mut iso_tp = iso_tp.with_padding(Some(0x00));
+        assert_eq!(iso_tp.padding(), Some(0x00));
+
+        iso_tp.write(false, vec![0x3E, 0x80]).unwrap();
+        assert_eq!(receiver.try_recv().unwrap().data(), [0x02, 0x3E, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut iso_tp, 0, ADDRESS.tx_id);
+
+        // the flow control frame too
+        let frames = received_frames(&(0..20).collect::<Vec<u8>>());
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &frames[..1]);
+        assert_eq!(receiver.try_recv().unwrap().data(), [0x30, 0x00, ST_MIN_ISO15765_2, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_write_raw() {
+        let frames = vec![
+            CanIsoTpFrame::FirstFrame { length: 10, data: vec![0x01; 6] },
+            CanIsoTpFrame::ConsecutiveFrame { sequence: 1, data: vec![0x02; 3] },
+            CanIsoTpFrame::ConsecutiveFrame { sequence: 2, data: vec![0x03; 1] },
+        ];
+        let expected = frames.iter()
+            .map(|frame| frame.clone().encode(Some(0x00)))
+            .collect::<Vec<_>>();
+
+        let (sender, receiver) = channel::<MockFrame>();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(MockEventListener::default()));
+        let mut writer = iso_tp.clone();
+        let handle = std::thread::spawn(move || writer.write_raw(false, frames, Some(0x00)));
+
+        let mut transmitted = Vec::new();
+        for index in 0..expected.len() {
+            transmitted.push(receiver.recv().unwrap().data().to_vec());
+            Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut iso_tp, 0, ADDRESS.tx_id);
+            if index == 0 {
+                let flow_ctrl = MockFrame::new(Id::from_bits(ADDRESS.rx_id, false), &[0x30, 0x00, 0x00, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]).unwrap();
+                Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[flow_ctrl]);
+            }
+        }
+
+        assert!(handle.join().unwrap().is_ok());
+        assert_eq!(transmitted, expected);
+    }
+
+    #[test]
+    fn test_extended_address() {
+        // 29-bit identifiers in the 11-bit range
+        let address = Address { tx_id: 0x7E0, rx_id: 0x7E8, fid: 0x7DF, extended: true };
+        let (sender, receiver) = channel::<MockFrame>();
+        let mut iso_tp = SyncCanIsoTp::new(0, address, sender, Box::new(MockEventListener::default()));
+        let mut writer = iso_tp.clone();
+        let handle = std::thread::spawn(move || writer.write(false, vec![0x3E, 0x00]));
+
+        let frame = receiver.recv().unwrap();
+        assert!(frame.is_extended());
+        assert_eq!(frame.id(false), Id::Extended(0x7E0));
+        Listener::<u8, u32, MockFrame>::on_frame_transmitted(&mut iso_tp, 0, address.tx_id);
+        assert!(handle.join().unwrap().is_ok());
+
+        // the flow control of a received first frame
+        let first = MockFrame::new(Id::Extended(address.rx_id), &[0x10, 0x14, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05]).unwrap();
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &[first]);
+        let flow_ctrl = receiver.recv().unwrap();
+        assert!(flow_ctrl.is_extended());
+        assert_eq!(flow_ctrl.id(false), Id::Extended(0x7E0));
+    }
+
+    #[test]
+    fn test_consecutive_sequence() {
+        let data = (0..40).map(|v| v as u8).collect::<Vec<u8>>();
+        let frames = received_frames(&data);
+        let receive = |sequences: &[usize]| {
+            let (sender, _receiver) = channel();
+            let listener = MockEventListener::default();
+            let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(listener.clone()));
+            let frames = std::iter::once(&frames[0])
+                .chain(sequences.iter().map(|&v| &frames[v]))
+                .cloned()
+                .collect::<Vec<_>>();
+            Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &frames);
+            listener.events().pop()
+        };
+
+        // frame 3 is lost
+        assert!(matches!(receive(&[1, 2, 4]), Some(IsoTpEvent::ErrorOccurred(Error::MissingConsecutive { expected: 3, got: 4 }))));
+        assert!(matches!(
+            receive(&[1, 2, 1]),
+            Some(IsoTpEvent::ErrorOccurred(Error::IsoTp(isotp_rs::error::Error::InvalidSequence { expect: 3, actual: 1 })))
+        ));
+    }
+
+    #[test]
+    fn test_dropped_consecutive_frame() {
+        // FF, FC, CF1, CF2(dropped), CF3 ...
+        let injector = ErrorInjector::default()
+            .with_rule(Target::Nth(4), Fault::Drop);
+        let mut device = LoopbackDevice::<u8, MockFrame>::new(())
+            .with_error_injector(injector);
+        let listener = MockEventListener::default();
+        let receiver = SyncCanIsoTp::new(0, ADDRESS, device.sender(), Box::new(listener.clone()));
+        let peer = ADDRESS.swapped();
+        let mut writer = SyncCanIsoTp::new(0, peer, device.sender(), Box::new(MockEventListener::default()));
+        assert!(device.register_listener("receiver".into(), Box::new(receiver.clone())));
+        assert!(device.register_listener("writer".into(), Box::new(writer.clone())));
+        device.sync_start(100);
+
+        let data = (0..40).map(|v| v as u8).collect::<Vec<u8>>();
+        assert!(writer.write(false, data).is_ok());
+        std::thread::sleep(Duration::from_millis(20));
+        device.close();
+
+        // the transfer is aborted
+        assert!(listener.events().iter().any(|event| matches!(
+            event,
+            IsoTpEvent::ErrorOccurred(Error::MissingConsecutive { expected: 2, got: 3 })
+        )));
+        assert!(!listener.events().iter().any(|event| matches!(event, IsoTpEvent::DataReceived(_))));
+    }
+
+    #[test]
+    fn test_block_size_end_to_end() {
+        let mut device = LoopbackDevice::<u8, MockFrame>::new(());
+        let listener = MockEventListener::default();
+        let receiver = SyncCanIsoTp::new(0, ADDRESS, device.sender(), Box::new(listener.clone()))
+            .with_block_size(4);
+        let mut writer = SyncCanIsoTp::new(0, ADDRESS.swapped(), device.sender(), Box::new(MockEventListener::default()));
+        assert!(device.register_listener("receiver".into(), Box::new(receiver.clone())));
+        assert!(device.register_listener("writer".into(), Box::new(writer.clone())));
+        device.sync_start(100);
+
+        // 1 first frame and 28 consecutive frames of 7 blocks
+        let data = (0..200).map(|v| v as u8).collect::<Vec<u8>>();
+        let handle = std::thread::spawn(move || writer.write(false, data));
+        let start = Instant::now();
+        while !handle.is_finished() && start.elapsed() < Duration::from_millis(1500) {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(handle.is_finished());
+        assert!(handle.join().unwrap().is_ok());
+        std::thread::sleep(Duration::from_millis(20));
+        device.close();
+
+        let expected = (0..200).map(|v| v as u8).collect::<Vec<u8>>();
+        assert!(listener.events().iter().any(|event| matches!(event, IsoTpEvent::DataReceived(v) if *v == expected)));
+        assert_eq!(
+            listener.events().iter().filter(|event| matches!(event, IsoTpEvent::Wait)).count(),
+            27
+        );
+    }
+
+    #[test]
+    fn test_send_retried() {
+        let (sender, receiver) = channel::<MockFrame>();
+        drop(receiver);
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(MockEventListener::default()))
+            .with_retry_policy(RetryPolicy { max_retries: 2, backoff: Duration::from_millis(5) });
+
+        // the send is retried twice then falls back to the error
+        let start = Instant::now();
+        assert!(matches!(iso_tp.write(false, vec![0x01, 0x02]), Err(IsoTpError::DeviceError)));
+        assert!(start.elapsed() >= Duration::from_millis(10));
+        assert!(!iso_tp.state_contains(IsoTpState::Sending));
+    }
+
+    #[test]
+    fn test_retransmit_failed() {
+        let write = |max_retries| {
+            // the first frame fails to transmit twice
+            let injector = ErrorInjector::default()
+                .with_rule(Target::Nth(1), Fault::TransmitFailed(TransmitFailure::ArbitrationLost))
+                .with_rule(Target::Nth(2), Fault::TransmitFailed(TransmitFailure::ArbitrationLost));
+            let mut device = LoopbackDevice::<u8, MockFrame>::new(())
+                .with_error_injector(injector);
+            let listener = MockEventListener::default();
+            let receiver = SyncCanIsoTp::new(0, ADDRESS, device.sender(), Box::new(listener.clone()));
+            let mut writer = SyncCanIsoTp::new(0, ADDRESS.swapped(), device.sender(), Box::new(MockEventListener::default()))
+                .with_retry_policy(RetryPolicy { max_retries, backoff: Duration::from_millis(1) });
+            assert!(device.register_listener("receiver".into(), Box::new(receiver.clone())));
+            assert!(device.register_listener("writer".into(), Box::new(writer.clone())));
+            device.sync_start(100);
+
+            let data = (0..20).map(|v| v as u8).collect::<Vec<u8>>();
+            let handle = std::thread::spawn(move || writer.write(false, data));
+            let start = Instant::now();
+            while !handle.is_finished() && start.elapsed() < Duration::from_millis(1000) {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            assert!(handle.is_finished());
+            let result = handle.join().unwrap();
+            std::thread::sleep(Duration::from_millis(20));
+            device.close();
+
+            (result, listener.events())
+        };
+
+        let expected = (0..20).map(|v| v as u8).collect::<Vec<u8>>();
+        let (result, events) = write(2);
+        assert!(result.is_ok());
+        assert!(events.iter().any(|event| matches!(event, IsoTpEvent::DataReceived(v) if *v == expected)));
+        // the retries are exhausted
+        let (result, events) = write(1);
+        assert!(result.is_err());
+        assert!(!events.iter().any(|event| matches!(event, IsoTpEvent::DataReceived(_))));
+    }
+
+    #[test]
+    fn test_receive_with_retry_policy() {
+        let data = (0..40).map(|v| v as u8).collect::<Vec<u8>>();
+        let (sender, receiver) = channel();
+        let listener = MockEventListener::default();
+        let mut iso_tp = SyncCanIsoTp::new(0, ADDRESS, sender, Box::new(listener.clone()))
+            .with_retry_policy(RetryPolicy { max_retries: 1, backoff: Duration::ZERO });
+        Listener::<u8, u32, MockFrame>::on_frame_received(&mut iso_tp, 0, &received_frames(&data));
+        assert!(matches!(listener.events().last(), Some(IsoTpEvent::DataReceived(v)) if *v == data));
+
+        // the flow control frame is retransmitted
+        assert_eq!(receiver.try_iter().count(), 1);
+        Listener::<u8, u32, MockFrame>::on_frame_transmit_failed(&mut iso_tp, 0, ADDRESS.tx_id, TransmitFailure::ArbitrationLost);
+        let frames = receiver.try_iter().collect::<Vec<_>>();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].data()[..3], [0x30, 0x00, ST_MIN_ISO15765_2]);
+        assert!(!iso_tp.state_contains(IsoTpState::Error));
+    }
+
+    #[test]
+    fn test_close_gracefully() {
+        let data = (0..40).map(|v| v as u8).collect::<Vec<u8>>();
+        let frames = received_frames(&data);
+        let open = || {
+            let device = LoopbackDevice::<u8, MockFrame>::new(());
+            let listener = MockEventListener::default();
+            let iso_tp = SyncCanIsoTp::new(0, ADDRESS, device.sender(), Box::new(listener.clone()));
+            device.register_listener("iso-tp".into(), Box::new(iso_tp.clone()));
+            // the first frame is received and the flow control frame is transmitted
+            device.inject(frames[0].clone());
+            device.receive();
+            device.transmit();
+            (device, iso_tp, listener)
+        };
+
+        // timed out
+        let (mut device, mut iso_tp, _) = open();
+        assert!(!iso_tp.close_gracefully(&mut device, "iso-tp", Duration::from_millis(20)));
+        assert!(device.listener_names().is_empty());
+        assert!(iso_tp.is_idle());
+
+        // completed
+        let (mut device, mut iso_tp, listener) = open();
+        let bus = device.clone();
+        let remains = frames[1..].to_vec();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            remains.into_iter().for_each(|frame| bus.inject(frame));
+            bus.receive();
+        });
+        assert!(iso_tp.close_gracefully(&mut device, "iso-tp", Duration::from_secs(1)));
+        handle.join().unwrap();
+        assert!(device.listener_names().is_empty());
+        assert!(matches!(listener.events().last(), Some(IsoTpEvent::DataReceived(v)) if *v == data));
     }
 }
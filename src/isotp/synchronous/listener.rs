@@ -1,14 +1,15 @@
 use std::fmt::Display;
-use isotp_rs::{IsoTpEvent, IsoTpFrame, IsoTpState, can::CanIsoTpFrame};
-use crate::device::Listener;
+use std::sync::atomic::Ordering;
+use isotp_rs::IsoTpState;
+use crate::device::{Listener, TransmitFailure};
 use crate::frame::Frame;
-use crate::isotp::SyncCanIsoTp;
+use crate::isotp::{Error, SyncCanIsoTp, IsoTpEvent, OnDecodeError, util};
 
 impl<C, Id, F> Listener<C, Id, F> for SyncCanIsoTp<C, F>
 where
-    C: Clone + Eq + Display,
+    C: Clone + Eq + Display + Send,
     Id: PartialEq<u32>,
-    F: Frame<Channel = C> + Clone {
+    F: Frame<Channel = C> + Clone + Send {
 
     fn on_frame_transmitting(&mut self, _: C, _: &F) {
 
@@ -18,12 +19,26 @@ where
             return;
         }
 
-        if id == self.address.tx_id ||
-            id == self.address.fid {
+        if self.is_write_id(&id) {
             self.state_remove(IsoTpState::Sending);
         }
     }
 
+    fn on_frame_transmit_failed(&mut self, channel: C, id: Id, reason: TransmitFailure) {
+        if channel != self.channel {
+            return;
+        }
+
+        if self.is_write_id(&id) {
+            self.logger.warn(format_args!("ISO-TP(CAN sync) - transmit failed: {:?}", reason));
+            if self.retransmit() {
+                return;
+            }
+            self.state_append(IsoTpState::Error);
+            self.iso_tp_event(IsoTpEvent::ErrorOccurred(Error::TransmitFailed(reason)));
+        }
+    }
+
     fn on_frame_received(&mut self, channel: C, frames: &[F]) {
         if channel != self.channel
             || self.state_contains(IsoTpState::Error) {
@@ -31,34 +46,38 @@ where
         }
 
         let rx_id = self.address.rx_id;
+        let mut iso_tp_frames = Vec::with_capacity(frames.len());
+        let mut error = None;
         for frame in frames {
-            if frame.id(false).as_raw() == rx_id {
-                log::debug!("ISO-TP(CAN sync) received: {:?} on {}", frame.data(), channel);
+            if frame.can_id().as_raw() == rx_id {
+                self.observe_raw(frame);
+                self.logger.debug(format_args!("ISO-TP(CAN sync) received: {:?} on {}", frame.data(), channel));
 
-                match CanIsoTpFrame::decode(frame.data()) {
-                    Ok(frame) => match frame {
-                        CanIsoTpFrame::SingleFrame { data } => {
-                            self.on_single_frame(data);
-                        }
-                        CanIsoTpFrame::FirstFrame { length, data } => {
-                            self.on_first_frame(length, data);
-                        }
-                        CanIsoTpFrame::ConsecutiveFrame { sequence, data } => {
-                            self.on_consecutive_frame(sequence, data);
-                        },
-                        CanIsoTpFrame::FlowControlFrame(ctx) => {
-                            self.on_flow_ctrl_frame(ctx);
-                        },
-                    },
+                match util::decode(frame.data(), None) {
+                    Ok(frame) => iso_tp_frames.push(frame),
                     Err(e) => {
-                        log::warn!("ISO-TP(CAN sync) - data convert to frame failed: {}", e);
-                        self.state_append(IsoTpState::Error);
-                        self.iso_tp_event(IsoTpEvent::ErrorOccurred(e));
-
-                        break;
+                        self.logger.warn(format_args!("ISO-TP(CAN sync) - data convert to frame failed: {}", e));
+                        match self.on_decode_error {
+                            OnDecodeError::Abort => {
+                                error = Some(e);
+                                break;
+                            },
+                            OnDecodeError::Skip => {},
+                            OnDecodeError::Count => {
+                                self.decode_errors.fetch_add(1, Ordering::Relaxed);
+                            },
+                        }
                     }
                 }
             }
         }
+
+        if !iso_tp_frames.is_empty() {
+            self.on_frames(&iso_tp_frames);
+        }
+        if let Some(e) = error {
+            self.state_append(IsoTpState::Error);
+            self.iso_tp_event(IsoTpEvent::ErrorOccurred(e));
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,125 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use isotp_rs::can::CanIsoTpFrame;
+use isotp_rs::error::Error as IsoTpError;
+use crate::frame::Frame;
+use crate::isotp::{SyncCanIsoTp, util};
+
+/// The progress of a [`WriteHandle`].
+#[derive(Debug, Clone)]
+pub enum WriteProgress {
+    /// The next frame is not writable yet, poll again later.
+    Pending,
+    /// All frames are sent.
+    Done,
+    /// The write is aborted, the channel is usable for the next write.
+    Error(IsoTpError),
+}
+
+/// A write of [`SyncCanIsoTp::write_nonblocking`] that is driven by [`Self::poll`] without sleeping.
+///
+/// It holds the frames not sent yet, the next frame is sent once the separation time(STmin)
+/// or the min gap elapses and the flow control allows.
+pub struct WriteHandle<C, F> {
+    iso_tp: SyncCanIsoTp<C, F>,
+    can_id: u32,
+    frames: VecDeque<CanIsoTpFrame>,
+    index: usize,
+    /// The time that the next frame is writable after, set when it is polled first.
+    writable_at: Option<Instant>,
+    error: Option<IsoTpError>,
+    #[cfg(feature = "metrics")]
+    started: (Instant, usize, usize),
+}
+
+impl<C: Clone, F: Frame<Channel = C> + Clone> SyncCanIsoTp<C, F> {
+    /// Start a write without blocking, the frames are sent by polling the returned handle
+    /// instead of sleeping between them, e.g. to drive the write by an event loop.
+    pub fn write_nonblocking(&self, functional: bool, data: Vec<u8>) -> Result<WriteHandle<C, F>, IsoTpError> {
+        let can_id = if functional { self.address.fid } else { self.address.tx_id };
+        self.logger.debug(format_args!("ISO-TP(CAN sync) - Sending to {:#X} without blocking: {:?}", can_id, data));
+        #[cfg(feature = "metrics")]
+        let (started, bytes) = (Instant::now(), data.len());
+        let frames = util::segment(&data, self.can_fd)?;
+
+        if let Some(mut context) = self.context_mut() {
+            context.tx.write_id = Some(can_id);
+            #[cfg(feature = "metrics")]
+            context.metrics.start_writing();
+        }
+
+        Ok(WriteHandle {
+            iso_tp: self.clone(),
+            can_id,
+            #[cfg(feature = "metrics")]
+            started: (started, bytes, frames.len()),
+            frames: frames.into(),
+            index: 0,
+            writable_at: None,
+            error: None,
+        })
+    }
+}
+
+impl<C: Clone, F: Frame<Channel = C>> WriteHandle<C, F> {
+    /// Send the next frame if it is writable, at most one frame is sent by a call.
+    ///
+    /// [`WriteProgress::Done`] and [`WriteProgress::Error`] are returned again once reached.
+    pub fn poll(&mut self) -> WriteProgress {
+        if let Some(e) = &self.error {
+            return WriteProgress::Error(e.clone());
+        }
+        if self.frames.is_empty() {
+            return WriteProgress::Done;
+        }
+
+        let writable_at = *self.writable_at
+            .get_or_insert_with(|| Instant::now() + self.iso_tp.write_gap(self.index));
+        match self.iso_tp.write_blocked() {
+            Ok(false) if Instant::now() >= writable_at => {},
+            Ok(_) => return WriteProgress::Pending,
+            Err(e) => return self.abort(e),
+        }
+
+        if let Some(frame) = self.frames.pop_front() {
+            if let Err(e) = self.iso_tp.send_frame(self.can_id, frame, self.iso_tp.padding) {
+                return self.abort(e);
+            }
+        }
+        self.index += 1;
+        self.writable_at = None;
+
+        match self.frames.is_empty() {
+            true => {
+                #[cfg(feature = "metrics")]
+                if let Some(mut context) = self.iso_tp.context_mut() {
+                    let (started, bytes, frame_len) = self.started;
+                    context.metrics.complete_writing(started, bytes, frame_len);
+                }
+                WriteProgress::Done
+            },
+            false => WriteProgress::Pending,
+        }
+    }
+
+    /// The count of the frames not sent yet.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// The time until the next frame is writable by the separation time, zero if it is not polled yet
+    /// or it is waiting for the flow control only.
+    pub fn wait_time(&self) -> Duration {
+        self.writable_at
+            .map(|v| v.saturating_duration_since(Instant::now()))
+            .unwrap_or_default()
+    }
+
+    fn abort(&mut self, e: IsoTpError) -> WriteProgress {
+        self.iso_tp.write_reset();
+        self.frames.clear();
+        self.error = Some(e.clone());
+        WriteProgress::Error(e)
+    }
+}
@@ -0,0 +1,687 @@
+use isotp_rs::{FlowControlContext, FlowControlState, FrameType};
+use isotp_rs::can::{CanIsoTpFrame, CAN_FRAME_MAX_SIZE, CONSECUTIVE_FRAME_SIZE, DEFAULT_PADDING, FIRST_FRAME_SIZE_2004, ISO_TP_MAX_LENGTH_2004, ISO_TP_MAX_LENGTH_2016, SINGLE_FRAME_SIZE_2004, SINGLE_FRAME_SIZE_2016};
+use isotp_rs::error::Error as IsoTpError;
+use crate::constant::CANFD_FRAME_MAX_SIZE;
+use crate::isotp::{Error, FirstFrameLength, IsoTpConfig};
+
+/// The max data size of single frame when using extended addressing(2004).
+pub const SINGLE_FRAME_SIZE_2004_EXTENDED: usize = SINGLE_FRAME_SIZE_2004 - 1;
+/// The max data size of single frame when using extended addressing(2016).
+pub const SINGLE_FRAME_SIZE_2016_EXTENDED: usize = SINGLE_FRAME_SIZE_2016 - 1;
+/// The max data size of single frame of the default [`IsoTpConfig::can_fd`],
+/// the escaped length(2016) is required beyond classic CAN.
+#[cfg(not(feature = "can-fd"))]
+pub const SINGLE_FRAME_MAX_SIZE: usize = SINGLE_FRAME_SIZE_2004;
+/// The max data size of single frame of the default [`IsoTpConfig::can_fd`],
+/// the escaped length(2016) is required beyond classic CAN.
+#[cfg(feature = "can-fd")]
+pub const SINGLE_FRAME_MAX_SIZE: usize = SINGLE_FRAME_SIZE_2016;
+/// The max data size of single frame when using extended addressing.
+pub const SINGLE_FRAME_MAX_SIZE_EXTENDED: usize = SINGLE_FRAME_MAX_SIZE - 1;
+/// The data size of first frame when using extended addressing(2004).
+pub const FIRST_FRAME_SIZE_2004_EXTENDED: usize = FIRST_FRAME_SIZE_2004 - 1;
+/// The data size of first frame with the escaped length(2016).
+pub const FIRST_FRAME_SIZE_ESCAPED: usize = FIRST_FRAME_SIZE_2004 - 4;
+/// The data size of first frame with the escaped length(2016) when using extended addressing.
+pub const FIRST_FRAME_SIZE_ESCAPED_EXTENDED: usize = FIRST_FRAME_SIZE_ESCAPED - 1;
+/// The max data size of consecutive frame when using extended addressing.
+pub const CONSECUTIVE_FRAME_SIZE_EXTENDED: usize = CONSECUTIVE_FRAME_SIZE - 1;
+
+/// New single frame from data.
+///
+/// * `data` - the single frame data
+/// * `address_extension` - the address extension(N_AE) that is prepended when encoding,
+///   it reduces the available data size by one.
+///
+/// With `can-fd`, up to [`SINGLE_FRAME_MAX_SIZE`] bytes are accepted, and the frame is encoded
+/// with the escaped length(byte0 low nibble is 0, the length in byte1) when exceeding classic CAN,
+/// see [`new_single_with`] to select CAN-FD at runtime.
+pub fn new_single<T: AsRef<[u8]>>(data: T, address_extension: Option<u8>) -> Result<CanIsoTpFrame, Error> {
+    new_single_with(data, &IsoTpConfig { address_extension, ..Default::default() })
+}
+
+/// New single frame from data by `config`, the empty data is accepted if [`IsoTpConfig::allow_empty_single`].
+pub fn new_single_with<T: AsRef<[u8]>>(data: T, config: &IsoTpConfig) -> Result<CanIsoTpFrame, Error> {
+    let data = data.as_ref();
+    let max_size = single_frame_size(config);
+
+    match data.len() {
+        0 if !config.allow_empty_single => Err(IsoTpError::EmptyPdu.into()),
+        v if v <= max_size => Ok(CanIsoTpFrame::SingleFrame { data: data.to_vec() }),
+        v => Err(IsoTpError::LengthOutOfRange(v).into()),
+    }
+}
+
+/// Encoding full multi-frame from original data.
+///
+/// * `data` - original data
+/// * `address_extension` - see [`new_single`]
+pub fn from_data<T: AsRef<[u8]>>(data: T, address_extension: Option<u8>) -> Result<Vec<CanIsoTpFrame>, Error> {
+    let config = IsoTpConfig { address_extension, ..Default::default() };
+    let mut results = Vec::new();
+    from_data_into(data, &config, &mut results, usize::MAX)?;
+
+    Ok(results)
+}
+
+/// Encoding full multi-frame from original data into `out` without allocating beyond a cap.
+///
+/// * `data` - original data
+/// * `config` - the address extension and the first frame length encoding
+/// * `out` - the frames are appended to it
+/// * `max_frames` - the max count of frames, [`Error::TooManyFrames`] is returned
+///   and nothing is appended when exceeded.
+pub fn from_data_into<T: AsRef<[u8]>>(
+    data: T,
+    config: &IsoTpConfig,
+    out: &mut impl Extend<CanIsoTpFrame>,
+    max_frames: usize,
+) -> Result<(), Error> {
+    let data = data.as_ref();
+    let (count, first_size) = frame_sizes(data.len(), config)?;
+    if count > max_frames {
+        return Err(Error::TooManyFrames { actual: count, max: max_frames });
+    }
+
+    segment_into(data, first_size, config, out);
+    Ok(())
+}
+
+/// Segment the data that a session writes by the 12-bit first frame length, the frames are not capped.
+pub(crate) fn segment(data: &[u8], can_fd: bool) -> Result<Vec<CanIsoTpFrame>, IsoTpError> {
+    let config = IsoTpConfig { first_frame_length: FirstFrameLength::Short, can_fd, ..Default::default() };
+    let (count, first_size) = frame_sizes(data.len(), &config)?;
+    let mut frames = Vec::with_capacity(count);
+    segment_into(data, first_size, &config, &mut frames);
+
+    Ok(frames)
+}
+
+fn segment_into(data: &[u8], first_size: Option<usize>, config: &IsoTpConfig, out: &mut impl Extend<CanIsoTpFrame>) {
+    let first_size = match first_size {
+        Some(v) => v,
+        None => {
+            out.extend(Some(CanIsoTpFrame::SingleFrame { data: data.to_vec() }));
+            return;
+        },
+    };
+
+    out.extend(Some(CanIsoTpFrame::FirstFrame {
+        length: data.len() as u32,
+        data: data[..first_size].to_vec(),
+    }));
+    out.extend(data[first_size..].chunks(consecutive_frame_size(config))
+        .enumerate()
+        .map(|(index, chunk)| CanIsoTpFrame::ConsecutiveFrame {
+            sequence: ((index + 1) & 0x0F) as u8,
+            data: chunk.to_vec(),
+        }));
+}
+
+/// Compute the count of frames that [`from_data_into`] produces for a payload, without building them.
+///
+/// * `payload_len` - the length of original data
+/// * `config` - the address extension and the first frame length encoding
+pub fn frame_count(payload_len: usize, config: &IsoTpConfig) -> Result<usize, Error> {
+    let (count, _) = frame_sizes(payload_len, config)?;
+    Ok(count)
+}
+
+/// Returns the frame count and the data size of the first frame, `None` if a single frame.
+fn frame_sizes(length: usize, config: &IsoTpConfig) -> Result<(usize, Option<usize>), IsoTpError> {
+    match length {
+        0 if !config.allow_empty_single => Err(IsoTpError::EmptyPdu),
+        0 => Ok((1, None)),
+        v if v <= single_frame_size(config) && !config.force_multi_frame => Ok((1, None)),
+        _ => {
+            // the PCI of 2 bytes, 6 bytes of the escaped length
+            let pci_size = match first_frame_escaped(config.first_frame_length, length)? {
+                true => 6,
+                false => 2,
+            };
+            let first_size = (frame_size(config) - pci_size - extension_size(config)).min(length);
+
+            Ok((1 + (length - first_size).div_ceil(consecutive_frame_size(config)), Some(first_size)))
+        },
+    }
+}
+
+/// Returns the max data size of a frame, [`CANFD_FRAME_MAX_SIZE`] if [`IsoTpConfig::can_fd`].
+#[inline]
+fn frame_size(config: &IsoTpConfig) -> usize {
+    match config.can_fd {
+        true => CANFD_FRAME_MAX_SIZE,
+        false => CAN_FRAME_MAX_SIZE,
+    }
+}
+
+#[inline]
+fn extension_size(config: &IsoTpConfig) -> usize {
+    config.address_extension.map_or(0, |_| 1)
+}
+
+/// The escaped length(byte1) is required beyond classic CAN.
+#[inline]
+fn single_frame_size(config: &IsoTpConfig) -> usize {
+    let pci_size = match config.can_fd {
+        true => 2,
+        false => 1,
+    };
+
+    frame_size(config) - pci_size - extension_size(config)
+}
+
+#[inline]
+fn consecutive_frame_size(config: &IsoTpConfig) -> usize {
+    frame_size(config) - 1 - extension_size(config)
+}
+
+/// Encode frame to data, the first frame length is encoded as [`FirstFrameLength::Auto`].
+///
+/// * `frame` - the iso-tp frame
+/// * `address_extension` - the address extension(N_AE) prepended as byte0
+/// * `padding` - the padding value when the length of return value is insufficient.
+pub fn encode(frame: CanIsoTpFrame, address_extension: Option<u8>, padding: Option<u8>) -> Vec<u8> {
+    encode_sized(frame, address_extension, padding, cfg!(feature = "can-fd"))
+}
+
+/// The same as [`encode`] but padded to the valid lengths of CAN-FD if `can_fd` instead of by the feature.
+pub(crate) fn encode_sized(frame: CanIsoTpFrame, address_extension: Option<u8>, padding: Option<u8>, can_fd: bool) -> Vec<u8> {
+    let escaped = matches!(&frame, CanIsoTpFrame::FirstFrame { length, .. } if *length as usize > ISO_TP_MAX_LENGTH_2004);
+    encode_frame(frame, address_extension, padding, escaped, can_fd)
+}
+
+/// Encode frame to data by configuration.
+///
+/// Returns [`IsoTpError::LengthOutOfRange`] when the first frame length is greater than 0xFFF
+/// and [`FirstFrameLength::Short`] is configured.
+pub fn encode_with(frame: CanIsoTpFrame, config: &IsoTpConfig) -> Result<Vec<u8>, Error> {
+    let escaped = match &frame {
+        CanIsoTpFrame::FirstFrame { length, .. } => first_frame_escaped(config.first_frame_length, *length as usize)?,
+        _ => false,
+    };
+
+    Ok(encode_frame(frame, config.address_extension, config.padding, escaped, config.can_fd))
+}
+
+/// Returns true if the first frame length should be encoded as the escaped form.
+fn first_frame_escaped(encoding: FirstFrameLength, length: usize) -> Result<bool, IsoTpError> {
+    if length > ISO_TP_MAX_LENGTH_2016 {
+        return Err(IsoTpError::LengthOutOfRange(length));
+    }
+
+    match encoding {
+        FirstFrameLength::Auto => Ok(length > ISO_TP_MAX_LENGTH_2004),
+        FirstFrameLength::Short => match length {
+            ..=ISO_TP_MAX_LENGTH_2004 => Ok(false),
+            v => Err(IsoTpError::LengthOutOfRange(v)),
+        },
+        FirstFrameLength::Escaped => Ok(true),
+    }
+}
+
+fn encode_frame(frame: CanIsoTpFrame, address_extension: Option<u8>, padding: Option<u8>, escaped: bool, can_fd: bool) -> Vec<u8> {
+    let mut result = Vec::with_capacity(CAN_FRAME_MAX_SIZE);
+    if let Some(ae) = address_extension {
+        result.push(ae);
+    }
+
+    match frame {
+        CanIsoTpFrame::SingleFrame { data } => {
+            if result.len() + 1 + data.len() > CAN_FRAME_MAX_SIZE {
+                result.push(FrameType::Single as u8);
+                result.push(data.len() as u8);
+            }
+            else {
+                result.push(FrameType::Single as u8 | data.len() as u8);
+            }
+            result.extend(data);
+        },
+        CanIsoTpFrame::FirstFrame { length, data } => {
+            if escaped {
+                result.push(FrameType::First as u8);
+                result.push(0x00);
+                result.extend(length.to_be_bytes());
+            }
+            else {
+                result.push(FrameType::First as u8 | ((length & 0x0F00) >> 8) as u8);
+                result.push((length & 0x00FF) as u8);
+            }
+            result.extend(data);
+        },
+        CanIsoTpFrame::ConsecutiveFrame { sequence, data } => {
+            result.push(FrameType::Consecutive as u8 | sequence);
+            result.extend(data);
+        },
+        CanIsoTpFrame::FlowControlFrame(context) => {
+            result.push(FrameType::FlowControl as u8 | context.state() as u8);
+            result.push(context.block_size());
+            result.push(context.st_min());
+        },
+    }
+
+    let size = padded_size(result.len(), can_fd);
+    if result.len() < size {
+        result.resize(size, padding.unwrap_or(DEFAULT_PADDING));
+    }
+
+    result
+}
+
+/// Decode frame from data.
+///
+/// * `data` - the data of a can-frame
+/// * `address_extension` - the byte0 is skipped as the address extension(N_AE) if `Some`
+pub fn decode<T: AsRef<[u8]>>(data: T, address_extension: Option<u8>) -> Result<CanIsoTpFrame, Error> {
+    let raw = data.as_ref();
+    let data = match address_extension {
+        Some(_) => raw.get(1..).unwrap_or_default(),
+        None => raw,
+    };
+    let invalid = || Error::from(IsoTpError::InvalidPdu(raw.to_vec()));
+
+    let byte0 = *data.first()
+        .ok_or(IsoTpError::EmptyPdu)?;
+    match FrameType::try_from(byte0)? {
+        FrameType::Single => {
+            let (offset, length) = match byte0 & 0x0F {
+                0 => (2, *data.get(1).ok_or_else(invalid)? as usize),
+                v => (1, v as usize),
+            };
+            match data.get(offset..offset + length) {
+                Some(data) if length > 0 => Ok(CanIsoTpFrame::SingleFrame { data: data.to_vec() }),
+                _ => Err(invalid()),
+            }
+        },
+        FrameType::First => {
+            let length = ((byte0 & 0x0F) as u32) << 8 | *data.get(1).ok_or_else(invalid)? as u32;
+            match length {
+                0 => match data.get(2..6) {
+                    Some(v) => Ok(CanIsoTpFrame::FirstFrame {
+                        length: u32::from_be_bytes([v[0], v[1], v[2], v[3]]),
+                        data: data[6..].to_vec(),
+                    }),
+                    None => Err(invalid()),
+                },
+                _ => Ok(CanIsoTpFrame::FirstFrame { length, data: data[2..].to_vec() }),
+            }
+        },
+        FrameType::Consecutive => Ok(CanIsoTpFrame::ConsecutiveFrame {
+            sequence: byte0 & 0x0F,
+            data: data[1..].to_vec(),
+        }),
+        FrameType::FlowControl => {
+            let state = match byte0 & 0x0F {
+                v @ 0x00..=0x02 => FlowControlState::try_from(v)?,
+                v => return Err(Error::InvalidFlowStatus(v)),
+            };
+            match data.get(1..3) {
+                Some(v) => Ok(CanIsoTpFrame::FlowControlFrame(FlowControlContext::new(state, v[0], v[1]))),
+                None => Err(invalid()),
+            }
+        },
+    }
+}
+
+/// Decode frame from data, and returns the trailing bytes beyond the declared length too, i.e. the padding.
+///
+/// * `data` - the data of a can-frame
+/// * `address_extension` - see [`decode`]
+/// * `remaining` - the count of data bytes that a consecutive frame is expected to carry,
+///   no trailing bytes of a consecutive frame are returned if `None`.
+///
+/// A first frame has no trailing bytes.
+pub fn decode_tail(data: &[u8], address_extension: Option<u8>, remaining: Option<usize>) -> Result<(CanIsoTpFrame, &[u8]), Error> {
+    let frame = decode(data, address_extension)?;
+    let extension = address_extension.map_or(0, |_| 1);
+    let offset = match &frame {
+        // the escaped length is in byte1
+        CanIsoTpFrame::SingleFrame { data: payload } => match data[extension] & 0x0F {
+            0 => 2 + payload.len(),
+            _ => 1 + payload.len(),
+        },
+        CanIsoTpFrame::FirstFrame { .. } => usize::MAX,
+        CanIsoTpFrame::ConsecutiveFrame { .. } => match remaining {
+            Some(v) => 1 + v,
+            None => usize::MAX,
+        },
+        CanIsoTpFrame::FlowControlFrame(_) => 3,
+    };
+    let offset = offset.saturating_add(extension);
+
+    Ok((frame, data.get(offset..).unwrap_or_default()))
+}
+
+/// Decode frame from data by `config`.
+///
+/// A frame that is shorter than its padded size(8 bytes of classic CAN) is rejected
+/// unless [`IsoTpConfig::accept_unpadded`], while [`decode`] always accepts it.
+///
+/// A first frame is sized to the max frame size(TX_DL) of the sender, so it is accepted at any
+/// valid length of CAN-FD, e.g. 16 or 24 bytes, and rejected at the other lengths even if unpadded is accepted.
+/// A frame beyond classic CAN is rejected unless [`IsoTpConfig::can_fd`].
+///
+/// If [`IsoTpConfig::allow_empty_single`], byte0 of 0x00 in a frame of classic CAN size is a single frame of no data,
+/// the escaped length(byte1) is only valid beyond classic CAN.
+pub fn decode_with<T: AsRef<[u8]>>(data: T, config: &IsoTpConfig) -> Result<CanIsoTpFrame, Error> {
+    let data = data.as_ref();
+    if !config.can_fd && data.len() > CAN_FRAME_MAX_SIZE {
+        return Err(IsoTpError::InvalidDataLength { actual: data.len(), expect: CAN_FRAME_MAX_SIZE }.into());
+    }
+    let size = padded_size(data.len(), config.can_fd);
+    if !config.accept_unpadded && data.len() < size {
+        return Err(IsoTpError::InvalidDataLength { actual: data.len(), expect: size }.into());
+    }
+
+    let pci = data.get(config.address_extension.map_or(0, |_| 1));
+    if config.allow_empty_single && data.len() <= CAN_FRAME_MAX_SIZE && pci == Some(&0x00) {
+        return Ok(CanIsoTpFrame::SingleFrame { data: Vec::new() });
+    }
+
+    match decode(data, config.address_extension)? {
+        CanIsoTpFrame::FirstFrame { .. } if data.len() != size =>
+            Err(IsoTpError::InvalidDataLength { actual: data.len(), expect: size }.into()),
+        frame => Ok(frame),
+    }
+}
+
+/// Returns the size of a frame that holds `length` bytes, the valid CAN-FD data lengths
+/// are used beyond classic CAN if `can_fd`.
+#[inline]
+fn padded_size(length: usize, can_fd: bool) -> usize {
+    match length {
+        ..=CAN_FRAME_MAX_SIZE => CAN_FRAME_MAX_SIZE,
+        _ if !can_fd => length,
+        9..=12 => 12,
+        13..=16 => 16,
+        17..=20 => 20,
+        21..=24 => 24,
+        25..=32 => 32,
+        33..=48 => 48,
+        49..=64 => 64,
+        _ => length,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constant::is_can_fd_build;
+    use super::*;
+
+    #[test]
+    #[cfg(not(feature = "can-fd"))]
+    fn test_single_with_address_extension() -> anyhow::Result<()> {
+        let data = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+        let frame = new_single(data, Some(0xF1))?;
+        assert_eq!(encode(frame, Some(0xF1), None), vec![0xF1, 0x06, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+
+        assert!(matches!(new_single([0x00; 7], Some(0xF1)), Err(Error::IsoTp(IsoTpError::LengthOutOfRange(7)))));
+        assert!(new_single([0x00; 7], None).is_ok());
+
+        let frames = from_data([0x00; 7], Some(0xF1))?;
+        assert!(matches!(frames.first(), Some(CanIsoTpFrame::FirstFrame { length: 7, data }) if data.len() == 5));
+        assert!(matches!(frames.get(1), Some(CanIsoTpFrame::ConsecutiveFrame { sequence: 1, data }) if data.len() == 2));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(feature = "can-fd"))]
+    fn test_from_data_into_max_frames() -> anyhow::Result<()> {
+        // 6 bytes in the first frame then 7 bytes per consecutive frame
+        let data = [0x00; 4095];
+        let mut frames = Vec::new();
+        assert!(matches!(
+            from_data_into(data, &Default::default(), &mut frames, 16),
+            Err(Error::TooManyFrames { actual: 586, max: 16 })
+        ));
+        assert!(frames.is_empty());
+
+        from_data_into(&data[..6 + 7 * 15], &Default::default(), &mut frames, 16)?;
+        assert_eq!(frames.len(), 16);
+        assert!(matches!(frames.last(), Some(CanIsoTpFrame::ConsecutiveFrame { sequence: 15, .. })));
+
+        assert_eq!(from_data(data, None)?.len(), 586);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(feature = "can-fd"))]
+    fn test_first_frame_length() -> anyhow::Result<()> {
+        let short = IsoTpConfig { first_frame_length: FirstFrameLength::Short, ..Default::default() };
+        let escaped = IsoTpConfig { first_frame_length: FirstFrameLength::Escaped, ..Default::default() };
+
+        let frame = CanIsoTpFrame::FirstFrame { length: 0xFFF, data: vec![0x01; 6] };
+        assert_eq!(encode_with(frame.clone(), &short)?, vec![0x1F, 0xFF, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01]);
+        assert_eq!(encode(frame, None, None), vec![0x1F, 0xFF, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01]);
+
+        let frame = CanIsoTpFrame::FirstFrame { length: 0x1000, data: vec![0x01; 2] };
+        assert_eq!(encode_with(frame.clone(), &escaped)?, vec![0x10, 0x00, 0x00, 0x00, 0x10, 0x00, 0x01, 0x01]);
+        assert_eq!(encode(frame.clone(), None, None), vec![0x10, 0x00, 0x00, 0x00, 0x10, 0x00, 0x01, 0x01]);
+        assert!(matches!(encode_with(frame, &short), Err(Error::IsoTp(IsoTpError::LengthOutOfRange(0x1000)))));
+
+        let mut frames = Vec::new();
+        from_data_into([0x00; 0xFFF], &escaped, &mut frames, usize::MAX)?;
+        assert!(matches!(frames.first(), Some(CanIsoTpFrame::FirstFrame { length: 0xFFF, data }) if data.len() == 2));
+        assert!(matches!(
+            from_data_into([0x00; 0x1000], &short, &mut frames, usize::MAX),
+            Err(Error::IsoTp(IsoTpError::LengthOutOfRange(0x1000)))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_frame_count() -> anyhow::Result<()> {
+        let extended = IsoTpConfig { address_extension: Some(0xF1), ..Default::default() };
+        let escaped = IsoTpConfig { first_frame_length: FirstFrameLength::Escaped, ..Default::default() };
+        for config in [IsoTpConfig::default(), extended, escaped.clone()] {
+            for length in [1, 6, 7, 8, 13, 14, 100, 0xFFF, 0x1000, 0x2000] {
+                let frames = from_data_into_vec(length, &config)?;
+                assert_eq!(frame_count(length, &config)?, frames, "length: {}, config: {:?}", length, config);
+            }
+        }
+
+        assert!(matches!(frame_count(0, &Default::default()), Err(Error::IsoTp(IsoTpError::EmptyPdu))));
+        #[cfg(not(feature = "can-fd"))]
+        {
+            assert_eq!(frame_count(7, &Default::default())?, 1);
+            // 6 + 7 * 2
+            assert_eq!(frame_count(20, &Default::default())?, 3);
+            // 2 + 7 * 2
+            assert_eq!(frame_count(16, &escaped)?, 3);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_force_multi_frame() -> anyhow::Result<()> {
+        let config = IsoTpConfig { force_multi_frame: true, ..Default::default() };
+        let mut frames = Vec::new();
+        from_data_into([0x01, 0x02, 0x03], &config, &mut frames, usize::MAX)?;
+        assert!(matches!(
+            frames.as_slice(),
+            [CanIsoTpFrame::FirstFrame { length: 3, data }] if data == &[0x01, 0x02, 0x03]
+        ));
+        assert_eq!(frame_count(3, &config)?, 1);
+
+        #[cfg(not(feature = "can-fd"))]
+        {
+            frames.clear();
+            from_data_into([0x00; 7], &config, &mut frames, usize::MAX)?;
+            assert!(matches!(
+                frames.as_slice(),
+                [CanIsoTpFrame::FirstFrame { length: 7, .. }, CanIsoTpFrame::ConsecutiveFrame { sequence: 1, .. }]
+            ));
+            assert_eq!(frame_count(7, &config)?, 2);
+        }
+
+        Ok(())
+    }
+
+    fn from_data_into_vec(length: usize, config: &IsoTpConfig) -> Result<usize, Error> {
+        let mut frames = Vec::new();
+        from_data_into(vec![0x00; length], config, &mut frames, usize::MAX)?;
+        Ok(frames.len())
+    }
+
+    #[test]
+    #[cfg(feature = "can-fd")]
+    fn test_can_fd_single() -> anyhow::Result<()> {
+        for (length, size) in [(8, 12), (20, 24), (62, 64)] {
+            let data = (0..length).map(|v| v as u8).collect::<Vec<_>>();
+            let encoded = encode(new_single(&data, None)?, None, None);
+            assert_eq!(encoded.len(), size);
+            assert_eq!(&encoded[..2], &[0x00, length as u8]);
+            assert_eq!(&encoded[2..2 + length], data.as_slice());
+            assert!(matches!(decode(&encoded, None)?, CanIsoTpFrame::SingleFrame { data: v } if v == data));
+        }
+        assert!(matches!(new_single([0x00; 63], None), Err(Error::IsoTp(IsoTpError::LengthOutOfRange(63)))));
+        // the classic form of short payloads, padded to 8 bytes instead of 64
+        let encoded = encode(new_single([0x3E, 0x80, 0x01], None)?, None, None);
+        assert_eq!(encoded, [0x03, 0x3E, 0x80, 0x01, DEFAULT_PADDING, DEFAULT_PADDING, DEFAULT_PADDING, DEFAULT_PADDING]);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "can-fd")]
+    fn test_can_fd_first_frame() -> anyhow::Result<()> {
+        for size in [8, 12, 16, 24, 32, 48, 64] {
+            let mut data = vec![0x10, 0x64];
+            data.extend((2..size).map(|v| v as u8));
+            assert!(matches!(
+                decode_with(&data, &Default::default())?,
+                CanIsoTpFrame::FirstFrame { length: 100, data: v } if v.len() == size - 2 && v == data[2..]
+            ));
+        }
+        // not a valid length of CAN-FD
+        let mut data = vec![0x10, 0x64];
+        data.resize(50, 0x00);
+        assert!(matches!(
+            decode_with(&data, &Default::default()),
+            Err(Error::IsoTp(IsoTpError::InvalidDataLength { actual: 50, expect: 64 }))
+        ));
+        let lenient = IsoTpConfig { accept_unpadded: true, ..Default::default() };
+        assert!(matches!(
+            decode_with(&data[..10], &lenient),
+            Err(Error::IsoTp(IsoTpError::InvalidDataLength { actual: 10, expect: 12 }))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode() -> anyhow::Result<()> {
+        let data = [0x01, 0x02, 0x03];
+        assert!(matches!(
+            decode(encode(new_single(data, Some(0xF1))?, Some(0xF1), None), Some(0xF1))?,
+            CanIsoTpFrame::SingleFrame { data: v } if v == data
+        ));
+        let frame = CanIsoTpFrame::FirstFrame { length: 0x1000, data: vec![0x01; FIRST_FRAME_SIZE_ESCAPED] };
+        assert!(matches!(
+            decode(encode(frame, None, None), None)?,
+            CanIsoTpFrame::FirstFrame { length: 0x1000, data } if data == vec![0x01; FIRST_FRAME_SIZE_ESCAPED]
+        ));
+        assert!(matches!(decode([0x05, 0x01], None), Err(Error::IsoTp(IsoTpError::InvalidPdu(_)))));
+
+        let frame = decode([0x31, 0x08, 0xF5], None)?;
+        assert!(matches!(
+            frame,
+            CanIsoTpFrame::FlowControlFrame(ctx)
+            if ctx.state() == FlowControlState::Wait && ctx.block_size() == 0x08 && ctx.st_min() == 0xF5
+        ));
+        assert!(matches!(decode([0x33, 0x00, 0x00], None), Err(Error::InvalidFlowStatus(0x03))));
+        assert!(matches!(decode([0x30, 0x00], None), Err(Error::IsoTp(IsoTpError::InvalidPdu(_)))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_unpadded() -> anyhow::Result<()> {
+        let data = [0x03, 0x01, 0x02, 0x03];
+        let lenient = IsoTpConfig { accept_unpadded: true, ..Default::default() };
+        assert!(matches!(
+            decode_with(data, &lenient)?,
+            CanIsoTpFrame::SingleFrame { data } if data == [0x01, 0x02, 0x03]
+        ));
+        assert!(matches!(
+            decode_with(data, &Default::default()),
+            Err(Error::IsoTp(IsoTpError::InvalidDataLength { actual: 4, expect: 8 }))
+        ));
+        assert!(matches!(
+            decode_with([0x03, 0x01, 0x02, 0x03, 0xAA, 0xAA, 0xAA, 0xAA], &Default::default())?,
+            CanIsoTpFrame::SingleFrame { data } if data == [0x01, 0x02, 0x03]
+        ));
+        // a first frame is never unpadded
+        assert!(matches!(
+            decode_with([0x10, 0x14, 0x01, 0x02], &lenient),
+            Err(Error::IsoTp(IsoTpError::InvalidDataLength { actual: 4, expect: 8 }))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_runtime_can_fd() -> anyhow::Result<()> {
+        let classic = IsoTpConfig { can_fd: false, ..Default::default() };
+        let fd = IsoTpConfig { can_fd: true, ..Default::default() };
+        let data = (0..100).map(|v| v as u8).collect::<Vec<_>>();
+
+        assert_eq!(frame_count(20, &classic)?, 3);
+        assert_eq!(frame_count(20, &fd)?, 1);
+        // a first frame of 62 bytes and a consecutive frame of 38 bytes
+        assert_eq!(frame_count(100, &fd)?, 2);
+        assert_eq!(frame_count(100, &IsoTpConfig { address_extension: Some(0xF1), ..fd.clone() })?, 2);
+        // the default of the build
+        assert_eq!(new_single(&data[..8], None).is_ok(), is_can_fd_build());
+        assert!(matches!(new_single_with(&data[..62], &classic), Err(Error::IsoTp(IsoTpError::LengthOutOfRange(62)))));
+
+        let frame = new_single_with(&data[..20], &fd)?;
+        let encoded = encode_with(frame, &fd)?;
+        assert_eq!((encoded.len(), &encoded[..2]), (24, &[0x00, 20][..]));
+        assert!(matches!(decode_with(&encoded, &fd)?, CanIsoTpFrame::SingleFrame { data: v } if v == data[..20]));
+        assert!(matches!(
+            decode_with(&encoded, &classic),
+            Err(Error::IsoTp(IsoTpError::InvalidDataLength { actual: 24, expect: 8 }))
+        ));
+
+        let mut frames = Vec::new();
+        from_data_into(&data, &fd, &mut frames, usize::MAX)?;
+        let encoded = frames.into_iter()
+            .map(|frame| encode_with(frame, &fd))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(encoded.iter().map(Vec::len).collect::<Vec<_>>(), vec![64, 48]);
+        // the classic short frames are padded to 8 bytes of both
+        assert_eq!(encode_with(new_single_with([0x3E, 0x00], &fd)?, &fd)?.len(), 8);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_single() -> anyhow::Result<()> {
+        assert!(matches!(new_single([], None), Err(Error::IsoTp(IsoTpError::EmptyPdu))));
+        assert!(matches!(frame_count(0, &Default::default()), Err(Error::IsoTp(IsoTpError::EmptyPdu))));
+
+        let config = IsoTpConfig { allow_empty_single: true, padding: Some(0xCC), ..Default::default() };
+        let frame = new_single_with([], &config)?;
+        assert!(matches!(&frame, CanIsoTpFrame::SingleFrame { data } if data.is_empty()));
+        assert_eq!(frame_count(0, &config)?, 1);
+
+        let encoded = encode_with(frame, &config)?;
+        assert_eq!(encoded, [0x00, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC]);
+        assert!(matches!(decode_with(&encoded, &config)?, CanIsoTpFrame::SingleFrame { data } if data.is_empty()));
+        // rejected by default
+        assert!(decode_with(&encoded, &Default::default()).is_err());
+
+        // with address extension
+        let config = IsoTpConfig { address_extension: Some(0xF1), ..config };
+        let encoded = encode_with(new_single_with([], &config)?, &config)?;
+        assert_eq!(encoded[..2], [0xF1, 0x00]);
+        assert!(matches!(decode_with(&encoded, &config)?, CanIsoTpFrame::SingleFrame { data } if data.is_empty()));
+
+        Ok(())
+    }
+}
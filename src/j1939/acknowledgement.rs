@@ -0,0 +1,100 @@
+use crate::j1939::{J1939, DataField, Message, Pdu, Pgn};
+
+/// PDU format of acknowledgment(PGN 59392).
+pub const ACKNOWLEDGEMENT_PDU_FORMAT: u8 = 0xE8;
+
+/// The control byte of acknowledgment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AckControl {
+    Positive,
+    Negative,
+    AccessDenied,
+    CannotRespond,
+}
+
+impl TryFrom<u8> for AckControl {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Positive),
+            1 => Ok(Self::Negative),
+            2 => Ok(Self::AccessDenied),
+            3 => Ok(Self::CannotRespond),
+            v => Err(v),
+        }
+    }
+}
+
+/// An acknowledgment(ACK/NACK) that an ECU replies to a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Acknowledgement {
+    pub control: AckControl,
+    pub group_function: u8,
+    /// The address of the acknowledged requester.
+    pub address: u8,
+    /// The acknowledged PGN.
+    pub pgn: Pgn,
+}
+
+impl Acknowledgement {
+    /// Decode the acknowledgment from a message of PGN 59392.
+    ///
+    /// # Returns
+    /// - `Some(ack)` if the message is an acknowledgment with a known control byte.
+    /// - `None` otherwise.
+    pub fn from_message(msg: &Message) -> Option<Self> {
+        let id = msg.id().as_j1939()?;
+        if id.pdu_format() != ACKNOWLEDGEMENT_PDU_FORMAT {
+            return None;
+        }
+
+        let data = match msg.pdu() {
+            Pdu::DataFiled(data) => data,
+            Pdu::NameField(_) => return None,
+        };
+
+        Self::from_data(data)
+    }
+
+    fn from_data(data: DataField) -> Option<Self> {
+        let data = data.to_be_bytes();
+        Some(Self {
+            control: AckControl::try_from(data[0]).ok()?,
+            group_function: data[1],
+            address: data[4],
+            pgn: Pgn::from_bits(u32::from_le_bytes([data[5], data[6], data[7], 0x00])),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::j1939::{Message, PduType};
+    use super::*;
+
+    #[test]
+    fn test_acknowledgement() {
+        // positive ACK of PGN 0xFEEC(vehicle identification) from 0x00 to 0xF9
+        let msg = Message::from_hex("18E8F900", "00FFFFFFF9ECFE00", PduType::Data);
+        let ack = Acknowledgement::from_message(&msg).unwrap();
+        assert_eq!(ack.control, AckControl::Positive);
+        assert_eq!(ack.group_function, 0xFF);
+        assert_eq!(ack.address, 0xF9);
+        assert_eq!(ack.pgn.into_bits(), 0xFEEC);
+
+        // negative ACK of PGN 0xEF00(proprietary A)
+        let msg = Message::from_hex("18E8FF00", "0102FFFFF900EF00", PduType::Data);
+        let ack = Acknowledgement::from_message(&msg).unwrap();
+        assert_eq!(ack.control, AckControl::Negative);
+        assert_eq!(ack.group_function, 0x02);
+        assert_eq!(ack.pgn.into_bits(), 0xEF00);
+
+        // unknown control byte
+        let msg = Message::from_hex("18E8FF00", "04FFFFFFF900EF00", PduType::Data);
+        assert!(Acknowledgement::from_message(&msg).is_none());
+        // not an acknowledgment
+        let msg = Message::from_hex("18EAFF00", "00FFFFFFF9ECFE00", PduType::Data);
+        assert!(Acknowledgement::from_message(&msg).is_none());
+    }
+}
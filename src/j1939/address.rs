@@ -1,6 +1,6 @@
 use std::fmt::{Display, Formatter};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Address {
     PrimaryEngineController,
     SecondaryEngineController,
@@ -261,8 +261,18 @@ impl Display for Address {
     }
 }
 
+#[cfg(feature = "defmt")]
+impl defmt::Format for Address {
+    fn format(&self, f: defmt::Formatter) {
+        match *self {
+            Self::Unknown(num) => defmt::write!(f, "Unknown({=u8})", num),
+            _ => defmt::write!(f, "{=u8:#04x}", u8::from(*self)),
+        }
+    }
+}
+
 /// Represents the source address.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum SourceAddress {
     /// No source address.
     None,
@@ -271,7 +281,7 @@ pub enum SourceAddress {
 }
 
 /// Represents the destination address.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum DestinationAddress {
     /// No destination address.
     None,
@@ -308,3 +318,28 @@ impl DestinationAddress {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use crate::Conversion;
+    use crate::j1939::J1939Id;
+    use super::*;
+
+    #[test]
+    fn test_hash() {
+        let mut handlers = HashMap::new();
+        handlers.insert(Address::from(0x00), "engine");
+        handlers.insert(Address::from(0x0B), "brakes");
+        handlers.insert(Address::PrimaryEngineController, "engine #1");
+        assert_eq!(handlers.len(), 2);
+        assert_eq!(handlers.get(&Address::from(0x00)), Some(&"engine #1"));
+        assert_eq!(handlers.get(&Address::Brakes), Some(&"brakes"));
+        assert!(!handlers.contains_key(&Address::from(0x03)));
+
+        let sources = HashSet::from([SourceAddress::Some(0x00), SourceAddress::Some(0x00), SourceAddress::None]);
+        assert_eq!(sources.len(), 2);
+        let ids = HashSet::from([J1939Id::from_bits(0x0CF00400), J1939Id::from_hex("0CF00400")]);
+        assert_eq!(ids.len(), 1);
+    }
+}
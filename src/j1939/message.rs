@@ -1,6 +1,31 @@
+use std::fmt::Write;
 use crate::Conversion;
+use crate::frame::{self, Direct};
 use crate::identifier::Id;
-use crate::j1939::{J1939Id, NameField, DataField, Pdu, PduType};
+use crate::j1939::{Address, DestinationAddress, J1939, J1939Id, NameField, DataField, Pdu, PduType, SourceAddress};
+
+/// The max hexadecimal digits of a 29-bit identifier.
+const ID_HEX_MAX_LEN: usize = 8;
+/// The max hexadecimal digits of the 8 PDU bytes.
+const PDU_HEX_MAX_LEN: usize = 16;
+
+/// A flat record of a [`Message`] for the data pipelines, e.g. a JSON or CSV row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct J1939Record {
+    pub timestamp: u64,
+    pub pgn: u32,
+    /// See [`crate::j1939::Pgn::name`].
+    pub pgn_name: Option<String>,
+    pub priority: u8,
+    pub source: u8,
+    /// The `Display` of the [`Address`], `None` if the address is unknown.
+    pub source_name: Option<String>,
+    /// The destination address of a PDU1 PGN, `None` of a PDU2(broadcast) PGN.
+    pub destination: Option<u8>,
+    /// The PDU bytes in the on-wire order, see [`Message::as_raw`].
+    pub data: [u8; 8],
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Message {
@@ -26,6 +51,12 @@ impl Message {
         }
     }
 
+    /// Constructs a new [`Message`] of a J1939 identifier, which is always valid unlike [`Self::from_parts`].
+    #[inline]
+    pub(crate) fn from_j1939(id: J1939Id, pdu: Pdu) -> Self {
+        Self { id: Id::J1939(id), pdu }
+    }
+
     /// Destructures the [`Message`] into its parts: a 29-bit J1939 identifier and pdu containing 64 bits of generic data.
     ///
     /// # Returns
@@ -43,44 +74,32 @@ impl Message {
     pub fn try_from_bits(hex_id: u32, hex_pdu: u64, pdu_type: PduType) -> Option<Self> {
         let id = Id::from_bits(hex_id, true);
         let pdu = match pdu_type {
-            PduType::Name => match NameField::try_from_bits(hex_pdu) {
-                Some(v) => Some(Pdu::NameField(v)),
-                None => None,
-            }
-            PduType::Data => match DataField::try_from_bits(hex_pdu) {
-                Some(v) => Some(Pdu::DataFiled(v)),
-                None => None,
-            },
+            PduType::Name => NameField::try_from_bits(hex_pdu).map(Pdu::NameField),
+            PduType::Data => DataField::try_from_bits(hex_pdu).map(Pdu::DataFiled),
         };
 
-        match pdu {
-            Some(pdu) => Some(Self { id, pdu }),
-            None => None,
-        }
+        pdu.map(|pdu| Self { id, pdu })
     }
 
     /// Constructs a new [`Message`] from hexadecimal string representations of its components.
+    ///
+    /// `None` is returned if `hex_id` exceeds 8 digits or `hex_pdu` exceeds 16 digits,
+    /// even if the leading digits are zeros, so that a pasted string is never truncated.
     #[inline]
     pub fn try_from_hex(hex_id: &str, hex_pdu: &str, pdu_type: PduType) -> Option<Self> {
+        if hex_id.len() > ID_HEX_MAX_LEN || hex_pdu.len() > PDU_HEX_MAX_LEN {
+            return None;
+        }
+
         let id = Id::try_from_hex(hex_id, true);
         match id {
             Some(id) => {
                 let pdu = match pdu_type {
-                    PduType::Name => match NameField::try_from_hex(hex_pdu) {
-                        Some(v) => Some(Pdu::NameField(v)),
-                        None => None,
-                    }
-                    PduType::Data => match DataField::try_from_hex(hex_pdu) {
-                        Some(v) => Some(Pdu::DataFiled(v)),
-                        None => None,
-                    },
+                    PduType::Name => NameField::try_from_hex(hex_pdu).map(Pdu::NameField),
+                    PduType::Data => DataField::try_from_hex(hex_pdu).map(Pdu::DataFiled),
                 };
 
-                match pdu {
-                    Some(pdu) => Some(Self { id, pdu }),
-                    None => None,
-                }
-
+                pdu.map(|pdu| Self { id, pdu })
             },
             None => None,
         }
@@ -145,7 +164,145 @@ impl Message {
     pub fn pdu(&self) -> Pdu {
         self.pdu
     }
+
+    /// Returns the raw 29-bit identifier and the 8 PDU bytes, e.g. to hand the message to other CAN libraries.
+    ///
+    /// The PDU bytes are in the on-wire order, the same as [`DataField::to_be_bytes`].
+    #[inline]
+    #[must_use]
+    pub fn as_raw(&self) -> (u32, [u8; 8]) {
+        let bits = match self.pdu {
+            Pdu::NameField(v) => v.into_bits(),
+            Pdu::DataFiled(v) => v.into_bits(),
+        };
+
+        (self.id.as_raw(), bits.to_be_bytes())
+    }
+
+    /// Flatten the message received at `timestamp` into a [`J1939Record`].
+    #[must_use]
+    pub fn to_record(&self, timestamp: u64) -> J1939Record {
+        let (raw, data) = self.as_raw();
+        let id = J1939Id::from_bits(raw);
+        let pgn = id.pgn();
+        let source = match id.source_address() {
+            SourceAddress::Some(v) => v,
+            SourceAddress::None => u8::MAX,
+        };
+        let source_name = match Address::from(source) {
+            Address::Unknown(_) => None,
+            address => Some(address.to_string()),
+        };
+        let destination = match pgn.destination_address() {
+            DestinationAddress::Some(v) => Some(v),
+            DestinationAddress::None => None,
+        };
+
+        J1939Record {
+            timestamp,
+            pgn: id.pgn_key().0,
+            pgn_name: pgn.name().map(String::from),
+            priority: id.priority(),
+            source,
+            source_name,
+            destination,
+            data,
+        }
+    }
+
+    /// Output the message as an `asc` line, the same as `Display` of an extended [`crate::frame::Frame`].
+    ///
+    /// # Arguments
+    /// - `channel`: The channel displayed.
+    /// - `timestamp`: The timestamp in milliseconds.
+    /// - `direct`: The direction of the message.
+    #[must_use]
+    pub fn to_asc_line(&self, channel: &str, timestamp: u64, direct: Direct) -> String {
+        let (id, data) = self.as_raw();
+        let data_str = data.iter()
+            .fold(String::new(), |mut out, &b| {
+                let _ = write!(out, "{b:02x} ");
+                out
+            });
+
+        format!("{:.3} {} {: >8x}{: <4} {} {} {: >2} {}",
+                timestamp as f64 / 1000.,
+                channel,
+                id,
+                "x",
+                frame::direct(direct),
+                "d",
+                data.len(),
+                data_str,
+        )
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use crate::frame::{Direct, Frame};
+    use crate::identifier::Id;
+    use crate::j1939::{Message, PduType};
+    use crate::mock::MockFrame;
+
+    #[test]
+    fn test_as_raw() {
+        let msg = Message::from_hex("18FEF100", "FFFF82DF1AFFFFFF", PduType::Data);
+        let (id, data) = msg.as_raw();
+        assert_eq!(id, 0x18FEF100);
+        assert_eq!(data, [0xFF, 0xFF, 0x82, 0xDF, 0x1A, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(Message::from_bits(id, u64::from_be_bytes(data), PduType::Data), msg);
+
+        // address claimed
+        let msg = Message::from_hex("18EEFF00", "0123456789ABCDEF", PduType::Name);
+        let (id, data) = msg.as_raw();
+        assert_eq!(id, 0x18EEFF00);
+        assert_eq!(Message::from_bits(id, u64::from_be_bytes(data), PduType::Name), msg);
+    }
+
+    #[test]
+    fn test_try_from_hex() {
+        let msg = Message::try_from_hex("18FEF100", "FFFF82DF1AFFFFFF", PduType::Data).unwrap();
+        assert_eq!(msg.as_raw().0, 0x18FEF100);
+        assert!(Message::try_from_hex("18FEF100", "FFFF82DF1AFFFFFFF", PduType::Data).is_none());
+        assert!(Message::try_from_hex("18FEF100", "0FFFF82DF1AFFFFFF", PduType::Name).is_none());
+        assert!(Message::try_from_hex("018FEF100", "FFFF82DF1AFFFFFF", PduType::Data).is_none());
+        assert!(Message::try_from_hex("18FEF100", "", PduType::Data).is_none());
+    }
 
+    #[test]
+    fn test_to_record() {
+        // EEC1 from the engine
+        let msg = Message::from_hex("0CF00400", "F07D7DA01AFFFFFF", PduType::Data);
+        let record = msg.to_record(1234);
+        assert_eq!(record.timestamp, 1234);
+        assert_eq!(record.pgn, 61444);
+        assert_eq!(record.pgn_name.as_deref(), Some("Electronic Engine Controller 1 | (EEC1)"));
+        assert_eq!(record.priority, 3);
+        assert_eq!(record.source, 0x00);
+        assert_eq!(record.source_name.as_deref(), Some("Primary Engine Controller | (CPC, ECM)"));
+        assert_eq!(record.destination, None);
+        assert_eq!(record.data, [0xF0, 0x7D, 0x7D, 0xA0, 0x1A, 0xFF, 0xFF, 0xFF]);
 
+        // PDU1 request from an unknown address to the engine
+        let record = Message::from_hex("18EA00F1", "00EE000000000000", PduType::Data).to_record(0);
+        assert_eq!(record.pgn, 0xEA00);
+        assert_eq!(record.pgn_name.as_deref(), Some("Request | (RQST)"));
+        assert_eq!((record.source, record.source_name), (0xF1, None));
+        assert_eq!(record.destination, Some(0x00));
+    }
+
+    #[test]
+    fn test_to_asc_line() {
+        let msg = Message::from_hex("18FEF100", "FFFF82DF1AFFFFFF", PduType::Data);
+        let mut frame = MockFrame::new(
+            Id::from_bits(0x18FEF100, true),
+            &[0xFF, 0xFF, 0x82, 0xDF, 0x1A, 0xFF, 0xFF, 0xFF]
+        ).unwrap();
+        frame.set_timestamp(Some(1234))
+            .set_channel(1)
+            .set_direct(Direct::Receive);
+
+        assert_eq!(msg.to_asc_line("1", 1234, Direct::Receive), format!("{}", frame));
+    }
+}
@@ -1,12 +1,20 @@
+mod acknowledgement;
 mod address;
 mod message;
 mod payload;
 mod pgn;
+mod request;
+mod signal;
+mod transport;
 
+pub use acknowledgement::*;
 pub use address::*;
 pub use message::*;
 pub use payload::*;
 pub use pgn::*;
+pub use request::*;
+pub use signal::*;
+pub use transport::*;
 
 use std::fmt::format;
 use bitfield_struct::bitfield;
@@ -23,6 +31,53 @@ pub trait J1939 {
         source_addr: u8,
     ) -> Option<Self> where Self: Sized;
 
+    /// Constructs a 29-bit J1939 identifier of a PGN, the inverse of [`J1939Id::pgn_bits`].
+    ///
+    /// The `destination` is the PDU specific bits of a PDU1 PGN, global(0xFF) if `None`.
+    ///
+    /// # Returns
+    /// - `None` if the priority is greater than 7, the PGN is out of 17 bits(the extended data page is reserved),
+    ///   the PDU specific bits of a PDU1 PGN are not 0, or a destination is given to a PDU2 PGN.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use can_type_rs::Conversion;
+    /// use can_type_rs::j1939::{J1939, J1939Id};
+    /// // EEC1(61444) from the engine
+    /// let eec1 = J1939Id::from_pgn(61444, 3, 0x00, None).unwrap();
+    /// assert_eq!(eec1.into_bits(), 0x0CF00400);
+    /// assert_eq!(eec1.pgn_bits(), 61444);
+    ///
+    /// // PDU1 request(59904) from 0xF9 to 0x00
+    /// let request = J1939Id::from_pgn(0xEA00, 6, 0xF9, Some(0x00)).unwrap();
+    /// assert_eq!(request.into_bits(), 0x18EA00F9);
+    /// assert_eq!(request.pgn_key(), (0xEA00, 0xF9));
+    /// // global
+    /// assert_eq!(J1939Id::from_pgn(0xEA00, 6, 0xF9, None).unwrap().pdu_specific(), 0xFF);
+    ///
+    /// assert!(J1939Id::from_pgn(61444, 8, 0x00, None).is_none());
+    /// assert!(J1939Id::from_pgn(61444, 3, 0x00, Some(0x21)).is_none());
+    /// assert!(J1939Id::from_pgn(0xEA21, 6, 0xF9, Some(0x00)).is_none());
+    /// assert!(J1939Id::from_pgn(0x2F004, 3, 0x00, None).is_none());
+    /// ```
+    fn from_pgn(pgn: u32, priority: u8, source: u8, destination: Option<u8>) -> Option<Self>
+    where
+        Self: Sized {
+        if priority > 0x07 || pgn > 0x1FFFF {
+            return None;
+        }
+
+        let [pdu_specific, pdu_format, data_page, _] = pgn.to_le_bytes();
+        let pdu_specific = match (pdu_format, destination) {
+            (..240, _) if pdu_specific != 0 => return None,
+            (..240, destination) => destination.unwrap_or(0xFF),
+            (_, None) => pdu_specific,
+            (_, Some(_)) => return None,
+        };
+
+        Self::from_raw_parts(priority, data_page != 0, pdu_format, pdu_specific, source)
+    }
+
     /// Returns the priority bits indicating the priority level.
     ///
     /// 0 = highest priority
@@ -39,6 +94,71 @@ pub trait J1939 {
 
     /// Returns the source address bits identifying the source of the data.
     fn source_address(&self) -> SourceAddress;
+
+    /// Returns true if the PDU format is PDU2(broadcast), i.e. not less than 240.
+    #[inline]
+    fn is_pdu2(&self) -> bool {
+        self.pdu_format() >= 240
+    }
+
+    /// Returns the group extension, the PDU specific bits of a PDU2 identifier.
+    ///
+    /// # Returns
+    /// - `Some(bits)` if the PDU format is PDU2.
+    /// - `None` if the PDU format is PDU1, the PDU specific bits are the destination address.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use can_type_rs::j1939::{J1939, J1939Id};
+    /// let pdu2 = J1939Id::from_raw_parts(3, false, 0xF0, 0x04, 0x00).unwrap();
+    /// let pdu1 = J1939Id::from_raw_parts(6, false, 0x00, 0x21, 0x00).unwrap();
+    ///
+    /// assert!(pdu2.is_pdu2());
+    /// assert_eq!(Some(0x04), pdu2.group_extension());
+    /// assert!(!pdu1.is_pdu2());
+    /// assert_eq!(None, pdu1.group_extension());
+    /// ```
+    #[inline]
+    fn group_extension(&self) -> Option<u8> {
+        match self.is_pdu2() {
+            true => Some(self.pdu_specific()),
+            false => None,
+        }
+    }
+
+    /// Returns `(pgn, source_address)` as a sort key that groups the messages by PGN, then by source.
+    ///
+    /// The priority bits are ignored and the destination of a PDU1 identifier is not a part of the PGN.
+    /// A missing source address sorts last(0xFF).
+    ///
+    /// # Examples
+    /// ```rust
+    /// use can_type_rs::j1939::{J1939, J1939Id};
+    /// // EEC1(0xF004) from 0x00 with priority 3 and 6, EEC2(0xF003) from 0x00
+    /// let mut ids = vec![
+    ///     J1939Id::from_raw_parts(3, false, 0xF0, 0x04, 0x00).unwrap(),
+    ///     J1939Id::from_raw_parts(3, false, 0xF0, 0x03, 0x00).unwrap(),
+    ///     J1939Id::from_raw_parts(6, false, 0xF0, 0x04, 0x00).unwrap(),
+    /// ];
+    /// ids.sort_by_key(|id| id.pgn_key());
+    ///
+    /// assert_eq!(ids[0].pgn_key(), (0xF003, 0x00));
+    /// assert_eq!(ids[1].pgn_key(), (0xF004, 0x00));
+    /// assert_eq!(ids[2].pgn_key(), (0xF004, 0x00));
+    /// assert_ne!(ids[1].priority(), ids[2].priority());
+    /// ```
+    #[inline]
+    fn pgn_key(&self) -> (u32, u8) {
+        let pgn = (self.data_page() as u32) << 16
+            | (self.pdu_format() as u32) << 8
+            | self.group_extension().unwrap_or_default() as u32;
+        let source = match self.source_address() {
+            SourceAddress::Some(v) => v,
+            SourceAddress::None => u8::MAX,
+        };
+
+        (pgn, source)
+    }
 }
 
 /// Bitfield representation of a 29-bit J1939 CAN identifier.
@@ -55,7 +175,7 @@ pub trait J1939 {
 /// | PDU specific bits      | 8           |
 /// | Source address bits    | 8           |
 #[bitfield(u32, order = Msb)]
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct J1939Id {
     #[bits(3)]
     _padding_bits: u8,
@@ -224,37 +344,90 @@ impl J1939 for J1939Id {
     ///
     /// 0 = highest priority
     #[inline]
-    #[must_use]
     fn priority(&self) -> u8 {
         self.priority_bits()
     }
 
     /// Returns the data page flag - 0 or 1
     #[inline]
-    #[must_use]
     fn data_page(&self) -> bool {
         self.data_page_bits()
     }
 
     /// Returns the PDU format bits specifying the Protocol Data Unit format.
     #[inline]
-    #[must_use]
     fn pdu_format(&self) -> u8 {
         self.pdu_format_bits()
     }
 
     /// Returns the PDU specific bits providing additional details about the PDU.
     #[inline]
-    #[must_use]
     fn pdu_specific(&self) -> u8 {
         self.pdu_specific_bits()
     }
 
     /// Returns the source address bits identifying the source of the data.
     #[inline]
-    #[must_use]
     fn source_address(&self) -> SourceAddress {
         SourceAddress::Some(self.source_address_bits())
     }
 }
 
+impl J1939Id {
+    /// Returns the reserved bit(R, bit 25 of the identifier), see [`Self::data_pages`].
+    ///
+    /// It is always 0 of J1939-21 before 2006, ISO 11783 and the later J1939-21 use it as the extended data page(EDP).
+    #[inline]
+    #[must_use]
+    pub fn reserved(&self) -> bool {
+        self.reserved_bits()
+    }
+
+    /// Returns `(extended_data_page, data_page)`, bit 25 and bit 24 of the identifier.
+    ///
+    /// | EDP | DP | Definition                              |
+    /// |-----|----|-----------------------------------------|
+    /// | 0   | 0  | J1939 page 0 PGNs                       |
+    /// | 0   | 1  | J1939 page 1 PGNs, e.g. NMEA 2000/ISOBUS |
+    /// | 1   | 0  | ISO 15765-3                             |
+    /// | 1   | 1  | reserved                                |
+    ///
+    /// # Examples
+    /// ```rust
+    /// use can_type_rs::Conversion;
+    /// use can_type_rs::j1939::{J1939, J1939Id};
+    /// // a PDU2 PGN of page 1 from 0x00
+    /// let id = J1939Id::from_bits(0x19FEF100);
+    /// assert_eq!(id.data_pages(), (false, true));
+    /// assert!(!id.reserved());
+    /// assert_eq!(id.priority(), 6);
+    /// assert_eq!(id.pgn_bits(), 0x1FEF1);
+    /// assert_eq!(id.extended_pgn_bits(), 0x1FEF1);
+    ///
+    /// let id = J1939Id::from_bits(0x1BFEF100);
+    /// assert_eq!(id.data_pages(), (true, true));
+    /// assert!(id.reserved());
+    /// // the PGN of J1939 ignores the reserved bit
+    /// assert_eq!(id.pgn_bits(), 0x1FEF1);
+    /// assert_eq!(id.extended_pgn_bits(), 0x3FEF1);
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn data_pages(&self) -> (bool, bool) {
+        (self.reserved_bits(), self.data_page_bits())
+    }
+
+    /// Returns the 18-bit PGN including the extended data page, see [`Self::data_pages`] and [`Self::pgn_bits`].
+    #[inline]
+    #[must_use]
+    pub fn extended_pgn_bits(&self) -> u32 {
+        (self.reserved_bits() as u32) << 17 | self.pgn_bits()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for J1939Id {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{=u32:08X}", self.into_bits())
+    }
+}
@@ -48,6 +48,38 @@ pub enum GroupExtension {
     Some(u8),
 }
 
+/// Represents the category of a PGN, e.g. to route the proprietary traffic to OEM-specific decoders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PgnCategory {
+    /// The PGNs except the proprietary ones.
+    Standard,
+    /// Proprietary A (0xEF00), a PDU1 PGN of any destination address.
+    ProprietaryA,
+    /// Proprietary B (0xFF00..=0xFFFF), a PDU2 PGN of any group extension.
+    ProprietaryB,
+}
+
+/// The PGN of Proprietary A.
+pub const PGN_PROPRIETARY_A: u32 = 0xEF00;
+/// The first PGN of Proprietary B.
+pub const PGN_PROPRIETARY_B: u32 = 0xFF00;
+
+/// Returns true if `pgn` is Proprietary A, the PDU specific bits(the destination address) are ignored.
+///
+/// Proprietary A2(0x1EF00) of the data page 1 is not.
+#[inline]
+#[must_use]
+pub const fn is_proprietary_a(pgn: u32) -> bool {
+    pgn & !0xFF == PGN_PROPRIETARY_A
+}
+
+/// Returns true if `pgn` is Proprietary B(0xFF00..=0xFFFF).
+#[inline]
+#[must_use]
+pub const fn is_proprietary_b(pgn: u32) -> bool {
+    pgn & !0xFF == PGN_PROPRIETARY_B
+}
+
 /// Bitfield representation of 18-bit Parameter Group Number (PGN).
 ///
 /// ### Repr: `u32`
@@ -149,6 +181,36 @@ impl Pgn {
         }
     }
 
+    /// Returns the name of a well-known PGN, the destination address of a PDU1 PGN is ignored.
+    ///
+    /// # Returns
+    /// - `Some(name)` in the form of `name | (acronym)`, the same as the `Display` of [`crate::j1939::Address`].
+    /// - `None` if the PGN is not well-known.
+    #[must_use]
+    pub fn name(&self) -> Option<&'static str> {
+        let pgn = match self.pdu_format() {
+            PduFormat::Pdu1(_) => self.into_bits() & !0xFF,
+            PduFormat::Pdu2(_) => self.into_bits(),
+        };
+
+        match pgn {
+            0xE800 => Some("Acknowledgment | (ACKM)"),
+            0xEA00 => Some("Request | (RQST)"),
+            0xEB00 => Some("Transport Protocol - Data Transfer | (TP.DT)"),
+            0xEC00 => Some("Transport Protocol - Connection Management | (TP.CM)"),
+            0xEE00 => Some("Address Claimed | (ACL)"),
+            0xF003 => Some("Electronic Engine Controller 2 | (EEC2)"),
+            0xF004 => Some("Electronic Engine Controller 1 | (EEC1)"),
+            0xFECA => Some("Active Diagnostic Trouble Codes | (DM1)"),
+            0xFEE5 => Some("Engine Hours, Revolutions | (HOURS)"),
+            0xFEEE => Some("Engine Temperature 1 | (ET1)"),
+            0xFEEF => Some("Engine Fluid Level/Pressure 1 | (EFL/P1)"),
+            0xFEF1 => Some("Cruise Control/Vehicle Speed | (CCVS)"),
+            0xFEF2 => Some("Fuel Economy (Liquid) | (LFE)"),
+            _ => None,
+        }
+    }
+
     /// Returns the destination address based on the parsed PDU format.
     ///
     /// # Returns
@@ -201,6 +263,16 @@ impl Pgn {
         }
     }
 
+    /// Returns the category of the PGN, see [`is_proprietary_a`] and [`is_proprietary_b`].
+    #[must_use]
+    pub fn category(&self) -> PgnCategory {
+        match self.into_bits() {
+            v if is_proprietary_a(v) => PgnCategory::ProprietaryA,
+            v if is_proprietary_b(v) => PgnCategory::ProprietaryB,
+            _ => PgnCategory::Standard,
+        }
+    }
+
     /// Determines the PDU assignment based on the parsed bits.
     ///
     /// # Returns
@@ -223,6 +295,55 @@ impl Pgn {
     }
 }
 
+/// An iterator over the PGNs of a PDU format, see [`pgns_for_format`].
+#[derive(Debug, Clone)]
+pub struct PgnIter {
+    base: Pgn,
+    pdu_specific: std::ops::RangeInclusive<u8>,
+}
+
+impl Iterator for PgnIter {
+    type Item = Pgn;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pdu_specific.next()
+            .map(|v| self.base.with_pdu_specific_bits(v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.pdu_specific.size_hint()
+    }
+}
+
+impl ExactSizeIterator for PgnIter {}
+
+/// Returns the PGNs that the PDU format `pdu_format` of the data page `data_page` represents.
+///
+/// The PDU specific bits of a PDU1 PGN are the destination address of an identifier, so a PDU1 format
+/// yields only the PGN of which the PDU specific bits are 0, while a PDU2 format yields 256 PGNs,
+/// one of each group extension.
+///
+/// # Examples
+/// ```rust
+/// use can_type_rs::Conversion;
+/// use can_type_rs::j1939::pgns_for_format;
+///
+/// assert_eq!(pgns_for_format(0xEA, false).map(Conversion::into_bits).collect::<Vec<_>>(), vec![0xEA00]);
+/// assert_eq!(pgns_for_format(0xFE, false).len(), 256);
+/// ```
+#[must_use]
+pub fn pgns_for_format(pdu_format: u8, data_page: bool) -> PgnIter {
+    let base = Pgn::new()
+        .with_data_page_bits(data_page)
+        .with_pdu_format_bits(pdu_format);
+    let pdu_specific = match base.pdu_format() {
+        PduFormat::Pdu1(_) => 0..=0,
+        PduFormat::Pdu2(_) => 0..=u8::MAX,
+    };
+
+    PgnIter { base, pdu_specific }
+}
+
 impl J1939Id {
     /// Computes the PGN bitfield value based on the 29-bit identifier fields.
     ///
@@ -250,3 +371,44 @@ impl J1939Id {
             .with_pdu_specific_bits(self.pdu_specific())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::Conversion;
+    use super::*;
+
+    #[test]
+    fn test_category() {
+        assert!(is_proprietary_a(0xEF00));
+        // of a destination address
+        assert!(is_proprietary_a(0xEF21));
+        assert!(!is_proprietary_a(0x1EF00));
+        assert!(is_proprietary_b(0xFF20));
+        assert!(is_proprietary_b(0xFFFF));
+        assert!(!is_proprietary_b(0xFEFF));
+        assert!(!is_proprietary_b(0x1FF20));
+
+        assert_eq!(Pgn::from_bits(0xEF00).category(), PgnCategory::ProprietaryA);
+        assert_eq!(Pgn::from_bits(0xFF20).category(), PgnCategory::ProprietaryB);
+        assert_eq!(Pgn::from_bits(0xFECA).category(), PgnCategory::Standard);
+        assert_eq!(J1939Id::from_bits(0x18EF21F1).pgn().category(), PgnCategory::ProprietaryA);
+    }
+
+    #[test]
+    fn test_pgns_for_format() {
+        // PDU1, the PDU specific bits are the destination address
+        let pgns = pgns_for_format(0xEA, false).collect::<Vec<_>>();
+        assert_eq!(pgns, vec![Pgn::from_bits(0xEA00)]);
+        assert_eq!(pgns[0].name(), Some("Request | (RQST)"));
+        assert_eq!(pgns_for_format(0xEF, true).map(Conversion::into_bits).collect::<Vec<_>>(), vec![0x1EF00]);
+
+        // PDU2, one of each group extension
+        let pgns = pgns_for_format(0xFE, false);
+        assert_eq!(pgns.len(), 256);
+        let pgns = pgns.collect::<Vec<_>>();
+        assert_eq!(pgns.first().copied().map(Conversion::into_bits), Some(0xFE00));
+        assert_eq!(pgns.last().copied().map(Conversion::into_bits), Some(0xFEFF));
+        assert!(pgns.iter().all(|pgn| pgn.is_broadcast()));
+        assert!(pgns.contains(&Pgn::from_bits(0xFECA)));
+    }
+}
@@ -0,0 +1,55 @@
+use crate::j1939::{DataField, J1939Id, Message, Pdu};
+
+/// PDU format of request(PGN 59904).
+pub const REQUEST_PDU_FORMAT: u8 = 0xEA;
+/// The payload size of request, the 3-byte requested PGN.
+pub const REQUEST_SIZE: usize = 3;
+
+/// Builder of the request messages that ask an ECU for a PGN.
+#[derive(Debug, Clone, Copy)]
+pub struct Request;
+
+impl Request {
+    /// Build a request of the `requested` PGN from `source` to `destination`(0xFF for global).
+    ///
+    /// The payload is the PGN in little-endian, the 5 bytes after it are padded with 0xFF.
+    /// Only the low 3 bits of `priority` are used.
+    pub fn for_pgn(requested: u32, source: u8, destination: u8, priority: u8) -> Message {
+        let id = J1939Id::new()
+            .with_priority_bits(priority & 0x07)
+            .with_pdu_format_bits(REQUEST_PDU_FORMAT)
+            .with_pdu_specific_bits(destination)
+            .with_source_address_bits(source);
+
+        let mut data = [0xFF; 8];
+        data[..REQUEST_SIZE].copy_from_slice(&requested.to_le_bytes()[..REQUEST_SIZE]);
+        let pdu = Pdu::DataFiled(DataField::from_bits(u64::from_be_bytes(data)));
+
+        Message::from_j1939(id, pdu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::j1939::{J1939, Pdu};
+    use super::*;
+
+    #[test]
+    fn test_for_pgn() {
+        let msg = Request::for_pgn(0xFEEC, 0xF9, 0x00, 6);
+        let id = msg.id().as_j1939().unwrap();
+        assert_eq!(id.pdu_format(), REQUEST_PDU_FORMAT);
+        assert_eq!(id.pdu_specific(), 0x00);
+        assert_eq!(id.source_address_bits(), 0xF9);
+        assert_eq!(id.priority(), 6);
+        assert_eq!(id.into_bits(), 0x18EA00F9);
+
+        match msg.pdu() {
+            Pdu::DataFiled(data) => assert_eq!(
+                data.to_be_bytes(),
+                [0xEC, 0xFE, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]
+            ),
+            Pdu::NameField(_) => panic!("unexpected pdu"),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use crate::j1939::{J1939, Message};
+
+/// A scaled signal(SPN) of a parameter group.
+///
+/// The bits are little-endian as J1939, `start_bit` 0 is the least significant bit of the first data byte.
+/// The physical value is `raw * scale + offset`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signal {
+    pub name: String,
+    pub start_bit: u8,
+    /// The bit length, 1..=64.
+    pub length: u8,
+    pub scale: f64,
+    pub offset: f64,
+    pub unit: String,
+}
+
+impl Signal {
+    pub fn new(name: impl Into<String>, start_bit: u8, length: u8, scale: f64, offset: f64, unit: impl Into<String>) -> Self {
+        Self { name: name.into(), start_bit, length, scale, offset, unit: unit.into() }
+    }
+
+    /// Extract the physical value from the 8 data bytes, `None` if the signal is out of the data.
+    pub fn decode(&self, data: [u8; 8]) -> Option<f64> {
+        let end = self.start_bit as u32 + self.length as u32;
+        if self.length == 0 || end > u64::BITS {
+            return None;
+        }
+
+        let raw = u64::from_le_bytes(data) >> self.start_bit;
+        let raw = match self.length as u32 {
+            u64::BITS => raw,
+            v => raw & ((1 << v) - 1),
+        };
+
+        Some(raw as f64 * self.scale + self.offset)
+    }
+}
+
+/// The signals keyed by PGN, the programmatic counterpart of a DBC-like definition.
+#[derive(Debug, Clone, Default)]
+pub struct SignalMap {
+    signals: HashMap<u32, Vec<Signal>>,
+}
+
+impl SignalMap {
+    /// Add a signal of `pgn`, the destination address of a PDU1 PGN must be 0.
+    pub fn with_signal(mut self, pgn: u32, signal: Signal) -> Self {
+        self.signals.entry(pgn)
+            .or_default()
+            .push(signal);
+        self
+    }
+
+    /// Returns the signals of `pgn`.
+    pub fn signals(&self, pgn: u32) -> &[Signal] {
+        self.signals.get(&pgn)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Extract every signal of the message's PGN as `(name, value)` in order of addition.
+    ///
+    /// The signals out of the data are skipped, and nothing is returned for a non-J1939 message.
+    pub fn decode(&self, msg: &Message) -> Vec<(String, f64)> {
+        let (pgn, _) = match msg.id().as_j1939() {
+            Some(id) => id.pgn_key(),
+            None => return Vec::new(),
+        };
+        let (_, data) = msg.as_raw();
+
+        self.signals(pgn)
+            .iter()
+            .filter_map(|signal| signal.decode(data).map(|v| (signal.name.clone(), v)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::j1939::{Message, PduType};
+    use super::*;
+
+    /// Electronic Engine Controller 1.
+    const EEC1: u32 = 0xF004;
+
+    #[test]
+    fn test_decode() {
+        let map = SignalMap::default()
+            .with_signal(EEC1, Signal::new("ActualEnginePercentTorque", 16, 8, 1., -125., "%"))
+            .with_signal(EEC1, Signal::new("EngineSpeed", 24, 16, 0.125, 0., "rpm"));
+
+        let msg = Message::from_hex("0CF00400", "F07D91803E00FFFF", PduType::Data);
+        assert_eq!(
+            map.decode(&msg),
+            vec![("ActualEnginePercentTorque".to_owned(), 20.), ("EngineSpeed".to_owned(), 2000.)]
+        );
+
+        // other PGNs
+        let msg = Message::from_hex("18FEF100", "FFFF82DF1AFFFFFF", PduType::Data);
+        assert!(map.decode(&msg).is_empty());
+        // out of the data
+        assert_eq!(Signal::new("Invalid", 60, 8, 1., 0., "").decode([0xFF; 8]), None);
+        assert_eq!(Signal::new("All", 0, 64, 1., 0., "").decode([0x01, 0, 0, 0, 0, 0, 0, 0]), Some(1.));
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::VecDeque;
+use crate::j1939::{DataField, J1939, J1939Id, Message, Pdu, Pgn};
+
+/// PDU format of transport protocol connection management(TP.CM).
+pub const TP_CM_PDU_FORMAT: u8 = 0xEC;
+/// PDU format of transport protocol data transfer(TP.DT).
+pub const TP_DT_PDU_FORMAT: u8 = 0xEB;
+/// TP.CM control byte of request to send.
+pub const TP_CM_RTS: u8 = 16;
+/// TP.CM control byte of clear to send.
+pub const TP_CM_CTS: u8 = 17;
+/// TP.CM control byte of end of message acknowledgment.
+pub const TP_CM_END_OF_MSG_ACK: u8 = 19;
+/// TP.CM control byte of broadcast announce message.
+pub const TP_CM_BAM: u8 = 32;
+/// TP.CM control byte of connection abort.
+pub const TP_CM_ABORT: u8 = 255;
+/// The data size of a TP.DT packet.
+pub const TP_DT_DATA_SIZE: usize = 7;
+/// The max message size of transport protocol, 255 packets.
+pub const TP_MAX_SIZE: usize = 255 * TP_DT_DATA_SIZE;
+/// The default max count of simultaneous open sessions.
+pub const TP_DEFAULT_MAX_SESSIONS: usize = 16;
+/// The priority of transport protocol frames.
+pub const TP_PRIORITY: u8 = 7;
+/// The default priority of the messages of a single frame.
+const DEFAULT_PRIORITY: u8 = 6;
+/// The global destination address.
+const GLOBAL_ADDRESS: u8 = 0xFF;
+
+/// A message reassembled from the transport protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TpMessage {
+    pub source: u8,
+    pub destination: u8,
+    pub pgn: Pgn,
+    pub data: Vec<u8>,
+}
+
+impl TpMessage {
+    /// Segment the message into the frames to transmit.
+    ///
+    /// It is not a method of [`Message`], which carries at most the 8 bytes of a single frame,
+    /// the longer payload of a PGN to transmit is held by a [`TpMessage`].
+    ///
+    /// A payload of at most 8 bytes is a single frame of the PGN. A longer payload is announced by
+    /// a TP.CM `BAM` to global if `use_bam`, else by a TP.CM `RTS` to the destination,
+    /// then followed by the TP.DT packets of sequence 1..=n, the unused bytes are padded with 0xFF.
+    ///
+    /// The CM/DT handshake is not handled, the packets of a `RTS` should be sent after the `CTS` is received.
+    /// Returns `None` if the payload exceeds [`TP_MAX_SIZE`], i.e. the 255 packets that a TP.CM announces,
+    /// no frame of it is transmittable.
+    pub fn into_tp_frames(&self, use_bam: bool) -> Option<Vec<Message>> {
+        let size = self.data.len();
+        if size > TP_MAX_SIZE {
+            return None;
+        }
+
+        let [pgn_0, pgn_1, pgn_2, _] = self.pgn.into_bits().to_le_bytes();
+        if size <= 8 {
+            let pdu_specific = match pgn_1 {
+                ..240 => self.destination,
+                _ => pgn_0,
+            };
+            let id = J1939Id::new()
+                .with_priority_bits(DEFAULT_PRIORITY)
+                .with_data_page_bits(pgn_2 & 0x01 != 0)
+                .with_pdu_format_bits(pgn_1)
+                .with_pdu_specific_bits(pdu_specific)
+                .with_source_address_bits(self.source);
+            return Some(vec![Self::message(id, &self.data)]);
+        }
+
+        let destination = if use_bam { GLOBAL_ADDRESS } else { self.destination };
+        let tp_id = |pdu_format| J1939Id::new()
+            .with_priority_bits(TP_PRIORITY)
+            .with_pdu_format_bits(pdu_format)
+            .with_pdu_specific_bits(destination)
+            .with_source_address_bits(self.source);
+
+        let packets = size.div_ceil(TP_DT_DATA_SIZE);
+        let [size_l, size_h] = (size as u16).to_le_bytes();
+        let control = if use_bam { TP_CM_BAM } else { TP_CM_RTS };
+        // the max packets of a CTS are not limited by a RTS
+        let announcement = [control, size_l, size_h, packets as u8, 0xFF, pgn_0, pgn_1, pgn_2];
+
+        let mut results = Vec::with_capacity(packets + 1);
+        results.push(Self::message(tp_id(TP_CM_PDU_FORMAT), &announcement));
+        results.extend(self.data.chunks(TP_DT_DATA_SIZE)
+            .enumerate()
+            .map(|(index, chunk)| {
+                let mut data = vec![index as u8 + 1];
+                data.extend_from_slice(chunk);
+                Self::message(tp_id(TP_DT_PDU_FORMAT), &data)
+            }));
+
+        Some(results)
+    }
+
+    /// Build a message of `data` that is padded to 8 bytes with 0xFF.
+    fn message(id: J1939Id, data: &[u8]) -> Message {
+        let mut bytes = [0xFF; 8];
+        bytes[..data.len()].copy_from_slice(data);
+        let pdu = Pdu::DataFiled(DataField::from_bits(u64::from_be_bytes(bytes)));
+
+        Message::from_j1939(id, pdu)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TpSession {
+    source: u8,
+    destination: u8,
+    pgn: Pgn,
+    size: usize,
+    packets: u8,
+    sequence: u8,
+    data: Vec<u8>,
+}
+
+/// Reassemble the messages of J1939 transport protocol(BAM and CM/DT) from received frames.
+///
+/// The reassembler is passive, it does not respond `CTS` nor `EndOfMsgAck` to a `RTS`.
+#[derive(Debug, Clone)]
+pub struct TpReassembler {
+    sessions: VecDeque<TpSession>,
+    sources: Option<Vec<u8>>,
+    max_sessions: usize,
+}
+
+impl Default for TpReassembler {
+    fn default() -> Self {
+        Self {
+            sessions: Default::default(),
+            sources: None,
+            max_sessions: TP_DEFAULT_MAX_SESSIONS,
+        }
+    }
+}
+
+impl TpReassembler {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Only reassemble the sessions from `sources`, the others are ignored entirely.
+    pub fn with_source_filter(mut self, sources: &[u8]) -> Self {
+        self.sources = Some(sources.to_vec());
+        self
+    }
+
+    /// Limit the count of simultaneous open sessions, the oldest one is evicted on overflow.
+    pub fn with_max_sessions(mut self, max_sessions: usize) -> Self {
+        self.max_sessions = max_sessions.max(1);
+        self
+    }
+
+    /// Returns the count of open sessions.
+    #[inline]
+    pub fn sessions(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Process a received frame, returns the message when its last packet is received.
+    pub fn on_frame(&mut self, id: J1939Id, data: &[u8]) -> Option<TpMessage> {
+        let source = id.source_address_bits();
+        if let Some(sources) = &self.sources {
+            if !sources.contains(&source) {
+                return None;
+            }
+        }
+
+        let destination = id.pdu_specific();
+        match id.pdu_format() {
+            TP_CM_PDU_FORMAT => {
+                self.on_connection_management(source, destination, data);
+                None
+            },
+            TP_DT_PDU_FORMAT => self.on_data_transfer(source, destination, data),
+            _ => None,
+        }
+    }
+
+    fn on_connection_management(&mut self, source: u8, destination: u8, data: &[u8]) {
+        match data.first() {
+            Some(&TP_CM_RTS) | Some(&TP_CM_BAM) => {
+                if data.len() < 8 {
+                    log::warn!("J1939 TP - invalid announcement: {:?}", data);
+                    return;
+                }
+
+                let size = u16::from_le_bytes([data[1], data[2]]) as usize;
+                let packets = data[3];
+                if !(TP_DT_DATA_SIZE + 2..=TP_MAX_SIZE).contains(&size)
+                    || size.div_ceil(TP_DT_DATA_SIZE) != packets as usize {
+                    log::warn!("J1939 TP - invalid size: {} of {} packets", size, packets);
+                    return;
+                }
+
+                self.remove(source, destination);
+                if self.sessions.len() >= self.max_sessions {
+                    if let Some(session) = self.sessions.pop_front() {
+                        log::warn!("J1939 TP - too many sessions, evict the session from {:#04x} to {:#04x}",
+                            session.source, session.destination);
+                    }
+                }
+                self.sessions.push_back(TpSession {
+                    source,
+                    destination,
+                    pgn: Pgn::from_bits(u32::from_le_bytes([data[5], data[6], data[7], 0x00])),
+                    size,
+                    packets,
+                    sequence: 1,
+                    data: Vec::with_capacity(packets as usize * TP_DT_DATA_SIZE),
+                });
+            },
+            Some(&TP_CM_ABORT) => {
+                self.remove(source, destination);
+            },
+            _ => {},
+        }
+    }
+
+    fn on_data_transfer(&mut self, source: u8, destination: u8, data: &[u8]) -> Option<TpMessage> {
+        let index = self.position(source, destination)?;
+        let session = &mut self.sessions[index];
+
+        let (&sequence, data) = data.split_first()?;
+        if sequence != session.sequence {
+            log::warn!("J1939 TP - invalid sequence: {}, expect: {}", sequence, session.sequence);
+            self.sessions.remove(index);
+            return None;
+        }
+
+        session.data.extend(data.iter().take(TP_DT_DATA_SIZE));
+        if sequence < session.packets {
+            session.sequence += 1;
+            return None;
+        }
+
+        let mut session = self.sessions.remove(index)?;
+        session.data.truncate(session.size);
+
+        Some(TpMessage {
+            source: session.source,
+            destination: session.destination,
+            pgn: session.pgn,
+            data: session.data,
+        })
+    }
+
+    #[inline]
+    fn position(&self, source: u8, destination: u8) -> Option<usize> {
+        self.sessions.iter()
+            .position(|s| s.source == source && s.destination == destination)
+    }
+
+    #[inline]
+    fn remove(&mut self, source: u8, destination: u8) {
+        if let Some(index) = self.position(source, destination) {
+            self.sessions.remove(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::j1939::J1939Id;
+    use super::*;
+
+    const GLOBAL: u8 = 0xFF;
+
+    fn bam(source: u8, size: u16) -> (J1939Id, Vec<u8>) {
+        let id = J1939Id::from_raw_parts(7, false, TP_CM_PDU_FORMAT, GLOBAL, source).unwrap();
+        let [size_l, size_h] = size.to_le_bytes();
+        let packets = (size as usize).div_ceil(TP_DT_DATA_SIZE) as u8;
+        // PGN 0xFEE3
+        (id, vec![TP_CM_BAM, size_l, size_h, packets, 0xFF, 0xE3, 0xFE, 0x00])
+    }
+
+    fn packet(source: u8, sequence: u8, data: &[u8]) -> (J1939Id, Vec<u8>) {
+        let id = J1939Id::from_raw_parts(7, false, TP_DT_PDU_FORMAT, GLOBAL, source).unwrap();
+        let mut result = vec![sequence];
+        result.extend_from_slice(data);
+        result.resize(8, 0xFF);
+        (id, result)
+    }
+
+    #[test]
+    fn test_source_filter() {
+        let mut reassembler = TpReassembler::new()
+            .with_source_filter(&[0x00]);
+        let payload = (0..17).collect::<Vec<u8>>();
+
+        for source in [0x00, 0x03, 0x0B] {
+            let (id, data) = bam(source, payload.len() as u16);
+            assert!(reassembler.on_frame(id, &data).is_none());
+        }
+        assert_eq!(reassembler.sessions(), 1);
+
+        let mut messages = Vec::new();
+        for (index, chunk) in payload.chunks(TP_DT_DATA_SIZE).enumerate() {
+            for source in [0x00, 0x03, 0x0B] {
+                let (id, data) = packet(source, index as u8 + 1, chunk);
+                messages.extend(reassembler.on_frame(id, &data));
+            }
+        }
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].source, 0x00);
+        assert_eq!(messages[0].destination, GLOBAL);
+        assert_eq!(messages[0].pgn.into_bits(), 0xFEE3);
+        assert_eq!(messages[0].data, payload);
+        assert_eq!(reassembler.sessions(), 0);
+    }
+
+    #[test]
+    fn test_into_tp_frames() {
+        let message = TpMessage {
+            source: 0x00,
+            destination: 0x21,
+            pgn: Pgn::from_bits(0xFEE3),
+            data: (0..17).collect(),
+        };
+
+        let frames = message.into_tp_frames(true).unwrap();
+        assert_eq!(frames.len(), 4);
+        let (id, data) = frames[0].as_raw();
+        assert_eq!(id, 0x1CECFF00);
+        assert_eq!(data, [TP_CM_BAM, 17, 0, 3, 0xFF, 0xE3, 0xFE, 0x00]);
+        let sequences = frames[1..].iter()
+            .map(|frame| frame.as_raw())
+            .inspect(|(id, _)| assert_eq!(*id, 0x1CEBFF00))
+            .map(|(_, data)| data[0])
+            .collect::<Vec<_>>();
+        assert_eq!(sequences, [1, 2, 3]);
+        assert_eq!(frames[3].as_raw().1, [3, 14, 15, 16, 0xFF, 0xFF, 0xFF, 0xFF]);
+
+        // reassembled to the global
+        let mut reassembler = TpReassembler::new();
+        let reassembled = frames.iter()
+            .filter_map(|frame| {
+                let (_, data) = frame.as_raw();
+                reassembler.on_frame(frame.id().as_j1939().unwrap(), &data)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(reassembled, [TpMessage { destination: GLOBAL, ..message.clone() }]);
+
+        let frames = message.into_tp_frames(false).unwrap();
+        assert_eq!(frames[0].as_raw(), (0x1CEC2100, [TP_CM_RTS, 17, 0, 3, 0xFF, 0xE3, 0xFE, 0x00]));
+        assert_eq!(frames.len(), 4);
+
+        // a single frame of the PGN
+        let message = TpMessage { data: vec![0x01, 0x02], ..message };
+        assert_eq!(message.into_tp_frames(true).unwrap()[0].as_raw(), (0x18FEE300, [0x01, 0x02, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]));
+        let message = TpMessage { data: vec![0x00; TP_MAX_SIZE + 1], ..message };
+        assert!(message.into_tp_frames(true).is_none());
+    }
+
+    #[test]
+    fn test_max_sessions() {
+        let mut reassembler = TpReassembler::new()
+            .with_max_sessions(2);
+        for source in [0x00, 0x03, 0x0B] {
+            let (id, data) = bam(source, 9);
+            reassembler.on_frame(id, &data);
+        }
+        assert_eq!(reassembler.sessions(), 2);
+
+        // the oldest session is evicted
+        let (id, data) = packet(0x00, 1, &[0x00; 7]);
+        assert!(reassembler.on_frame(id, &data).is_none());
+        let (id, data) = packet(0x00, 2, &[0x00; 2]);
+        assert!(reassembler.on_frame(id, &data).is_none());
+
+        let (id, data) = packet(0x0B, 1, &[0x00; 7]);
+        assert!(reassembler.on_frame(id, &data).is_none());
+        let (id, data) = packet(0x0B, 2, &[0x01; 2]);
+        assert!(matches!(reassembler.on_frame(id, &data), Some(m) if m.source == 0x0B && m.data.len() == 9));
+        assert_eq!(reassembler.sessions(), 1);
+    }
+}
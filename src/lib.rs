@@ -1,3 +1,4 @@
+pub mod asc;
 pub mod constant;
 pub mod frame;
 pub mod identifier;
@@ -5,6 +6,10 @@ pub mod j1939;
 pub mod device;
 #[cfg(feature = "isotp-rs")]
 pub mod isotp;
+#[cfg(feature = "obd")]
+pub mod obd;
+#[cfg(test)]
+mod mock;
 
 pub trait Conversion
 where
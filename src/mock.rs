@@ -0,0 +1,258 @@
+use std::cell::Cell;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+use crate::device::Listener;
+use crate::frame::{Direct, ErrorClass, Frame};
+use crate::identifier::Id;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct MockFrame {
+    pub(crate) id: u32,
+    pub(crate) extended: bool,
+    pub(crate) data: Vec<u8>,
+    pub(crate) timestamp: u64,
+    pub(crate) channel: u8,
+    pub(crate) direct: Direct,
+    pub(crate) can_fd: bool,
+    pub(crate) remote: bool,
+    pub(crate) bitrate_switch: bool,
+    pub(crate) error_frame: bool,
+    pub(crate) esi: bool,
+    pub(crate) rx_sequence: Option<u64>,
+    pub(crate) error_class: Option<ErrorClass>,
+}
+
+thread_local! {
+    static CONSTRUCTIONS: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+impl MockFrame {
+    /// Fail the constructions of frames on the current thread after `count` successful ones.
+    pub(crate) fn fail_after(count: usize) {
+        CONSTRUCTIONS.set(Some(count));
+    }
+}
+
+impl Frame for MockFrame {
+    type Channel = u8;
+
+    fn new(id: impl Into<Id>, data: &[u8]) -> Option<Self> {
+        match CONSTRUCTIONS.get() {
+            Some(0) => return None,
+            Some(v) => CONSTRUCTIONS.set(Some(v - 1)),
+            None => {},
+        }
+
+        let id = id.into();
+        match data.len() {
+            ..=64 => Some(Self {
+                id: id.as_raw(),
+                extended: id.is_extended(),
+                data: data.to_vec(),
+                can_fd: data.len() > 8,
+                ..Default::default()
+            }),
+            _ => None,
+        }
+    }
+
+    fn new_remote(id: impl Into<Id>, len: usize) -> Option<Self> {
+        let mut frame = Self::new(id, &vec![0; len])?;
+        frame.remote = true;
+        Some(frame)
+    }
+
+    fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    fn set_timestamp(&mut self, value: Option<u64>) -> &mut Self {
+        self.timestamp = value.unwrap_or_default();
+        self
+    }
+
+    fn rx_sequence(&self) -> Option<u64> {
+        self.rx_sequence
+    }
+
+    fn set_rx_sequence(&mut self, value: Option<u64>) -> &mut Self {
+        self.rx_sequence = value;
+        self
+    }
+
+    fn id(&self, _: bool) -> Id {
+        Id::from_bits(self.id, self.extended)
+    }
+
+    fn is_can_fd(&self) -> bool {
+        self.can_fd
+    }
+
+    fn set_can_fd(&mut self, value: bool) -> &mut Self {
+        self.can_fd = value;
+        self
+    }
+
+    fn is_remote(&self) -> bool {
+        self.remote
+    }
+
+    fn is_extended(&self) -> bool {
+        self.extended
+    }
+
+    fn direct(&self) -> Direct {
+        self.direct
+    }
+
+    fn set_direct(&mut self, direct: Direct) -> &mut Self {
+        self.direct = direct;
+        self
+    }
+
+    fn is_bitrate_switch(&self) -> bool {
+        self.bitrate_switch
+    }
+
+    fn set_bitrate_switch(&mut self, value: bool) -> &mut Self {
+        self.bitrate_switch = value;
+        self
+    }
+
+    fn is_error_frame(&self) -> bool {
+        self.error_frame
+    }
+
+    fn set_error_frame(&mut self, value: bool) -> &mut Self {
+        self.error_frame = value;
+        self
+    }
+
+    fn error_class(&self) -> Option<ErrorClass> {
+        self.error_class
+    }
+
+    fn is_esi(&self) -> bool {
+        self.esi
+    }
+
+    fn set_esi(&mut self, value: bool) -> &mut Self {
+        self.esi = value;
+        self
+    }
+
+    fn channel(&self) -> Self::Channel {
+        self.channel
+    }
+
+    fn set_channel(&mut self, value: Self::Channel) -> &mut Self {
+        self.channel = value;
+        self
+    }
+
+    fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn dlc(&self) -> Option<usize> {
+        match self.data.len() {
+            ..=8 => Some(self.data.len()),
+            9..=12 => Some(9),
+            13..=16 => Some(10),
+            17..=20 => Some(11),
+            21..=24 => Some(12),
+            25..=32 => Some(13),
+            33..=48 => Some(14),
+            49..=64 => Some(15),
+            _ => None,
+        }
+    }
+
+    fn length(&self) -> usize {
+        self.data.len()
+    }
+}
+
+impl Display for MockFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        <dyn Frame<Channel = u8> as Display>::fmt(self, f)
+    }
+}
+
+/// Records all transmitted and received frames.
+pub(crate) struct MockListener<F> {
+    pub(crate) transmitting: Arc<Mutex<Vec<F>>>,
+    pub(crate) transmitted: Arc<Mutex<Vec<u32>>>,
+    pub(crate) received: Arc<Mutex<Vec<F>>>,
+}
+
+impl<F> Clone for MockListener<F> {
+    fn clone(&self) -> Self {
+        Self {
+            transmitting: Arc::clone(&self.transmitting),
+            transmitted: Arc::clone(&self.transmitted),
+            received: Arc::clone(&self.received),
+        }
+    }
+}
+
+impl<F> Default for MockListener<F> {
+    fn default() -> Self {
+        Self {
+            transmitting: Default::default(),
+            transmitted: Default::default(),
+            received: Default::default(),
+        }
+    }
+}
+
+impl<F: Clone> MockListener<F> {
+    pub(crate) fn received(&self) -> Vec<F> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+impl<C, F: Clone + Send> Listener<C, u32, F> for MockListener<F> {
+    fn on_frame_transmitting(&mut self, _: C, frame: &F) {
+        self.transmitting.lock().unwrap().push(frame.clone());
+    }
+
+    fn on_frame_transmitted(&mut self, _: C, id: u32) {
+        self.transmitted.lock().unwrap().push(id);
+    }
+
+    fn on_frame_received(&mut self, _: C, frames: &[F]) {
+        self.received.lock().unwrap().extend_from_slice(frames);
+    }
+}
+
+#[cfg(feature = "isotp-rs")]
+pub(crate) use isotp::*;
+
+#[cfg(feature = "isotp-rs")]
+mod isotp {
+    use std::sync::{Arc, Mutex};
+    use crate::isotp::{IsoTpEvent, IsoTpEventListener};
+
+    /// Records all received ISO-TP events.
+    #[derive(Clone, Default)]
+    pub(crate) struct MockEventListener {
+        pub(crate) events: Arc<Mutex<Vec<IsoTpEvent>>>,
+    }
+
+    impl MockEventListener {
+        pub(crate) fn events(&self) -> Vec<IsoTpEvent> {
+            self.events.lock().unwrap().clone()
+        }
+    }
+
+    impl IsoTpEventListener for MockEventListener {
+        fn clear_buffer(&mut self) {
+            self.events.lock().unwrap().clear();
+        }
+
+        fn on_iso_tp_event(&mut self, event: IsoTpEvent) {
+            self.events.lock().unwrap().push(event);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! OBD-II(SAE J1979) responses that are reassembled by ISO-TP.
+
+/// Show current data.
+pub const MODE_CURRENT_DATA: u8 = 0x01;
+/// Engine coolant temperature, °C.
+pub const PID_COOLANT_TEMPERATURE: u8 = 0x05;
+/// Engine speed, rpm.
+pub const PID_ENGINE_RPM: u8 = 0x0C;
+/// Vehicle speed, km/h.
+pub const PID_VEHICLE_SPEED: u8 = 0x0D;
+
+/// The offset of the positive response service identifier to the request mode.
+const POSITIVE_RESPONSE_OFFSET: u8 = 0x40;
+
+/// A positive OBD-II response.
+///
+/// # Examples
+/// ```rust
+/// use can_type_rs::obd::{ObdResponse, PID_ENGINE_RPM};
+///
+/// let response = ObdResponse::parse(&[0x41, 0x0C, 0x1A, 0xF8]).unwrap();
+/// assert_eq!((response.mode(), response.pid()), (0x01, Some(PID_ENGINE_RPM)));
+/// assert_eq!(response.engine_rpm(), Some(1726.));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObdResponse {
+    mode: u8,
+    pid: Option<u8>,
+    data: Vec<u8>,
+}
+
+impl ObdResponse {
+    /// Parse the payload of a positive response, `None` if it is a negative response(0x7F),
+    /// not of the modes 0x01..=0x0A, or the PID is missing.
+    ///
+    /// The responses of mode 0x03, 0x04, 0x07 and 0x0A carry no PID.
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        let (&service, rest) = payload.split_first()?;
+        let mode = service.checked_sub(POSITIVE_RESPONSE_OFFSET)?;
+        match mode {
+            0x03 | 0x04 | 0x07 | 0x0A => Some(Self { mode, pid: None, data: rest.to_vec() }),
+            0x01..=0x0A => {
+                let (&pid, data) = rest.split_first()?;
+                Some(Self { mode, pid: Some(pid), data: data.to_vec() })
+            },
+            _ => None,
+        }
+    }
+
+    /// The request mode(service) of the response, e.g. 0x01 of a 0x41 response.
+    #[inline]
+    pub fn mode(&self) -> u8 {
+        self.mode
+    }
+
+    /// The PID of the response.
+    #[inline]
+    pub fn pid(&self) -> Option<u8> {
+        self.pid
+    }
+
+    /// The data bytes after the PID.
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The engine speed of [`PID_ENGINE_RPM`] in rpm, `(256 * A + B) / 4`.
+    pub fn engine_rpm(&self) -> Option<f64> {
+        match self.current_data(PID_ENGINE_RPM)? {
+            [a, b, ..] => Some(u16::from_be_bytes([*a, *b]) as f64 / 4.),
+            _ => None,
+        }
+    }
+
+    /// The vehicle speed of [`PID_VEHICLE_SPEED`] in km/h, `A`.
+    pub fn vehicle_speed(&self) -> Option<u8> {
+        self.current_data(PID_VEHICLE_SPEED)?
+            .first()
+            .copied()
+    }
+
+    /// The engine coolant temperature of [`PID_COOLANT_TEMPERATURE`] in °C, `A - 40`.
+    pub fn coolant_temperature(&self) -> Option<i16> {
+        self.current_data(PID_COOLANT_TEMPERATURE)?
+            .first()
+            .map(|&v| v as i16 - 40)
+    }
+
+    /// Returns the data if it is the current data of `pid`.
+    #[inline]
+    fn current_data(&self, pid: u8) -> Option<&[u8]> {
+        match (self.mode, self.pid) {
+            (MODE_CURRENT_DATA, Some(v)) if v == pid => Some(&self.data),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engine_rpm() {
+        let response = ObdResponse::parse(&[0x41, 0x0C, 0x1A, 0xF8]).unwrap();
+        assert_eq!(response.mode(), MODE_CURRENT_DATA);
+        assert_eq!(response.pid(), Some(PID_ENGINE_RPM));
+        assert_eq!(response.data(), [0x1A, 0xF8]);
+        assert_eq!(response.engine_rpm(), Some(1726.));
+        assert_eq!(response.vehicle_speed(), None);
+        // truncated
+        assert_eq!(ObdResponse::parse(&[0x41, 0x0C, 0x1A]).unwrap().engine_rpm(), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(ObdResponse::parse(&[0x41, 0x0D, 0x32]).unwrap().vehicle_speed(), Some(50));
+        assert_eq!(ObdResponse::parse(&[0x41, 0x05, 0x28]).unwrap().coolant_temperature(), Some(0));
+
+        // DTCs without PID
+        let dtc = ObdResponse::parse(&[0x43, 0x01, 0x01, 0x43]).unwrap();
+        assert_eq!((dtc.mode(), dtc.pid()), (0x03, None));
+        assert_eq!(dtc.data(), [0x01, 0x01, 0x43]);
+
+        // negative response
+        assert_eq!(ObdResponse::parse(&[0x7F, 0x01, 0x12]), None);
+        assert_eq!(ObdResponse::parse(&[0x01, 0x0C]), None);
+        assert_eq!(ObdResponse::parse(&[0x41]), None);
+        assert_eq!(ObdResponse::parse(&[]), None);
+    }
+}